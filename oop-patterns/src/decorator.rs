@@ -20,7 +20,7 @@ pub struct Milk<'a> {
 }
 
 impl Milk<'_> {
-    pub fn new(coffee: &dyn Coffee) -> Milk {
+    pub fn new(coffee: &dyn Coffee) -> Milk<'_> {
         Milk { coffee }
     }
 }
@@ -40,7 +40,7 @@ pub struct Sugar<'a> {
 }
 
 impl Sugar<'_> {
-    pub fn new(coffee: &dyn Coffee) -> Sugar {
+    pub fn new(coffee: &dyn Coffee) -> Sugar<'_> {
         Sugar { coffee }
     }
 }
@@ -0,0 +1,36 @@
+// Magnus formula coefficients, accurate within 0.1 °C for -45..60 °C.
+const MAGNUS_A: f32 = 17.62;
+const MAGNUS_B: f32 = 243.12;
+
+// Dew point in °C for a temperature in °C and relative humidity in percent.
+pub fn dew_point(temperature: f32, humidity: f32) -> f32 {
+    // ln(0) is undefined, very dry air is treated as 1 %.
+    let humidity = humidity.clamp(1.0, 100.0);
+    let gamma = (humidity / 100.0).ln() + MAGNUS_A * temperature / (MAGNUS_B + temperature);
+    MAGNUS_B * gamma / (MAGNUS_A - gamma)
+}
+
+// Canadian humidex: how hot humid air feels, in °C.
+pub fn humidex(temperature: f32, humidity: f32) -> f32 {
+    let dew_point = dew_point(temperature, humidity) + 273.15;
+    let vapour_pressure = 6.11 * (5417.753 * (1.0 / 273.16 - 1.0 / dew_point)).exp();
+    temperature + 0.5555 * (vapour_pressure - 10.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dew_point() {
+        assert!((dew_point(30.0, 70.0) - 23.9).abs() < 0.1);
+        assert!((dew_point(20.0, 100.0) - 20.0).abs() < 0.01);
+        assert!(dew_point(20.0, 0.0).is_finite());
+    }
+
+    #[test]
+    fn test_humidex() {
+        assert!((humidex(30.0, 70.0) - 41.2).abs() < 0.2);
+        assert!(humidex(20.0, 30.0) < 21.0);
+    }
+}
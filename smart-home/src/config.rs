@@ -0,0 +1,674 @@
+use std::{
+    collections::HashMap,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+};
+
+use log::{LevelFilter, Log, Metadata, Record};
+use miette::Diagnostic;
+use thiserror::Error;
+
+// Read unless another file is given with `--config` or `SMART_HOME_CONFIG`.
+pub const DEFAULT_CONFIG_FILE: &str = "settings/smart-home.conf";
+pub const ENV_PREFIX: &str = "SMART_HOME_";
+
+// Older binaries read each of these from its own file, `settings/<name>`. Such a file
+// is still used when nothing else sets the value, with a warning to move it.
+pub const LEGACY_SETTINGS_DIR: &str = "settings";
+pub const LEGACY_SETTINGS: &[&str] = &[
+    "addr",
+    "house_addr",
+    "light_addr",
+    "lock_addr",
+    "thermometer_addr",
+    "thermometer_udp_addr",
+    "workers",
+];
+
+pub const LOG_LEVEL: Setting =
+    Setting::new("log_level", "info", "off, error, warn, info or debug").with_kind(Kind::Level);
+
+#[derive(Debug, Error, Diagnostic)]
+pub enum ConfigError {
+    #[error("Cannot read config file {path}: {source}")]
+    #[diagnostic(code(smart_home::config::unreadable))]
+    Unreadable { path: PathBuf, source: io::Error },
+
+    #[error("{0}: expected `key = value` or `[section]`")]
+    #[diagnostic(code(smart_home::config::syntax))]
+    Syntax(Source),
+
+    #[error("Unknown setting `{name}` in {origin}")]
+    #[diagnostic(code(smart_home::config::unknown_setting))]
+    UnknownSetting { name: String, origin: Source },
+
+    #[error("Missing value for {0}")]
+    #[diagnostic(code(smart_home::config::missing_value))]
+    MissingValue(String),
+
+    #[error("Invalid value `{value}` for `{name}` from {origin}: {reason}")]
+    #[diagnostic(code(smart_home::config::invalid_value))]
+    InvalidValue {
+        name: String,
+        value: String,
+        origin: Source,
+        reason: String,
+    },
+}
+
+// Where a value comes from, so errors can point at it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    Default,
+    File { path: PathBuf, line: usize },
+    Env(String),
+    Flag(String),
+    Legacy(PathBuf),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "the defaults"),
+            Source::File { path, line } => write!(f, "{}:{}", path.display(), line),
+            Source::Env(name) => write!(f, "environment variable {}", name),
+            Source::Flag(name) => write!(f, "flag {}", name),
+            Source::Legacy(path) => write!(f, "legacy file {}", path.display()),
+        }
+    }
+}
+
+// What values a setting takes, checked when the config is loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Text,
+    // `<host>:<port>`, the host may be a name.
+    Addr,
+    // A number of at least 1, e.g. of workers.
+    Count,
+    Number,
+    // A probability from 0 to 1, e.g. of a fault.
+    Rate,
+    // `true` or `false`.
+    Switch,
+    Level,
+}
+
+impl Kind {
+    fn check(self, value: &str) -> Result<(), String> {
+        match self {
+            Kind::Text => Ok(()),
+            Kind::Addr => match value.rsplit_once(':') {
+                Some((host, port)) if !host.is_empty() => port
+                    .parse::<u16>()
+                    .map(|_| ())
+                    .map_err(|_| "port must be a number up to 65535".to_owned()),
+                _ => Err("expected <host>:<port>".to_owned()),
+            },
+            Kind::Count => match value.parse::<usize>() {
+                Ok(0) => Err("must be at least 1".to_owned()),
+                Ok(_) => Ok(()),
+                Err(error) => Err(error.to_string()),
+            },
+            Kind::Number => value
+                .parse::<u32>()
+                .map(|_| ())
+                .map_err(|error| error.to_string()),
+            Kind::Rate => match value.parse::<f64>() {
+                Ok(rate) if (0.0..=1.0).contains(&rate) => Ok(()),
+                Ok(_) => Err("must be from 0 to 1".to_owned()),
+                Err(error) => Err(error.to_string()),
+            },
+            Kind::Switch => value
+                .parse::<bool>()
+                .map(|_| ())
+                .map_err(|_| "expected true or false".to_owned()),
+            Kind::Level => LevelFilter::from_str(value)
+                .map(|_| ())
+                .map_err(|error| error.to_string()),
+        }
+    }
+}
+
+// A setting a binary understands, with its default value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Setting {
+    pub name: &'static str,
+    pub default: &'static str,
+    pub help: &'static str,
+    pub kind: Kind,
+}
+
+impl Setting {
+    pub const fn new(name: &'static str, default: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            default,
+            help,
+            kind: Kind::Text,
+        }
+    }
+
+    pub const fn addr(name: &'static str, default: &'static str, help: &'static str) -> Self {
+        Self::new(name, default, help).with_kind(Kind::Addr)
+    }
+
+    pub const fn count(name: &'static str, default: &'static str, help: &'static str) -> Self {
+        Self::new(name, default, help).with_kind(Kind::Count)
+    }
+
+    pub const fn number(name: &'static str, default: &'static str, help: &'static str) -> Self {
+        Self::new(name, default, help).with_kind(Kind::Number)
+    }
+
+    pub const fn rate(name: &'static str, default: &'static str, help: &'static str) -> Self {
+        Self::new(name, default, help).with_kind(Kind::Rate)
+    }
+
+    pub const fn switch(name: &'static str, default: &'static str, help: &'static str) -> Self {
+        Self::new(name, default, help).with_kind(Kind::Switch)
+    }
+
+    pub const fn with_kind(mut self, kind: Kind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn env_var(&self) -> String {
+        format!("{}{}", ENV_PREFIX, self.name.to_uppercase())
+    }
+
+    pub fn flag(&self) -> String {
+        format!("--{}", self.name.replace('_', "-"))
+    }
+}
+
+// Settings of one binary, from lowest to highest priority: defaults, the config
+// file, `SMART_HOME_<NAME>` environment variables and `--<name> <value>` flags.
+//
+// The file has `key = value` lines, `#` starts a comment. Keys before the first
+// `[section]` apply to every binary that knows them, keys in a `[<binary>]` section
+// only to that binary, which must know them.
+#[derive(Debug, Clone)]
+pub struct Config {
+    binary: String,
+    settings: Vec<Setting>,
+    values: HashMap<&'static str, (String, Source)>,
+    args: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl Config {
+    // Reads the process arguments and environment. Errors are printed with the
+    // usage and end the process, as the binary cannot start without its settings.
+    pub fn from_env(binary: &str, settings: &[Setting]) -> Self {
+        let args = std::env::args().skip(1);
+        let config = Self::load(binary, settings, args, |name| std::env::var(name).ok())
+            .unwrap_or_else(|error| {
+                eprintln!("Error: {}\n", error);
+                eprintln!("{}", usage(binary, settings));
+                process::exit(2);
+            });
+        // The logger is not set up yet.
+        for warning in config.get_warnings() {
+            eprintln!("Warning: {}", warning);
+        }
+        config
+    }
+
+    pub fn load(
+        binary: &str,
+        settings: &[Setting],
+        args: impl IntoIterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self, ConfigError> {
+        Self::load_with_legacy(binary, settings, args, env, Path::new(LEGACY_SETTINGS_DIR))
+    }
+
+    fn load_with_legacy(
+        binary: &str,
+        settings: &[Setting],
+        args: impl IntoIterator<Item = String>,
+        env: impl Fn(&str) -> Option<String>,
+        legacy_dir: &Path,
+    ) -> Result<Self, ConfigError> {
+        let settings = with_log_level(settings);
+        let mut config = Self {
+            binary: binary.to_owned(),
+            values: settings
+                .iter()
+                .map(|setting| (setting.name, (setting.default.to_owned(), Source::Default)))
+                .collect(),
+            settings,
+            args: Vec::new(),
+            warnings: Vec::new(),
+        };
+
+        let (flags, args) = parse_flags(args)?;
+        config.args = args;
+
+        let config_flag = flags.iter().find(|(name, _)| name == "config");
+        let (path, required) = match (config_flag, env(&format!("{}CONFIG", ENV_PREFIX))) {
+            (Some((_, path)), _) => (PathBuf::from(path), true),
+            (None, Some(path)) => (PathBuf::from(path), true),
+            (None, None) => (PathBuf::from(DEFAULT_CONFIG_FILE), false),
+        };
+        match fs::read_to_string(&path) {
+            Ok(contents) => config.apply_file(&path, &contents)?,
+            Err(error) if error.kind() == io::ErrorKind::NotFound && !required => {}
+            Err(source) => return Err(ConfigError::Unreadable { path, source }),
+        }
+        config.apply_legacy(legacy_dir, &path)?;
+
+        for setting in config.settings.clone() {
+            let var = setting.env_var();
+            if let Some(value) = env(&var) {
+                config.set(setting.name, value, Source::Env(var));
+            }
+        }
+
+        for (name, value) in flags.into_iter().filter(|(name, _)| name != "config") {
+            let flag = format!("--{}", name);
+            let Some(setting) = config.find(&name.replace('-', "_")) else {
+                return Err(ConfigError::UnknownSetting {
+                    name,
+                    origin: Source::Flag(flag),
+                });
+            };
+            config.set(setting.name, value, Source::Flag(flag));
+        }
+
+        for setting in &config.settings {
+            setting
+                .kind
+                .check(config.get(setting.name))
+                .map_err(|reason| config.invalid(setting.name, reason))?;
+        }
+        Ok(config)
+    }
+
+    fn apply_file(&mut self, path: &Path, contents: &str) -> Result<(), ConfigError> {
+        // None before the first section.
+        let mut section: Option<&str> = None;
+        for (index, line) in contents.lines().enumerate() {
+            let source = Source::File {
+                path: path.to_owned(),
+                line: index + 1,
+            };
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+            {
+                section = Some(name.trim());
+                continue;
+            }
+            let Some((name, value)) = line.split_once('=') else {
+                return Err(ConfigError::Syntax(source));
+            };
+            let (name, value) = (name.trim(), value.trim());
+
+            let applies = match section {
+                None => true,
+                Some(section) => section == self.binary,
+            };
+            match (self.find(name), section) {
+                (Some(setting), _) if applies => self.set(setting.name, value.to_owned(), source),
+                // Common keys may be meant for other binaries.
+                (None, Some(_)) if applies => {
+                    return Err(ConfigError::UnknownSetting {
+                        name: name.to_owned(),
+                        origin: source,
+                    })
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn apply_legacy(&mut self, dir: &Path, config_path: &Path) -> Result<(), ConfigError> {
+        for setting in self.settings.clone() {
+            if !LEGACY_SETTINGS.contains(&setting.name)
+                || self.values[setting.name].1 != Source::Default
+            {
+                continue;
+            }
+            let path = dir.join(setting.name);
+            let value = match fs::read_to_string(&path) {
+                Ok(value) => value.trim().to_owned(),
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(source) => return Err(ConfigError::Unreadable { path, source }),
+            };
+            self.warnings.push(format!(
+                "{} is deprecated, move it to {} as `{} = {}`",
+                path.display(),
+                config_path.display(),
+                setting.name,
+                value
+            ));
+            self.set(setting.name, value, Source::Legacy(path));
+        }
+        Ok(())
+    }
+
+    fn find(&self, name: &str) -> Option<Setting> {
+        self.settings
+            .iter()
+            .find(|setting| setting.name == name)
+            .copied()
+    }
+
+    fn set(&mut self, name: &'static str, value: String, source: Source) {
+        self.values.insert(name, (value, source));
+    }
+
+    fn invalid(&self, name: &str, reason: String) -> ConfigError {
+        let (value, source) = &self.values[name];
+        ConfigError::InvalidValue {
+            name: name.to_owned(),
+            value: value.clone(),
+            origin: source.clone(),
+            reason,
+        }
+    }
+
+    // Arguments that are not flags, e.g. the command of a client.
+    pub fn get_args(&self) -> &[String] {
+        &self.args
+    }
+
+    // Problems that do not stop the binary, e.g. legacy settings files in use.
+    pub fn get_warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    // Panics for settings the binary did not declare.
+    pub fn get(&self, name: &str) -> &str {
+        match self.values.get(name) {
+            Some((value, _)) => value,
+            None => panic!("Undeclared setting: {}", name),
+        }
+    }
+
+    // The getters of a kind panic for settings of another one.
+    pub fn get_addr(&self, name: &str) -> &str {
+        self.get_checked(name, Kind::Addr)
+    }
+
+    pub fn get_count(&self, name: &str) -> usize {
+        self.get_checked(name, Kind::Count).parse().unwrap_or(1)
+    }
+
+    pub fn get_number(&self, name: &str) -> u32 {
+        self.get_checked(name, Kind::Number).parse().unwrap_or(0)
+    }
+
+    pub fn get_rate(&self, name: &str) -> f64 {
+        self.get_checked(name, Kind::Rate).parse().unwrap_or(0.0)
+    }
+
+    pub fn get_switch(&self, name: &str) -> bool {
+        self.get_checked(name, Kind::Switch).parse().unwrap_or(false)
+    }
+
+    fn get_checked(&self, name: &str, kind: Kind) -> &str {
+        match self.find(name) {
+            Some(setting) if setting.kind == kind => self.get(name),
+            _ => panic!("Setting {} is not of kind {:?}", name, kind),
+        }
+    }
+
+    // Prints log records of the configured level and above to stdout.
+    pub fn init_logger(&self) {
+        let level = LevelFilter::from_str(self.get(LOG_LEVEL.name)).unwrap_or(LevelFilter::Info);
+        // A logger set before, e.g. by another config, stays.
+        if log::set_logger(&StdoutLogger).is_ok() {
+            log::set_max_level(level);
+        }
+    }
+}
+
+// `--name value` or `--name=value` pairs, without the dashes.
+type Flags = Vec<(String, String)>;
+
+fn parse_flags(
+    args: impl IntoIterator<Item = String>,
+) -> Result<(Flags, Vec<String>), ConfigError> {
+    let mut flags = Vec::new();
+    let mut rest = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let Some(flag) = arg.strip_prefix("--") else {
+            rest.push(arg);
+            continue;
+        };
+        let (name, value) = match flag.split_once('=') {
+            Some((name, value)) => (name.to_owned(), value.to_owned()),
+            None => match args.next() {
+                Some(value) => (flag.to_owned(), value),
+                None => return Err(ConfigError::MissingValue(arg)),
+            },
+        };
+        flags.push((name, value));
+    }
+    Ok((flags, rest))
+}
+
+// Every binary has `log_level`, unless it declares its own.
+fn with_log_level(settings: &[Setting]) -> Vec<Setting> {
+    let mut settings = settings.to_vec();
+    if !settings
+        .iter()
+        .any(|setting| setting.name == LOG_LEVEL.name)
+    {
+        settings.push(LOG_LEVEL);
+    }
+    settings
+}
+
+pub fn usage(binary: &str, settings: &[Setting]) -> String {
+    let mut usage = format!(
+        "Settings of {binary}, from `[{binary}]` or the top of {DEFAULT_CONFIG_FILE}, \
+         overridden by {ENV_PREFIX}<NAME> or --<name> <value>:\n"
+    );
+    for setting in with_log_level(settings) {
+        usage.push_str(&format!(
+            "  {:<24} {} (default: {})\n",
+            setting.flag(),
+            setting.help,
+            setting.default
+        ));
+    }
+    usage
+}
+
+struct StdoutLogger;
+
+impl Log for StdoutLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            println!("[{}] {}", record.level(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    const ADDR: Setting = Setting::addr("addr", "127.0.0.1:55331", "address to listen on");
+    const POWER: Setting = Setting::number("power", "1000", "power consumption, W");
+    const WORKERS: Setting = Setting::count("workers", "4", "connections served at the same time");
+    const DROP_RATE: Setting = Setting::rate("drop_rate", "0", "share of dropped messages");
+    const VERBOSE: Setting = Setting::switch("verbose", "false", "print every message");
+
+    fn load(args: &[&str], vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        load_with_legacy(args, vars, Path::new("/nonexistent"))
+    }
+
+    fn load_with_legacy(
+        args: &[&str],
+        vars: &[(&str, &str)],
+        legacy_dir: &Path,
+    ) -> Result<Config, ConfigError> {
+        let vars = vars
+            .iter()
+            .map(|&(name, value)| (name.to_owned(), value.to_owned()))
+            .collect::<HashMap<_, _>>();
+        Config::load_with_legacy(
+            "socket-tcp-server",
+            &[ADDR, POWER, WORKERS, DROP_RATE, VERBOSE],
+            args.iter().map(|&arg| arg.to_owned()),
+            |name| vars.get(name).cloned(),
+            legacy_dir,
+        )
+    }
+
+    fn config_file(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("smart-home-{}-{}.conf", name, process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_precedence() {
+        let path = config_file(
+            "precedence",
+            "# shared by all binaries\nlog_level = warn\nname = ignored\n\n\
+             [socket-tcp-server]\naddr = 0.0.0.0:1\npower = 2000 # W\n\n\
+             [light-tcp-server]\nbrightness = 40\n",
+        );
+        let path = path.to_str().unwrap();
+
+        let config = load(&["--config", path], &[]).unwrap();
+        assert_eq!(config.get_addr("addr"), "0.0.0.0:1");
+        assert_eq!(config.get_number("power"), 2000);
+        assert_eq!(config.get_count("workers"), 4);
+        assert_eq!(config.get("log_level"), "warn");
+        assert_eq!(config.get_rate("drop_rate"), 0.0);
+        assert!(!config.get_switch("verbose"));
+
+        let config = load(
+            &["status", "--power=3000", "--addr", "localhost:2"],
+            &[("SMART_HOME_CONFIG", path), ("SMART_HOME_POWER", "2500")],
+        )
+        .unwrap();
+        assert_eq!(config.get_addr("addr"), "localhost:2");
+        assert_eq!(config.get_number("power"), 3000);
+        assert_eq!(config.get_args(), ["status"]);
+
+        let config = load(&["--drop-rate", "0.25", "--verbose=true"], &[]).unwrap();
+        assert_eq!(config.get_rate("drop_rate"), 0.25);
+        assert!(config.get_switch("verbose"));
+
+        let config = load(&["--config", path], &[("SMART_HOME_POWER", "2500")]).unwrap();
+        assert_eq!(config.get_number("power"), 2500);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_validation_errors() {
+        let error = load(&["--power", "lots"], &[]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Invalid value `lots` for `power` from flag --power: invalid digit found in string"
+        );
+
+        assert!(matches!(
+            load(&[], &[("SMART_HOME_WORKERS", "0")]),
+            Err(ConfigError::InvalidValue { origin: Source::Env(var), .. })
+                if var == "SMART_HOME_WORKERS"
+        ));
+        for addr in ["55331", ":55331", "localhost:http"] {
+            assert!(load(&["--addr", addr], &[]).is_err());
+        }
+        for rate in ["1.5", "-0.1", "often"] {
+            assert!(load(&["--drop-rate", rate], &[]).is_err());
+        }
+        assert!(load(&["--verbose", "yes"], &[]).is_err());
+
+        assert!(matches!(
+            load(&["--colour", "red"], &[]),
+            Err(ConfigError::UnknownSetting { name, .. }) if name == "colour"
+        ));
+        assert!(matches!(
+            load(&["--power"], &[]),
+            Err(ConfigError::MissingValue(_))
+        ));
+        assert!(matches!(
+            load(&["--log-level", "chatty"], &[]),
+            Err(ConfigError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            load(&["--config", "/nonexistent/smart-home.conf"], &[]),
+            Err(ConfigError::Unreadable { .. })
+        ));
+
+        let path = config_file("unknown", "[socket-tcp-server]\ncolour = red\n");
+        let error = load(&["--config", path.to_str().unwrap()], &[]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!("Unknown setting `colour` in {}:2", path.display())
+        );
+        fs::remove_file(&path).unwrap();
+
+        let path = config_file("syntax", "addr 127.0.0.1:1\n");
+        assert!(matches!(
+            load(&["--config", path.to_str().unwrap()], &[]),
+            Err(ConfigError::Syntax(Source::File { line: 1, .. }))
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_legacy_files() {
+        let dir = env::temp_dir().join(format!("smart-home-legacy-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("addr"), "0.0.0.0:1\n").unwrap();
+        fs::write(dir.join("workers"), "8\n").unwrap();
+        fs::write(dir.join("power"), "2000\n").unwrap();
+
+        let config = load_with_legacy(&[], &[], &dir).unwrap();
+        assert_eq!(config.get_addr("addr"), "0.0.0.0:1");
+        assert_eq!(config.get_count("workers"), 8);
+        // Only the settings older binaries read from files.
+        assert_eq!(config.get_number("power"), 1000);
+        assert_eq!(config.get_warnings().len(), 2);
+        assert!(config.get_warnings()[0].contains("`addr = 0.0.0.0:1`"));
+
+        let path = config_file("legacy", "workers = 2\n");
+        let config = load_with_legacy(&["--config", path.to_str().unwrap()], &[], &dir).unwrap();
+        assert_eq!(config.get_count("workers"), 2);
+        assert_eq!(config.get_warnings().len(), 1);
+        fs::remove_file(&path).unwrap();
+
+        let config = load_with_legacy(&[], &[("SMART_HOME_ADDR", "localhost:2")], &dir).unwrap();
+        assert_eq!(config.get_addr("addr"), "localhost:2");
+
+        fs::write(dir.join("workers"), "none\n").unwrap();
+        assert!(matches!(
+            load_with_legacy(&[], &[], &dir),
+            Err(ConfigError::InvalidValue {
+                origin: Source::Legacy(_),
+                ..
+            })
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{comfort, devices::device::Device};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnvironmentReading {
+    // °C
+    pub temperature: f32,
+    // Relative humidity, %.
+    pub humidity: f32,
+    // CO2 concentration, ppm.
+    pub co2: u16,
+}
+
+impl Default for EnvironmentReading {
+    fn default() -> Self {
+        // Typical indoor air.
+        Self {
+            temperature: 20.0,
+            humidity: 45.0,
+            co2: 600,
+        }
+    }
+}
+
+impl EnvironmentReading {
+    pub fn dew_point(&self) -> f32 {
+        comfort::dew_point(self.temperature, self.humidity)
+    }
+
+    pub fn humidex(&self) -> f32 {
+        comfort::humidex(self.temperature, self.humidity)
+    }
+}
+
+// Measures temperature, relative humidity and CO2.
+#[derive(Debug, Clone)]
+pub struct EnvironmentSensor {
+    name: String,
+    description: String,
+    reading: EnvironmentReading,
+}
+
+impl EnvironmentSensor {
+    pub fn new(name: &str, description: &str) -> EnvironmentSensor {
+        EnvironmentSensor {
+            name: name.into(),
+            description: description.into(),
+            reading: EnvironmentReading::default(),
+        }
+    }
+
+    pub fn get_reading(&self) -> EnvironmentReading {
+        self.reading
+    }
+
+    pub fn set_reading(&mut self, reading: EnvironmentReading) {
+        self.reading = reading;
+    }
+
+    pub fn get_temperature(&self) -> f32 {
+        self.reading.temperature
+    }
+
+    pub fn set_temperature(&mut self, temperature: f32) {
+        self.reading.temperature = temperature;
+    }
+}
+
+impl Device for EnvironmentSensor {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_description(&self) -> &str {
+        &self.description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_environment_sensor() {
+        let mut sensor = EnvironmentSensor::new("Air", "Air quality sensor");
+        assert_eq!(sensor.get_name(), "Air");
+        assert_eq!(sensor.get_description(), "Air quality sensor");
+
+        sensor.set_reading(EnvironmentReading {
+            temperature: 30.0,
+            humidity: 70.0,
+            co2: 800,
+        });
+        sensor.set_temperature(20.0);
+        let reading = sensor.get_reading();
+        assert_eq!(reading.temperature, 20.0);
+        assert_eq!(reading.co2, 800);
+        assert!(reading.dew_point() < reading.temperature);
+    }
+}
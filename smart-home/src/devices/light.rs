@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+use crate::devices::device::Device;
+
+pub const MAX_BRIGHTNESS: u8 = 100;
+pub const MIN_COLOR_TEMPERATURE: u16 = 2700;
+pub const MAX_COLOR_TEMPERATURE: u16 = 6500;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Transition {
+    from_brightness: u8,
+    to_brightness: u8,
+    from_color_temperature: u16,
+    to_color_temperature: u16,
+    elapsed: Duration,
+    duration: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmartLight {
+    name: String,
+    description: String,
+    is_on: bool,
+    brightness: u8,
+    color_temperature: u16,
+    transition: Option<Transition>,
+}
+
+impl SmartLight {
+    pub fn new(name: &str, description: &str) -> SmartLight {
+        SmartLight {
+            name: name.into(),
+            description: description.into(),
+            is_on: false,
+            brightness: MAX_BRIGHTNESS,
+            color_temperature: MIN_COLOR_TEMPERATURE,
+            transition: None,
+        }
+    }
+
+    pub fn turn_on(&mut self) {
+        self.is_on = true;
+    }
+
+    pub fn turn_off(&mut self) {
+        self.is_on = false;
+    }
+
+    pub fn switch(&mut self) {
+        self.is_on = !self.is_on;
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.is_on
+    }
+
+    pub fn brightness(&self) -> u8 {
+        self.brightness
+    }
+
+    pub fn color_temperature(&self) -> u16 {
+        self.color_temperature
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        self.transition.is_some()
+    }
+
+    // Values out of range are clamped: brightness to 0..=100 %, colour temperature
+    // to 2700..=6500 K.
+    pub fn set_brightness(&mut self, brightness: u8) {
+        self.transition = None;
+        self.brightness = brightness.min(MAX_BRIGHTNESS);
+    }
+
+    pub fn set_color_temperature(&mut self, color_temperature: u16) {
+        self.transition = None;
+        self.color_temperature =
+            color_temperature.clamp(MIN_COLOR_TEMPERATURE, MAX_COLOR_TEMPERATURE);
+    }
+
+    // Starts a linear fade to the given brightness and colour temperature, driven by `advance`.
+    pub fn start_transition(&mut self, brightness: u8, color_temperature: u16, duration: Duration) {
+        let to_brightness = brightness.min(MAX_BRIGHTNESS);
+        let to_color_temperature =
+            color_temperature.clamp(MIN_COLOR_TEMPERATURE, MAX_COLOR_TEMPERATURE);
+
+        if duration.is_zero() {
+            self.brightness = to_brightness;
+            self.color_temperature = to_color_temperature;
+            self.transition = None;
+            return;
+        }
+
+        self.transition = Some(Transition {
+            from_brightness: self.brightness,
+            to_brightness,
+            from_color_temperature: self.color_temperature,
+            to_color_temperature,
+            elapsed: Duration::ZERO,
+            duration,
+        });
+    }
+
+    pub fn advance(&mut self, dt: Duration) {
+        let Some(transition) = self.transition.as_mut() else {
+            return;
+        };
+
+        transition.elapsed = (transition.elapsed + dt).min(transition.duration);
+        let progress = transition.elapsed.as_secs_f32() / transition.duration.as_secs_f32();
+        let lerp = |from: f32, to: f32| from + (to - from) * progress;
+
+        self.brightness = lerp(
+            transition.from_brightness as f32,
+            transition.to_brightness as f32,
+        )
+        .round() as u8;
+        self.color_temperature = lerp(
+            transition.from_color_temperature as f32,
+            transition.to_color_temperature as f32,
+        )
+        .round() as u16;
+
+        if transition.elapsed >= transition.duration {
+            self.transition = None;
+        }
+    }
+}
+
+impl Device for SmartLight {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_description(&self) -> &str {
+        &self.description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_smart_light() {
+        let mut light = SmartLight::new("Light", "A smart light");
+        assert_eq!(light.get_name(), "Light");
+        assert_eq!(light.get_description(), "A smart light");
+        assert!(!light.is_on());
+
+        light.switch();
+        assert!(light.is_on());
+
+        light.set_brightness(150);
+        assert_eq!(light.brightness(), 100);
+
+        light.set_color_temperature(1000);
+        assert_eq!(light.color_temperature(), MIN_COLOR_TEMPERATURE);
+    }
+
+    #[test]
+    fn test_transition() {
+        let mut light = SmartLight::new("Light", "A smart light");
+        light.set_brightness(0);
+        light.start_transition(100, 4700, Duration::from_secs(10));
+        assert!(light.is_transitioning());
+
+        light.advance(Duration::from_secs(5));
+        assert_eq!(light.brightness(), 50);
+        assert_eq!(light.color_temperature(), 3700);
+
+        light.advance(Duration::from_secs(10));
+        assert_eq!(light.brightness(), 100);
+        assert_eq!(light.color_temperature(), 4700);
+        assert!(!light.is_transitioning());
+    }
+}
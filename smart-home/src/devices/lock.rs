@@ -0,0 +1,294 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    time::{Duration, SystemTime},
+};
+
+use crate::{devices::device::Device, errors::SmartHouseError};
+
+// Only the latest log entries are kept, older ones are dropped.
+pub const MAX_LOG_ENTRIES: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinCode {
+    pub user: String,
+    pub pin: String,
+    // Unbounded on the missing side.
+    pub valid_from: Option<SystemTime>,
+    pub valid_until: Option<SystemTime>,
+}
+
+impl PinCode {
+    pub fn new(user: &str, pin: &str) -> Self {
+        Self {
+            user: user.into(),
+            pin: pin.into(),
+            valid_from: None,
+            valid_until: None,
+        }
+    }
+
+    pub fn with_validity(mut self, from: SystemTime, until: SystemTime) -> Self {
+        self.valid_from = Some(from);
+        self.valid_until = Some(until);
+        self
+    }
+
+    pub fn is_valid_at(&self, time: SystemTime) -> bool {
+        self.valid_from.is_none_or(|from| from <= time)
+            && self.valid_until.is_none_or(|until| time < until)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockAction {
+    Lock,
+    Unlock,
+    AutoLock,
+}
+
+impl fmt::Display for LockAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockAction::Lock => write!(f, "lock"),
+            LockAction::Unlock => write!(f, "unlock"),
+            LockAction::AutoLock => write!(f, "auto-lock"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccessEntry {
+    pub timestamp: SystemTime,
+    // None for attempts with an unknown PIN and for actions without one.
+    pub user: Option<String>,
+    pub action: LockAction,
+    pub granted: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct SmartLock {
+    name: String,
+    description: String,
+    locked: bool,
+    pins: HashMap<String, PinCode>,
+    access_log: VecDeque<AccessEntry>,
+    auto_relock: Option<Duration>,
+    unlocked_at: Option<SystemTime>,
+}
+
+impl SmartLock {
+    pub fn new(name: &str, description: &str) -> SmartLock {
+        SmartLock {
+            name: name.into(),
+            description: description.into(),
+            locked: true,
+            pins: HashMap::new(),
+            access_log: VecDeque::new(),
+            auto_relock: None,
+            unlocked_at: None,
+        }
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    // Replaces the PIN code of the same user, if any.
+    pub fn add_pin(&mut self, pin: PinCode) {
+        self.pins.insert(pin.user.clone(), pin);
+    }
+
+    pub fn remove_pin(&mut self, user: &str) -> Result<PinCode, SmartHouseError> {
+        self.pins
+            .remove(user)
+            .ok_or(SmartHouseError::PinNotFoundError(user.to_string()))
+    }
+
+    pub fn get_pins(&self) -> impl Iterator<Item = &PinCode> {
+        self.pins.values()
+    }
+
+    pub fn get_auto_relock(&self) -> Option<Duration> {
+        self.auto_relock
+    }
+
+    pub fn set_auto_relock(&mut self, delay: Option<Duration>) {
+        self.auto_relock = delay;
+    }
+
+    pub fn get_access_log(&self) -> impl Iterator<Item = &AccessEntry> {
+        self.access_log.iter()
+    }
+
+    pub fn lock(&mut self) {
+        self.lock_at(SystemTime::now());
+    }
+
+    pub fn lock_at(&mut self, now: SystemTime) {
+        self.locked = true;
+        self.unlocked_at = None;
+        self.log(now, None, LockAction::Lock, true);
+    }
+
+    pub fn unlock(&mut self, pin: &str) -> Result<(), SmartHouseError> {
+        self.unlock_at(pin, SystemTime::now())
+    }
+
+    // Every attempt is logged, failed ones with the user the PIN belongs to if known.
+    pub fn unlock_at(&mut self, pin: &str, now: SystemTime) -> Result<(), SmartHouseError> {
+        let code = self.pins.values().find(|code| code.pin == pin);
+        let user = code.map(|code| code.user.clone());
+
+        if !code.is_some_and(|code| code.is_valid_at(now)) {
+            self.log(now, user, LockAction::Unlock, false);
+            return Err(SmartHouseError::AccessDeniedError(self.name.clone()));
+        }
+
+        self.locked = false;
+        self.unlocked_at = Some(now);
+        self.log(now, user, LockAction::Unlock, true);
+        Ok(())
+    }
+
+    // Sets a state saved before a restart, without a PIN and without logging it. An
+    // unlocked lock is relocked once the auto-relock delay has passed from `now`.
+    pub fn restore_at(&mut self, locked: bool, now: SystemTime) {
+        self.locked = locked;
+        self.unlocked_at = (!locked).then_some(now);
+    }
+
+    // Locks the door again once the auto-relock delay has passed.
+    // Returns true if the lock was relocked.
+    pub fn tick(&mut self, now: SystemTime) -> bool {
+        let (Some(delay), Some(unlocked_at)) = (self.auto_relock, self.unlocked_at) else {
+            return false;
+        };
+        if self.locked || now < unlocked_at + delay {
+            return false;
+        }
+
+        self.locked = true;
+        self.unlocked_at = None;
+        self.log(now, None, LockAction::AutoLock, true);
+        true
+    }
+
+    fn log(
+        &mut self,
+        timestamp: SystemTime,
+        user: Option<String>,
+        action: LockAction,
+        granted: bool,
+    ) {
+        if self.access_log.len() == MAX_LOG_ENTRIES {
+            self.access_log.pop_front();
+        }
+        self.access_log.push_back(AccessEntry {
+            timestamp,
+            user,
+            action,
+            granted,
+        });
+    }
+}
+
+impl Device for SmartLock {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_description(&self) -> &str {
+        &self.description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn test_smart_lock() {
+        let mut lock = SmartLock::new("Lock", "Front door lock");
+        assert_eq!(lock.get_name(), "Lock");
+        assert_eq!(lock.get_description(), "Front door lock");
+        assert!(lock.is_locked());
+
+        lock.add_pin(PinCode::new("Alice", "1234"));
+        assert!(lock.unlock("0000").is_err());
+        assert!(lock.is_locked());
+
+        lock.unlock("1234").unwrap();
+        assert!(!lock.is_locked());
+
+        lock.lock();
+        assert!(lock.is_locked());
+
+        let log = lock.get_access_log().collect::<Vec<_>>();
+        assert_eq!(log.len(), 3);
+        assert_eq!((log[0].user.as_deref(), log[0].granted), (None, false));
+        assert_eq!(
+            (log[1].user.as_deref(), log[1].granted),
+            (Some("Alice"), true)
+        );
+        assert_eq!(log[2].action, LockAction::Lock);
+
+        lock.remove_pin("Alice").unwrap();
+        assert!(lock.remove_pin("Alice").is_err());
+        assert!(lock.unlock("1234").is_err());
+    }
+
+    #[test]
+    fn test_pin_validity() {
+        let start = SystemTime::UNIX_EPOCH + HOUR * 1000;
+        let mut lock = SmartLock::new("Lock", "Front door lock");
+        lock.add_pin(PinCode::new("Guest", "4321").with_validity(start, start + HOUR));
+
+        assert!(lock.unlock_at("4321", start - HOUR).is_err());
+        lock.unlock_at("4321", start).unwrap();
+        lock.lock_at(start);
+        assert!(lock.unlock_at("4321", start + HOUR).is_err());
+
+        let denied = lock.get_access_log().last().unwrap();
+        assert_eq!(denied.user.as_deref(), Some("Guest"));
+        assert!(!denied.granted);
+    }
+
+    #[test]
+    fn test_auto_relock() {
+        let start = SystemTime::UNIX_EPOCH;
+        let mut lock = SmartLock::new("Lock", "Front door lock");
+        lock.add_pin(PinCode::new("Alice", "1234"));
+        lock.set_auto_relock(Some(Duration::from_secs(30)));
+
+        lock.unlock_at("1234", start).unwrap();
+        assert!(!lock.tick(start + Duration::from_secs(29)));
+        assert!(!lock.is_locked());
+
+        assert!(lock.tick(start + Duration::from_secs(30)));
+        assert!(lock.is_locked());
+        assert_eq!(
+            lock.get_access_log().last().unwrap().action,
+            LockAction::AutoLock
+        );
+        assert!(!lock.tick(start + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_restore() {
+        let start = SystemTime::UNIX_EPOCH;
+        let mut lock = SmartLock::new("Lock", "Front door lock");
+        lock.set_auto_relock(Some(Duration::from_secs(30)));
+
+        lock.restore_at(false, start);
+        assert!(!lock.is_locked());
+        assert_eq!(lock.get_access_log().count(), 0);
+        assert!(lock.tick(start + Duration::from_secs(30)));
+
+        lock.restore_at(true, start);
+        assert!(lock.is_locked());
+        assert!(!lock.tick(start + Duration::from_secs(60)));
+    }
+}
@@ -0,0 +1,88 @@
+use std::time::Duration;
+
+use crate::devices::device::Device;
+
+// Measures the consumption of the whole house: switched-on sockets plus a
+// constant base load of everything not behind a smart socket.
+#[derive(Debug, Clone)]
+pub struct SmartMeter {
+    name: String,
+    description: String,
+    base_load: u32,
+    power: u32,
+    // Wh
+    energy: f64,
+}
+
+impl SmartMeter {
+    pub fn new(name: &str, description: &str, base_load: u32) -> SmartMeter {
+        SmartMeter {
+            name: name.into(),
+            description: description.into(),
+            base_load,
+            power: base_load,
+            energy: 0.0,
+        }
+    }
+
+    pub fn base_load(&self) -> u32 {
+        self.base_load
+    }
+
+    pub fn set_base_load(&mut self, base_load: u32) {
+        self.power = self.power - self.base_load + base_load;
+        self.base_load = base_load;
+    }
+
+    // Instantaneous power, W.
+    pub fn power(&self) -> u32 {
+        self.power
+    }
+
+    // Cumulative energy, Wh.
+    pub fn energy(&self) -> f64 {
+        self.energy
+    }
+
+    // Accounts the power measured so far for `dt` and switches to the new socket load.
+    pub fn measure(&mut self, load: u32, dt: Duration) {
+        self.energy += self.power as f64 * dt.as_secs_f64() / 3600.0;
+        self.power = self.base_load + load;
+    }
+}
+
+impl Device for SmartMeter {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_description(&self) -> &str {
+        &self.description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    #[test]
+    fn test_smart_meter() {
+        let mut meter = SmartMeter::new("Meter", "Main meter", 100);
+        assert_eq!(meter.get_name(), "Meter");
+        assert_eq!(meter.get_description(), "Main meter");
+        assert_eq!(meter.power(), 100);
+
+        meter.measure(1000, HOUR);
+        assert_eq!(meter.power(), 1100);
+        assert_eq!(meter.energy(), 100.0);
+
+        meter.measure(0, HOUR / 2);
+        assert_eq!(meter.power(), 100);
+        assert_eq!(meter.energy(), 650.0);
+
+        meter.set_base_load(50);
+        assert_eq!(meter.power(), 50);
+    }
+}
@@ -1,3 +1,8 @@
+pub mod environment;
+pub mod light;
+pub mod lock;
+pub mod meter;
+pub mod sensor;
 pub mod socket;
 pub mod thermometer;
 
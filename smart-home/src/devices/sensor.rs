@@ -0,0 +1,173 @@
+use std::{collections::VecDeque, fmt, time::SystemTime};
+
+use crate::devices::device::Device;
+
+// Only the latest events are kept, older ones are dropped.
+pub const MAX_EVENTS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinarySensorKind {
+    Motion,
+    Contact,
+}
+
+impl fmt::Display for BinarySensorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BinarySensorKind::Motion => write!(f, "motion"),
+            BinarySensorKind::Contact => write!(f, "contact"),
+        }
+    }
+}
+
+impl BinarySensorKind {
+    // Human readable state: motion sensors detect motion, contacts are open or closed.
+    pub fn state_name(&self, active: bool) -> &'static str {
+        match (self, active) {
+            (BinarySensorKind::Motion, true) => "motion",
+            (BinarySensorKind::Motion, false) => "clear",
+            (BinarySensorKind::Contact, true) => "open",
+            (BinarySensorKind::Contact, false) => "closed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SensorEvent {
+    pub active: bool,
+    pub timestamp: SystemTime,
+}
+
+#[derive(Debug, Clone)]
+pub struct BinarySensor {
+    name: String,
+    description: String,
+    kind: BinarySensorKind,
+    active: bool,
+    last_triggered: Option<SystemTime>,
+    events: VecDeque<SensorEvent>,
+}
+
+impl BinarySensor {
+    pub fn new(name: &str, description: &str, kind: BinarySensorKind) -> BinarySensor {
+        BinarySensor {
+            name: name.into(),
+            description: description.into(),
+            kind,
+            active: false,
+            last_triggered: None,
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn motion(name: &str, description: &str) -> BinarySensor {
+        Self::new(name, description, BinarySensorKind::Motion)
+    }
+
+    pub fn contact(name: &str, description: &str) -> BinarySensor {
+        Self::new(name, description, BinarySensorKind::Contact)
+    }
+
+    pub fn get_kind(&self) -> BinarySensorKind {
+        self.kind
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    pub fn get_state_name(&self) -> &'static str {
+        self.kind.state_name(self.active)
+    }
+
+    // Time of the last transition to the active state.
+    pub fn last_triggered(&self) -> Option<SystemTime> {
+        self.last_triggered
+    }
+
+    pub fn get_events(&self) -> impl Iterator<Item = &SensorEvent> {
+        self.events.iter()
+    }
+
+    pub fn set_active(&mut self, active: bool) -> Option<SensorEvent> {
+        let event = SensorEvent {
+            active,
+            timestamp: SystemTime::now(),
+        };
+        self.record(event).then_some(event)
+    }
+
+    // Applies an event produced elsewhere, e.g. received over the network.
+    // Returns false if the event does not change the sensor state.
+    pub fn record(&mut self, event: SensorEvent) -> bool {
+        if event.active == self.active {
+            return false;
+        }
+
+        self.active = event.active;
+        if event.active {
+            self.last_triggered = Some(event.timestamp);
+        }
+
+        if self.events.len() == MAX_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+
+        true
+    }
+}
+
+impl Device for BinarySensor {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    fn get_description(&self) -> &str {
+        &self.description
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_binary_sensor() {
+        let mut sensor = BinarySensor::motion("Motion", "Hallway motion sensor");
+        assert_eq!(sensor.get_name(), "Motion");
+        assert_eq!(sensor.get_description(), "Hallway motion sensor");
+        assert_eq!(sensor.get_kind(), BinarySensorKind::Motion);
+        assert!(!sensor.is_active());
+        assert_eq!(sensor.last_triggered(), None);
+
+        let event = sensor.set_active(true).expect("Event expected");
+        assert!(sensor.is_active());
+        assert_eq!(sensor.get_state_name(), "motion");
+        assert_eq!(sensor.last_triggered(), Some(event.timestamp));
+
+        assert_eq!(sensor.set_active(true), None);
+        assert!(sensor.set_active(false).is_some());
+        assert_eq!(sensor.last_triggered(), Some(event.timestamp));
+        assert_eq!(sensor.get_events().count(), 2);
+    }
+
+    #[test]
+    fn test_event_history_is_bounded() {
+        let mut sensor = BinarySensor::contact("Door", "Front door");
+        let start = SystemTime::UNIX_EPOCH;
+        for i in 0..(MAX_EVENTS as u64 + 10) {
+            sensor.record(SensorEvent {
+                active: i % 2 == 0,
+                timestamp: start + Duration::from_secs(i),
+            });
+        }
+
+        assert_eq!(sensor.get_events().count(), MAX_EVENTS);
+        let first = sensor.get_events().next().unwrap();
+        assert_eq!(first.timestamp, start + Duration::from_secs(10));
+        assert_eq!(sensor.get_state_name(), "closed");
+    }
+}
@@ -4,6 +4,7 @@ use crate::devices::device::Device;
 pub struct SmartSocket {
     power_consumption: u32,
     is_on: bool,
+    is_heater: bool,
     description: String,
     name: String,
 }
@@ -15,9 +16,22 @@ impl SmartSocket {
             name: name.into(),
             description: description.into(),
             is_on: false,
+            is_heater: false,
         }
     }
 
+    // A socket powering a heater warms its room with all of its power consumption.
+    pub fn heater(name: &str, description: &str, power_consumption: u32) -> SmartSocket {
+        SmartSocket {
+            is_heater: true,
+            ..SmartSocket::new(name, description, power_consumption)
+        }
+    }
+
+    pub fn is_heater(&self) -> bool {
+        self.is_heater
+    }
+
     pub fn turn_on(&mut self) {
         self.is_on = true;
     }
@@ -70,5 +84,14 @@ mod tests {
 
         smart_socket.turn_off();
         assert!(!smart_socket.is_on());
+        assert!(!smart_socket.is_heater());
+    }
+
+    #[test]
+    fn test_heater_socket() {
+        let heater = SmartSocket::heater("Heater", "An oil heater", 1500);
+        assert!(heater.is_heater());
+        assert!(!heater.is_on());
+        assert_eq!(heater.power_consumption(), 1500);
     }
 }
@@ -4,6 +4,7 @@ use crate::devices::device::Device;
 pub struct SmartThermometer {
     name: String,
     description: String,
+    temperature: f32,
 }
 
 impl SmartThermometer {
@@ -11,11 +12,16 @@ impl SmartThermometer {
         SmartThermometer {
             name: name.into(),
             description: description.into(),
+            temperature: 0.0,
         }
     }
 
     pub fn get_temperature(&self) -> f32 {
-        todo!("Implement get_temperature() for SmartThermometer")
+        self.temperature
+    }
+
+    pub fn set_temperature(&mut self, temperature: f32) {
+        self.temperature = temperature;
     }
 }
 
@@ -39,4 +45,11 @@ mod tests {
         assert_eq!(smart_thermometer.get_name(), "Thermometer");
         assert_eq!(smart_thermometer.get_description(), "A smart thermometer");
     }
+
+    #[test]
+    fn test_set_temperature() {
+        let mut smart_thermometer = SmartThermometer::new("Thermometer", "A smart thermometer");
+        smart_thermometer.set_temperature(21.5);
+        assert_eq!(smart_thermometer.get_temperature(), 21.5);
+    }
 }
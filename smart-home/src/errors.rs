@@ -3,6 +3,14 @@ use thiserror::Error;
 
 #[derive(Debug, Error, Diagnostic)]
 pub enum SmartHouseError {
+    #[error("House already exists: {0}")]
+    #[diagnostic(code(smart_home::house_already_exists))]
+    HouseAlreadyExistsError(String),
+
+    #[error("House not found: {0}")]
+    #[diagnostic(code(smart_home::house_not_found))]
+    HouseNotFoundError(String),
+
     #[error("Room already exists: {0}")]
     #[diagnostic(code(smart_home::room_already_exists))]
     RoomAlreadyExistsError(String),
@@ -18,4 +26,28 @@ pub enum SmartHouseError {
     #[error("Device not found: {0}")]
     #[diagnostic(code(smart_home::device_not_found))]
     DeviceNotFoundError(String),
+
+    #[error("Scene already exists: {0}")]
+    #[diagnostic(code(smart_home::scene_already_exists))]
+    SceneAlreadyExistsError(String),
+
+    #[error("Scene not found: {0}")]
+    #[diagnostic(code(smart_home::scene_not_found))]
+    SceneNotFoundError(String),
+
+    #[error("Unsupported action for device: {0}")]
+    #[diagnostic(code(smart_home::unsupported_action))]
+    UnsupportedActionError(String),
+
+    #[error("PIN code not found for user: {0}")]
+    #[diagnostic(code(smart_home::pin_not_found))]
+    PinNotFoundError(String),
+
+    #[error("Access denied: {0}")]
+    #[diagnostic(code(smart_home::access_denied))]
+    AccessDeniedError(String),
+
+    #[error("Invalid thermal properties: {0}")]
+    #[diagnostic(code(smart_home::invalid_thermal_properties))]
+    InvalidThermalPropertiesError(String),
 }
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use crate::{
+    devices::device::Device,
+    errors::SmartHouseError,
+    house::House,
+    report_provider::DeviceInfoProvider,
+    room::{Room, RoomDevice},
+};
+
+#[derive(Debug)]
+pub struct DeviceLocation<'a> {
+    pub house: &'a House,
+    pub room: &'a Room,
+    pub device: &'a RoomDevice,
+}
+
+#[derive(Debug)]
+pub struct Estate {
+    name: String,
+    houses: HashMap<String, House>,
+}
+
+impl Estate {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            houses: HashMap::new(),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn add_house(&mut self, house: House) -> Result<(), SmartHouseError> {
+        if self.houses.contains_key(house.get_name()) {
+            return Err(SmartHouseError::HouseAlreadyExistsError(
+                house.get_name().to_string(),
+            ));
+        }
+
+        self.houses.insert(house.get_name().to_owned(), house);
+        Ok(())
+    }
+
+    pub fn remove_house(&mut self, name: &str) -> Result<House, SmartHouseError> {
+        self.houses
+            .remove(name)
+            .ok_or(SmartHouseError::HouseNotFoundError(name.to_string()))
+    }
+
+    pub fn get_houses(&self) -> impl Iterator<Item = &House> {
+        let mut houses = self.houses.values().collect::<Vec<&House>>();
+        houses.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+        houses.into_iter()
+    }
+
+    pub fn get_house(&self, name: &str) -> Option<&House> {
+        self.houses.get(name)
+    }
+
+    pub fn get_house_mut(&mut self, name: &str) -> Option<&mut House> {
+        self.houses.get_mut(name)
+    }
+
+    pub fn get_devices(&self) -> impl Iterator<Item = DeviceLocation<'_>> {
+        self.get_houses().flat_map(|house| {
+            let mut rooms = house.get_rooms().collect::<Vec<&Room>>();
+            rooms.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+            rooms.into_iter().flat_map(move |room| {
+                room.get_devices().map(move |device| DeviceLocation {
+                    house,
+                    room,
+                    device,
+                })
+            })
+        })
+    }
+
+    pub fn find_devices<'a>(
+        &'a self,
+        device_name: &'a str,
+    ) -> impl Iterator<Item = DeviceLocation<'a>> {
+        self.get_devices()
+            .filter(move |location| location.device.get_name() == device_name)
+    }
+
+    pub fn create_report(&self, device_info_provider: &impl DeviceInfoProvider) -> String {
+        let mut report = format!("Estate: {}\n", self.name);
+        for house in self.get_houses() {
+            report.push_str(&house.create_report(device_info_provider));
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::socket::SmartSocket;
+
+    struct TestDeviceInfoProvider;
+
+    impl DeviceInfoProvider for TestDeviceInfoProvider {
+        fn get_devices(&self) -> Vec<&str> {
+            vec!["Socket"]
+        }
+    }
+
+    fn house_with_socket(name: &str, room: &str) -> House {
+        let mut house = House::new(name);
+        house.add_room(room).unwrap();
+        house
+            .get_room_mut(room)
+            .unwrap()
+            .add_device(SmartSocket::new("Socket", "A smart socket", 100).into())
+            .unwrap();
+        house
+    }
+
+    #[test]
+    fn test_estate() {
+        let mut estate = Estate::new("Estate");
+        assert_eq!(estate.get_name(), "Estate");
+
+        estate
+            .add_house(house_with_socket("Dacha", "Veranda"))
+            .unwrap();
+        estate
+            .add_house(house_with_socket("City", "Kitchen"))
+            .unwrap();
+        assert!(matches!(
+            estate.add_house(House::new("City")),
+            Err(SmartHouseError::HouseAlreadyExistsError(_))
+        ));
+
+        let houses = estate.get_houses().map(House::get_name).collect::<Vec<_>>();
+        assert_eq!(houses, vec!["City", "Dacha"]);
+
+        let found = estate
+            .find_devices("Socket")
+            .map(|location| (location.house.get_name(), location.room.get_name()))
+            .collect::<Vec<_>>();
+        assert_eq!(found, vec![("City", "Kitchen"), ("Dacha", "Veranda")]);
+
+        let report = estate.create_report(&TestDeviceInfoProvider);
+        assert_eq!(
+            report,
+            "Estate: Estate\nHouse: City\nRoom: Kitchen\n  Device: Socket\n    Description: A smart socket\nHouse: Dacha\nRoom: Veranda\n  Device: Socket\n    Description: A smart socket\n"
+        );
+
+        estate.remove_house("City").unwrap();
+        assert!(estate.get_house("City").is_none());
+        assert!(matches!(
+            estate.remove_house("City"),
+            Err(SmartHouseError::HouseNotFoundError(_))
+        ));
+    }
+}
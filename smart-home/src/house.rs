@@ -1,14 +1,19 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use crate::{
-    devices::device::Device, errors::SmartHouseError, report_provider::DeviceInfoProvider,
-    room::Room,
+    devices::{device::Device, meter::SmartMeter, sensor::SensorEvent},
+    errors::SmartHouseError,
+    report_provider::DeviceInfoProvider,
+    room::{Room, RoomDevice},
+    scene::Scene,
+    thermal,
 };
 
 #[derive(Debug)]
 pub struct House {
     name: String,
     rooms: HashMap<String, Room>,
+    scenes: HashMap<String, Scene>,
 }
 
 impl House {
@@ -16,6 +21,7 @@ impl House {
         Self {
             name: name.into(),
             rooms: HashMap::new(),
+            scenes: HashMap::new(),
         }
     }
 
@@ -45,10 +51,163 @@ impl House {
         self.rooms.iter().map(|kv| kv.1)
     }
 
+    pub fn get_room(&self, name: &str) -> Option<&Room> {
+        self.rooms.get(name)
+    }
+
     pub fn get_room_mut(&mut self, name: &str) -> Option<&mut Room> {
         self.rooms.get_mut(name)
     }
 
+    pub fn add_scene(&mut self, scene: Scene) -> Result<(), SmartHouseError> {
+        if self.scenes.contains_key(scene.get_name()) {
+            return Err(SmartHouseError::SceneAlreadyExistsError(
+                scene.get_name().to_string(),
+            ));
+        }
+
+        self.scenes.insert(scene.get_name().to_owned(), scene);
+        Ok(())
+    }
+
+    pub fn remove_scene(&mut self, name: &str) -> Result<(), SmartHouseError> {
+        if !self.scenes.contains_key(name) {
+            return Err(SmartHouseError::SceneNotFoundError(name.to_string()));
+        }
+
+        self.scenes.remove(name);
+        Ok(())
+    }
+
+    pub fn get_scenes(&self) -> impl Iterator<Item = &Scene> {
+        let mut scenes = self.scenes.values().collect::<Vec<&Scene>>();
+        scenes.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+        scenes.into_iter()
+    }
+
+    // Scenes are applied atomically: if any device is missing or does not support
+    // the action, already changed devices are restored to their previous state.
+    pub fn apply_scene(&mut self, name: &str) -> Result<(), SmartHouseError> {
+        let scene = self
+            .scenes
+            .get(name)
+            .cloned()
+            .ok_or(SmartHouseError::SceneNotFoundError(name.to_string()))?;
+
+        let mut applied: Vec<(String, RoomDevice)> = Vec::new();
+        for entry in scene.get_entries() {
+            if let Err(err) = self.with_device_mut(&entry.room, &entry.device, |device| {
+                let previous = device.clone();
+                entry.action.apply(device)?;
+                applied.push((entry.room.clone(), previous));
+                Ok(())
+            }) {
+                self.rollback_scene(applied);
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Runs `f` on the device, or fails if the room or the device does not exist.
+    fn with_device_mut(
+        &mut self,
+        room: &str,
+        device: &str,
+        f: impl FnOnce(&mut RoomDevice) -> Result<(), SmartHouseError>,
+    ) -> Result<(), SmartHouseError> {
+        let device = self
+            .rooms
+            .get_mut(room)
+            .ok_or(SmartHouseError::RoomNotFoundError(room.to_string()))?
+            .get_device_mut(device)
+            .ok_or(SmartHouseError::DeviceNotFoundError(format!(
+                "{room}/{device}"
+            )))?;
+        f(device)
+    }
+
+    fn rollback_scene(&mut self, applied: Vec<(String, RoomDevice)>) {
+        for (room, previous) in applied.into_iter().rev() {
+            if let Some(device) = self
+                .rooms
+                .get_mut(&room)
+                .and_then(|room| room.get_device_mut(previous.get_name()))
+            {
+                *device = previous;
+            }
+        }
+    }
+
+    // Total power of all switched-on sockets, W.
+    pub fn socket_load(&self) -> u32 {
+        self.get_rooms()
+            .flat_map(|room| room.get_devices())
+            .map(|device| match device {
+                RoomDevice::SmartSocket(socket) if socket.is_on() => socket.power_consumption(),
+                _ => 0,
+            })
+            .sum()
+    }
+
+    // Lets every smart meter account the last `dt` and read the current load.
+    pub fn update_meters(&mut self, dt: Duration) {
+        let load = self.socket_load();
+        for room in self.rooms.values_mut() {
+            for device in room.get_devices_mut() {
+                if let RoomDevice::SmartMeter(meter) = device {
+                    meter.measure(load, dt);
+                }
+            }
+        }
+    }
+
+    // Meters sorted by room and name.
+    pub fn get_meters(&self) -> impl Iterator<Item = &SmartMeter> {
+        let mut rooms = self.rooms.values().collect::<Vec<&Room>>();
+        rooms.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+        let mut meters = Vec::new();
+        for room in rooms {
+            let mut room_meters = room
+                .get_devices()
+                .filter_map(|device| match device {
+                    RoomDevice::SmartMeter(meter) => Some(meter),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            room_meters.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+            meters.extend(room_meters);
+        }
+        meters.into_iter()
+    }
+
+    // Returns whether the event changed the sensor state.
+    pub fn record_sensor_event(
+        &mut self,
+        room: &str,
+        device: &str,
+        event: SensorEvent,
+    ) -> Result<bool, SmartHouseError> {
+        let mut changed = false;
+        self.with_device_mut(room, device, |device| match device {
+            RoomDevice::BinarySensor(sensor) => {
+                changed = sensor.record(event);
+                Ok(())
+            }
+            device => Err(SmartHouseError::UnsupportedActionError(
+                device.get_name().to_string(),
+            )),
+        })?;
+        Ok(changed)
+    }
+
+    pub fn step_temperature(&mut self, outside: f32, dt: Duration) {
+        for room in self.rooms.values_mut() {
+            thermal::step_room(room, outside, dt);
+        }
+    }
+
     pub fn create_report(&self, device_info_provider: &impl DeviceInfoProvider) -> String {
         let mut report = format!("House: {}\n", self.name);
         let devices = device_info_provider.get_devices();
@@ -62,6 +221,41 @@ impl House {
                 if devices.contains(&device.get_name()) {
                     report.push_str(&format!("  Device: {}\n", device.get_name()));
                     report.push_str(&format!("    Description: {}\n", device.get_description()));
+                    match device {
+                        RoomDevice::SmartLight(light) => report.push_str(&format!(
+                            "    State: {}, {}%, {}K\n",
+                            if light.is_on() { "on" } else { "off" },
+                            light.brightness(),
+                            light.color_temperature()
+                        )),
+                        RoomDevice::BinarySensor(sensor) => {
+                            report.push_str(&format!("    State: {}\n", sensor.get_state_name()))
+                        }
+                        RoomDevice::SmartLock(lock) => report.push_str(&format!(
+                            "    State: {}\n",
+                            if lock.is_locked() {
+                                "locked"
+                            } else {
+                                "unlocked"
+                            }
+                        )),
+                        RoomDevice::EnvironmentSensor(sensor) => {
+                            let reading = sensor.get_reading();
+                            report.push_str(&format!(
+                                "    State: {:.1}C, {:.0}% RH, {} ppm CO2, dew point {:.1}C\n",
+                                reading.temperature,
+                                reading.humidity,
+                                reading.co2,
+                                reading.dew_point()
+                            ))
+                        }
+                        RoomDevice::SmartMeter(meter) => report.push_str(&format!(
+                            "    State: {} W, {:.1} Wh\n",
+                            meter.power(),
+                            meter.energy()
+                        )),
+                        _ => {}
+                    }
                 }
             }
         }
@@ -73,8 +267,11 @@ impl House {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::devices::socket::SmartSocket;
+    use crate::devices::{
+        light::SmartLight, meter::SmartMeter, sensor::BinarySensor, socket::SmartSocket,
+    };
     use crate::report_provider::DeviceInfoProvider;
+    use crate::scene::SceneAction;
 
     struct TestDeviceInfoProvider;
 
@@ -110,4 +307,141 @@ mod tests {
             "House: House\nRoom: Room1\n  Device: Socket\n    Description: A smart socket\nRoom: Room2\nRoom: Room3\n"
         );
     }
+
+    struct LightInfoProvider;
+
+    impl DeviceInfoProvider for LightInfoProvider {
+        fn get_devices(&self) -> Vec<&str> {
+            vec!["Light"]
+        }
+    }
+
+    #[test]
+    fn test_light_report() {
+        let mut house = House::new("House");
+        house.add_room("Hall").unwrap();
+        let mut light = SmartLight::new("Light", "Ceiling light");
+        light.turn_on();
+        light.set_brightness(40);
+        house
+            .get_room_mut("Hall")
+            .unwrap()
+            .add_device(light.into())
+            .unwrap();
+
+        assert_eq!(
+            house.create_report(&LightInfoProvider),
+            "House: House\nRoom: Hall\n  Device: Light\n    Description: Ceiling light\n    State: on, 40%, 2700K\n"
+        );
+    }
+
+    #[test]
+    fn test_record_sensor_event() {
+        let mut house = House::new("House");
+        house.add_room("Hall").unwrap();
+        let hall = house.get_room_mut("Hall").unwrap();
+        hall.add_device(BinarySensor::contact("Door", "Front door").into())
+            .unwrap();
+        hall.add_device(SmartSocket::new("Socket", "A smart socket", 100).into())
+            .unwrap();
+
+        let event = SensorEvent {
+            active: true,
+            timestamp: std::time::SystemTime::now(),
+        };
+        assert!(house.record_sensor_event("Hall", "Door", event).unwrap());
+        assert!(!house.record_sensor_event("Hall", "Door", event).unwrap());
+        assert!(house.record_sensor_event("Hall", "Socket", event).is_err());
+        assert!(house.record_sensor_event("Hall", "Window", event).is_err());
+
+        struct DoorInfoProvider;
+        impl DeviceInfoProvider for DoorInfoProvider {
+            fn get_devices(&self) -> Vec<&str> {
+                vec!["Door"]
+            }
+        }
+        assert_eq!(
+            house.create_report(&DoorInfoProvider),
+            "House: House\nRoom: Hall\n  Device: Door\n    Description: Front door\n    State: open\n"
+        );
+    }
+
+    #[test]
+    fn test_update_meters() {
+        let mut house = House::new("House");
+        house.add_room("Kitchen").unwrap();
+        house.add_room("Hall").unwrap();
+        let kitchen = house.get_room_mut("Kitchen").unwrap();
+        let mut kettle = SmartSocket::new("Kettle", "Kettle socket", 2000);
+        kettle.turn_on();
+        kitchen.add_device(kettle.into()).unwrap();
+        kitchen
+            .add_device(SmartSocket::new("Fridge", "Fridge socket", 150).into())
+            .unwrap();
+        house
+            .get_room_mut("Hall")
+            .unwrap()
+            .add_device(SmartMeter::new("Meter", "Main meter", 100).into())
+            .unwrap();
+
+        assert_eq!(house.socket_load(), 2000);
+        house.update_meters(Duration::from_secs(3600));
+        house.update_meters(Duration::from_secs(1800));
+
+        let meter = house.get_meters().next().unwrap();
+        assert_eq!(meter.power(), 2100);
+        assert_eq!(meter.energy(), 100.0 + 1050.0);
+    }
+
+    fn socket_is_on(house: &mut House, room: &str, device: &str) -> bool {
+        match house.get_room_mut(room).unwrap().get_device_mut(device) {
+            Some(RoomDevice::SmartSocket(socket)) => socket.is_on(),
+            _ => panic!("Socket expected"),
+        }
+    }
+
+    #[test]
+    fn test_apply_scene() {
+        let mut house = House::new("House");
+        house.add_room("Kitchen").unwrap();
+        let kitchen = house.get_room_mut("Kitchen").unwrap();
+        kitchen
+            .add_device(SmartSocket::new("Fridge", "Fridge socket", 100).into())
+            .unwrap();
+        kitchen
+            .add_device(SmartSocket::new("Kettle", "Kettle socket", 2000).into())
+            .unwrap();
+
+        let mut night = Scene::new("Night");
+        night.add_action("Kitchen", "Fridge", SceneAction::TurnOn);
+        night.add_action("Kitchen", "Kettle", SceneAction::TurnOff);
+        house.add_scene(night).unwrap();
+        assert!(matches!(
+            house.add_scene(Scene::new("Night")),
+            Err(SmartHouseError::SceneAlreadyExistsError(_))
+        ));
+
+        house.apply_scene("Night").unwrap();
+        assert!(socket_is_on(&mut house, "Kitchen", "Fridge"));
+        assert!(!socket_is_on(&mut house, "Kitchen", "Kettle"));
+
+        let mut broken = Scene::new("Broken");
+        broken.add_action("Kitchen", "Fridge", SceneAction::TurnOff);
+        broken.add_action("Kitchen", "Toaster", SceneAction::TurnOn);
+        house.add_scene(broken).unwrap();
+
+        assert!(matches!(
+            house.apply_scene("Broken"),
+            Err(SmartHouseError::DeviceNotFoundError(_))
+        ));
+        assert!(socket_is_on(&mut house, "Kitchen", "Fridge"));
+
+        assert!(matches!(
+            house.apply_scene("Away"),
+            Err(SmartHouseError::SceneNotFoundError(_))
+        ));
+
+        house.remove_scene("Broken").unwrap();
+        assert_eq!(house.get_scenes().count(), 1);
+    }
 }
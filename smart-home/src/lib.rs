@@ -1,6 +1,11 @@
+pub mod comfort;
+pub mod config;
 pub mod devices;
+pub mod estate;
 pub mod house;
 pub mod report_provider;
 pub mod room;
+pub mod scene;
+pub mod thermal;
 
 pub mod errors;
@@ -2,14 +2,24 @@ use std::fmt::Debug;
 use std::{collections::HashMap, fmt};
 
 use crate::{
-    devices::{device::Device, socket::SmartSocket, thermometer::SmartThermometer},
+    devices::{
+        device::Device, environment::EnvironmentSensor, light::SmartLight, lock::SmartLock,
+        meter::SmartMeter, sensor::BinarySensor, socket::SmartSocket,
+        thermometer::SmartThermometer,
+    },
     errors::SmartHouseError,
+    thermal::ThermalProperties,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum RoomDevice {
     SmartSocket(SmartSocket),
     Thermometer(SmartThermometer),
+    SmartLight(SmartLight),
+    BinarySensor(BinarySensor),
+    SmartLock(SmartLock),
+    EnvironmentSensor(EnvironmentSensor),
+    SmartMeter(SmartMeter),
 }
 
 impl Device for RoomDevice {
@@ -17,6 +27,11 @@ impl Device for RoomDevice {
         match self {
             RoomDevice::Thermometer(t) => t.get_name(),
             RoomDevice::SmartSocket(s) => s.get_name(),
+            RoomDevice::SmartLight(l) => l.get_name(),
+            RoomDevice::BinarySensor(b) => b.get_name(),
+            RoomDevice::SmartLock(l) => l.get_name(),
+            RoomDevice::EnvironmentSensor(e) => e.get_name(),
+            RoomDevice::SmartMeter(m) => m.get_name(),
         }
     }
 
@@ -24,6 +39,11 @@ impl Device for RoomDevice {
         match self {
             RoomDevice::Thermometer(t) => t.get_description(),
             RoomDevice::SmartSocket(s) => s.get_description(),
+            RoomDevice::SmartLight(l) => l.get_description(),
+            RoomDevice::BinarySensor(b) => b.get_description(),
+            RoomDevice::SmartLock(l) => l.get_description(),
+            RoomDevice::EnvironmentSensor(e) => e.get_description(),
+            RoomDevice::SmartMeter(m) => m.get_description(),
         }
     }
 }
@@ -34,6 +54,36 @@ impl From<SmartSocket> for RoomDevice {
     }
 }
 
+impl From<SmartLight> for RoomDevice {
+    fn from(light: SmartLight) -> Self {
+        RoomDevice::SmartLight(light)
+    }
+}
+
+impl From<BinarySensor> for RoomDevice {
+    fn from(sensor: BinarySensor) -> Self {
+        RoomDevice::BinarySensor(sensor)
+    }
+}
+
+impl From<SmartLock> for RoomDevice {
+    fn from(lock: SmartLock) -> Self {
+        RoomDevice::SmartLock(lock)
+    }
+}
+
+impl From<EnvironmentSensor> for RoomDevice {
+    fn from(sensor: EnvironmentSensor) -> Self {
+        RoomDevice::EnvironmentSensor(sensor)
+    }
+}
+
+impl From<SmartMeter> for RoomDevice {
+    fn from(meter: SmartMeter) -> Self {
+        RoomDevice::SmartMeter(meter)
+    }
+}
+
 impl From<SmartThermometer> for RoomDevice {
     fn from(thermometer: SmartThermometer) -> Self {
         RoomDevice::Thermometer(thermometer)
@@ -43,6 +93,8 @@ impl From<SmartThermometer> for RoomDevice {
 pub struct Room {
     name: String,
     devices: HashMap<String, RoomDevice>,
+    thermal: ThermalProperties,
+    temperature: f32,
 }
 
 impl Room {
@@ -50,6 +102,8 @@ impl Room {
         Self {
             name: name.into(),
             devices: HashMap::new(),
+            thermal: ThermalProperties::default(),
+            temperature: ThermalProperties::DEFAULT_TEMPERATURE,
         }
     }
 
@@ -57,13 +111,55 @@ impl Room {
         &self.name
     }
 
-    pub fn add_device(&mut self, device: RoomDevice) -> Result<(), SmartHouseError> {
+    pub fn get_thermal_properties(&self) -> &ThermalProperties {
+        &self.thermal
+    }
+
+    pub fn set_thermal_properties(&mut self, thermal: ThermalProperties) {
+        self.thermal = thermal;
+    }
+
+    pub fn get_temperature(&self) -> f32 {
+        self.temperature
+    }
+
+    // Room thermometers and environment sensors always show the room temperature.
+    pub fn set_temperature(&mut self, temperature: f32) {
+        self.temperature = temperature;
+        for device in self.devices.values_mut() {
+            Self::show_temperature(device, temperature);
+        }
+    }
+
+    fn show_temperature(device: &mut RoomDevice, temperature: f32) {
+        match device {
+            RoomDevice::Thermometer(thermometer) => thermometer.set_temperature(temperature),
+            RoomDevice::EnvironmentSensor(sensor) => sensor.set_temperature(temperature),
+            _ => {}
+        }
+    }
+
+    pub fn heating_power(&self) -> u32 {
+        self.devices
+            .values()
+            .map(|device| match device {
+                RoomDevice::SmartSocket(socket) if socket.is_heater() && socket.is_on() => {
+                    socket.power_consumption()
+                }
+                _ => 0,
+            })
+            .sum()
+    }
+
+    pub fn add_device(&mut self, mut device: RoomDevice) -> Result<(), SmartHouseError> {
         if self.devices.contains_key(device.get_name()) {
             return Err(SmartHouseError::DeviceAlreadyExistsError(
                 device.get_name().to_string(),
             ));
         }
 
+        Self::show_temperature(&mut device, self.temperature);
+
         self.devices.insert(device.get_name().to_owned(), device);
         Ok(())
     }
@@ -77,11 +173,23 @@ impl Room {
         Ok(())
     }
 
+    pub fn get_device(&self, name: &str) -> Option<&RoomDevice> {
+        self.devices.get(name)
+    }
+
+    pub fn get_device_mut(&mut self, name: &str) -> Option<&mut RoomDevice> {
+        self.devices.get_mut(name)
+    }
+
     pub fn get_devices(&self) -> impl Iterator<Item = &RoomDevice> {
         let mut devices = self.devices.values().collect::<Vec<&RoomDevice>>();
         devices.sort_by(|a, b| a.get_name().cmp(b.get_name()));
         devices.into_iter()
     }
+
+    pub fn get_devices_mut(&mut self) -> impl Iterator<Item = &mut RoomDevice> {
+        self.devices.values_mut()
+    }
 }
 
 impl Debug for Room {
@@ -94,6 +202,7 @@ impl Debug for Room {
         f.debug_struct("Room")
             .field("name", &self.name)
             .field("devices", &devices)
+            .field("temperature", &self.temperature)
             .finish()
     }
 }
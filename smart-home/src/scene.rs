@@ -0,0 +1,86 @@
+use crate::{devices::device::Device, errors::SmartHouseError, room::RoomDevice};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SceneAction {
+    TurnOn,
+    TurnOff,
+}
+
+impl SceneAction {
+    pub fn apply(&self, device: &mut RoomDevice) -> Result<(), SmartHouseError> {
+        match (self, device) {
+            (SceneAction::TurnOn, RoomDevice::SmartSocket(socket)) => socket.turn_on(),
+            (SceneAction::TurnOff, RoomDevice::SmartSocket(socket)) => socket.turn_off(),
+            (SceneAction::TurnOn, RoomDevice::SmartLight(light)) => light.turn_on(),
+            (SceneAction::TurnOff, RoomDevice::SmartLight(light)) => light.turn_off(),
+            (_, device) => {
+                return Err(SmartHouseError::UnsupportedActionError(
+                    device.get_name().to_string(),
+                ))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SceneEntry {
+    pub room: String,
+    pub device: String,
+    pub action: SceneAction,
+}
+
+// Named preset of device actions, e.g. "Night" or "Away".
+#[derive(Debug, Clone)]
+pub struct Scene {
+    name: String,
+    entries: Vec<SceneEntry>,
+}
+
+impl Scene {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn add_action(&mut self, room: &str, device: &str, action: SceneAction) {
+        self.entries.push(SceneEntry {
+            room: room.into(),
+            device: device.into(),
+            action,
+        });
+    }
+
+    pub fn get_entries(&self) -> impl Iterator<Item = &SceneEntry> {
+        self.entries.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::{socket::SmartSocket, thermometer::SmartThermometer};
+
+    #[test]
+    fn test_scene_action() {
+        let mut socket: RoomDevice = SmartSocket::new("Socket", "A smart socket", 100).into();
+        SceneAction::TurnOn.apply(&mut socket).unwrap();
+        assert!(matches!(&socket, RoomDevice::SmartSocket(s) if s.is_on()));
+
+        SceneAction::TurnOff.apply(&mut socket).unwrap();
+        assert!(matches!(&socket, RoomDevice::SmartSocket(s) if !s.is_on()));
+
+        let mut thermo: RoomDevice = SmartThermometer::new("Thermo", "A thermometer").into();
+        assert!(matches!(
+            SceneAction::TurnOn.apply(&mut thermo),
+            Err(SmartHouseError::UnsupportedActionError(_))
+        ));
+    }
+}
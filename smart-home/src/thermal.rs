@@ -0,0 +1,211 @@
+use std::time::Duration;
+
+use crate::{
+    errors::SmartHouseError,
+    house::House,
+    room::{Room, RoomDevice},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ThermalProperties {
+    // Energy needed to warm the room by one degree, J/K.
+    heat_capacity: f32,
+    // Heat lost to the outside per degree of difference, W/K.
+    loss_rate: f32,
+}
+
+impl ThermalProperties {
+    pub const DEFAULT_TEMPERATURE: f32 = 20.0;
+
+    // The temperature is divided by the heat capacity, so it must be positive.
+    pub fn new(heat_capacity: f32, loss_rate: f32) -> Result<Self, SmartHouseError> {
+        if !(heat_capacity.is_finite() && heat_capacity > 0.0) {
+            return Err(SmartHouseError::InvalidThermalPropertiesError(format!(
+                "heat capacity must be positive, got {}",
+                heat_capacity
+            )));
+        }
+        if !(loss_rate.is_finite() && loss_rate >= 0.0) {
+            return Err(SmartHouseError::InvalidThermalPropertiesError(format!(
+                "loss rate must not be negative, got {}",
+                loss_rate
+            )));
+        }
+        Ok(Self {
+            heat_capacity,
+            loss_rate,
+        })
+    }
+
+    pub fn get_heat_capacity(&self) -> f32 {
+        self.heat_capacity
+    }
+
+    pub fn get_loss_rate(&self) -> f32 {
+        self.loss_rate
+    }
+}
+
+impl Default for ThermalProperties {
+    fn default() -> Self {
+        // Roughly a 20 m² room with furniture and average insulation.
+        Self {
+            heat_capacity: 500_000.0,
+            loss_rate: 50.0,
+        }
+    }
+}
+
+// Advances the room temperature by `dt` using the exact solution of
+// C·dT/dt = P - k·(T - T_out), so large steps stay stable.
+pub fn step_room(room: &mut Room, outside: f32, dt: Duration) {
+    let ThermalProperties {
+        heat_capacity,
+        loss_rate,
+    } = room.get_thermal_properties().clone();
+    let power = room.heating_power() as f32;
+    let current = room.get_temperature();
+    let seconds = dt.as_secs_f32();
+
+    let temperature = if loss_rate <= 0.0 {
+        current + power * seconds / heat_capacity
+    } else {
+        let equilibrium = outside + power / loss_rate;
+        equilibrium + (current - equilibrium) * (-loss_rate * seconds / heat_capacity).exp()
+    };
+
+    room.set_temperature(temperature);
+}
+
+// Bang-bang thermostat switching a heater socket by a room thermometer reading.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Thermostat {
+    pub room: String,
+    pub thermometer: String,
+    pub heater: String,
+    pub target: f32,
+    pub hysteresis: f32,
+}
+
+impl Thermostat {
+    pub fn new(room: &str, thermometer: &str, heater: &str, target: f32) -> Self {
+        Self {
+            room: room.into(),
+            thermometer: thermometer.into(),
+            heater: heater.into(),
+            target,
+            hysteresis: 0.5,
+        }
+    }
+
+    pub fn regulate(&self, house: &mut House) -> Result<(), SmartHouseError> {
+        let room = house
+            .get_room_mut(&self.room)
+            .ok_or(SmartHouseError::RoomNotFoundError(self.room.clone()))?;
+
+        let temperature = match room.get_device(&self.thermometer) {
+            Some(RoomDevice::Thermometer(thermometer)) => thermometer.get_temperature(),
+            Some(_) => {
+                return Err(SmartHouseError::UnsupportedActionError(
+                    self.thermometer.clone(),
+                ))
+            }
+            None => {
+                return Err(SmartHouseError::DeviceNotFoundError(
+                    self.thermometer.clone(),
+                ))
+            }
+        };
+
+        match room.get_device_mut(&self.heater) {
+            Some(RoomDevice::SmartSocket(socket)) => {
+                if temperature < self.target - self.hysteresis {
+                    socket.turn_on();
+                } else if temperature > self.target + self.hysteresis {
+                    socket.turn_off();
+                }
+                Ok(())
+            }
+            Some(_) => Err(SmartHouseError::UnsupportedActionError(self.heater.clone())),
+            None => Err(SmartHouseError::DeviceNotFoundError(self.heater.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::{socket::SmartSocket, thermometer::SmartThermometer};
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn house() -> House {
+        let mut house = House::new("House");
+        house.add_room("Bedroom").unwrap();
+        let bedroom = house.get_room_mut("Bedroom").unwrap();
+        bedroom
+            .add_device(SmartSocket::heater("Heater", "Oil heater", 1000).into())
+            .unwrap();
+        bedroom
+            .add_device(SmartThermometer::new("Thermo", "Bedroom thermometer").into())
+            .unwrap();
+        house
+    }
+
+    fn thermometer(house: &House) -> f32 {
+        match house.get_room("Bedroom").unwrap().get_device("Thermo") {
+            Some(RoomDevice::Thermometer(t)) => t.get_temperature(),
+            _ => panic!("Thermometer expected"),
+        }
+    }
+
+    #[test]
+    fn test_room_cools_down_to_outside() {
+        let mut house = house();
+        assert_eq!(thermometer(&house), 20.0);
+
+        house.step_temperature(0.0, MINUTE * 60 * 24 * 7);
+        assert!(thermometer(&house).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_heater_reaches_equilibrium() {
+        let mut house = house();
+        let room = house.get_room_mut("Bedroom").unwrap();
+        if let Some(RoomDevice::SmartSocket(heater)) = room.get_device_mut("Heater") {
+            heater.turn_on();
+        }
+        assert_eq!(room.heating_power(), 1000);
+
+        // Equilibrium is outside + P / k = 0 + 1000 / 50.
+        house.step_temperature(0.0, MINUTE * 60 * 24 * 7);
+        assert!((thermometer(&house) - 20.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_invalid_properties() {
+        for (heat_capacity, loss_rate) in [(0.0, 50.0), (-1.0, 50.0), (f32::NAN, 50.0), (1.0, -1.0)]
+        {
+            assert!(matches!(
+                ThermalProperties::new(heat_capacity, loss_rate),
+                Err(SmartHouseError::InvalidThermalPropertiesError(_))
+            ));
+        }
+        let properties = ThermalProperties::new(1000.0, 0.0).unwrap();
+        assert_eq!(properties.get_heat_capacity(), 1000.0);
+    }
+
+    #[test]
+    fn test_thermostat_keeps_temperature() {
+        let mut house = house();
+        let thermostat = Thermostat::new("Bedroom", "Thermo", "Heater", 18.0);
+
+        for _ in 0..(60 * 24) {
+            thermostat.regulate(&mut house).unwrap();
+            house.step_temperature(5.0, MINUTE);
+        }
+
+        let temperature = thermometer(&house);
+        assert!((17.0..=19.0).contains(&temperature), "{temperature}");
+    }
+}
@@ -1,22 +1,37 @@
+use smart_home::config::{Config, Setting};
 use smart_home_async::devices::{
     socket::{Command, SmartSocketClient},
     thermo::SmartThermoClient,
 };
+use std::error::Error;
+
 use tokio::io::{self, AsyncBufReadExt, BufReader};
 
+const SETTINGS: &[Setting] = &[
+    Setting::addr(
+        "socket_addr",
+        "127.0.0.1:4322",
+        "address of the socket server",
+    ),
+    Setting::addr(
+        "thermo_addr",
+        "127.0.0.1:4320",
+        "address readings arrive at",
+    ),
+];
+
 #[tokio::main]
-pub async fn main() {
-    let mut socket_client = SmartSocketClient::init("127.0.0.1:4322")
-        .await
-        .expect("Expect socket client");
-    let thermo_client = SmartThermoClient::init("127.0.0.1:4320")
-        .await
-        .expect("Expect thermo client");
+pub async fn main() -> Result<(), Box<dyn Error>> {
+    let config = Config::from_env("client", SETTINGS);
+    let socket_addr = config.get_addr("socket_addr");
+    let thermo_addr = config.get_addr("thermo_addr");
+    config.init_logger();
+
+    let mut socket_client = SmartSocketClient::init(socket_addr).await?;
+    let thermo_client = SmartThermoClient::init(thermo_addr).await?;
 
     show_menu();
-    loop {
-        process_input(&mut socket_client, &thermo_client).await;
-    }
+    process_input(&mut socket_client, &thermo_client).await
 }
 
 fn show_menu() {
@@ -29,24 +44,21 @@ fn show_menu() {
     println!("_) exit");
 }
 
-async fn process_input(socket: &mut SmartSocketClient, thermo: &SmartThermoClient) {
+// Until the user exits or the input ends.
+async fn process_input(
+    socket: &mut SmartSocketClient,
+    thermo: &SmartThermoClient,
+) -> Result<(), Box<dyn Error>> {
     let stdin = io::stdin();
     let mut reader = BufReader::new(stdin).lines();
 
-    while let Ok(Some(line)) = reader.next_line().await {
+    while let Some(line) = reader.next_line().await? {
         match line.trim() {
             "1" => {
-                socket
-                    .run_command(Command::Switch)
-                    .await
-                    .expect("Failed to switch socket");
+                socket.run_command(Command::Switch).await?;
             }
             "2" => {
-                let status = socket
-                    .run_command(Command::Status)
-                    .await
-                    .expect("Failed to get status");
-
+                let status = socket.run_command(Command::Status).await?;
                 println!("Socket status: {status}");
             }
             "3" => {
@@ -55,8 +67,9 @@ async fn process_input(socket: &mut SmartSocketClient, thermo: &SmartThermoClien
             }
             _ => {
                 println!("Exiting...");
-                std::process::exit(0);
+                return Ok(());
             }
         }
     }
+    Ok(())
 }
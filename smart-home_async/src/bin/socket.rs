@@ -1,10 +1,22 @@
+use smart_home::config::{Config, Setting};
 use smart_home_async::devices::socket::SmartSocketServer;
 
+const SETTINGS: &[Setting] = &[
+    Setting::addr("socket_addr", "127.0.0.1:4322", "address to listen on"),
+    Setting::new("name", "Alisa's socket", "device name"),
+    Setting::new("description", "Smart socket", "device description"),
+];
+
 #[tokio::main]
-pub async fn main() {
-    let socket_server = SmartSocketServer::init("127.0.0.1:4322", "Alisa's socket", "Smart socket")
-        .await
-        .expect("Failed to initialize smart socket server");
+pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::from_env("socket", SETTINGS);
+    let addr = config.get_addr("socket_addr");
+    config.init_logger();
+
+    let socket_server =
+        SmartSocketServer::init(addr, config.get("name"), config.get("description")).await?;
 
     socket_server.listen().await;
+
+    Ok(())
 }
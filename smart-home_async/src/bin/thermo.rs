@@ -1,10 +1,29 @@
+use smart_home::config::{Config, Setting};
 use smart_home_async::devices::thermo::TemperatureGeneratorServer;
 
+const SETTINGS: &[Setting] = &[
+    Setting::addr(
+        "bind_addr",
+        "127.0.0.1:4321",
+        "address to send readings from",
+    ),
+    Setting::addr(
+        "thermo_addr",
+        "127.0.0.1:4320",
+        "address to send readings to",
+    ),
+];
+
 #[tokio::main]
-pub async fn main() {
-    let thermo_generator = TemperatureGeneratorServer::init("127.0.0.1:4321")
-        .await
-        .expect("Failed to initialize temperature generator");
+pub async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::from_env("thermo", SETTINGS);
+    let bind_addr = config.get_addr("bind_addr");
+    let thermo_addr = config.get_addr("thermo_addr");
+    config.init_logger();
+
+    let thermo_generator = TemperatureGeneratorServer::init(bind_addr).await?;
+
+    thermo_generator.listen(thermo_addr).await;
 
-    thermo_generator.listen("127.0.0.1:4320").await;
+    Ok(())
 }
@@ -0,0 +1,312 @@
+use std::{error::Error, fmt::Display, net::SocketAddr, sync::Arc, time::Duration};
+
+use log::{info, warn};
+use smart_home::devices::light::SmartLight;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::Mutex,
+    time::Instant,
+};
+
+// Requests are `[code, value_hi, value_lo, transition_ms_hi, transition_ms_lo]`.
+pub const REQUEST_SIZE: usize = 5;
+// Responses are `[code, brightness, color_temperature_hi, color_temperature_lo]`.
+pub const RESPONSE_SIZE: usize = 4;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LightCommand {
+    Switch,
+    Status,
+    On,
+    Off,
+    Brightness(u8, Duration),
+    ColorTemperature(u16, Duration),
+    Unknown,
+}
+
+impl From<[u8; REQUEST_SIZE]> for LightCommand {
+    fn from(buffer: [u8; REQUEST_SIZE]) -> Self {
+        let value = u16::from_be_bytes([buffer[1], buffer[2]]);
+        let transition = Duration::from_millis(u16::from_be_bytes([buffer[3], buffer[4]]) as u64);
+        match buffer[0] {
+            0 => Self::Switch,
+            1 => Self::Status,
+            2 => Self::On,
+            3 => Self::Off,
+            4 => match u8::try_from(value) {
+                Ok(brightness) => Self::Brightness(brightness, transition),
+                Err(_) => Self::Unknown,
+            },
+            5 => Self::ColorTemperature(value, transition),
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl From<LightCommand> for [u8; REQUEST_SIZE] {
+    fn from(command: LightCommand) -> [u8; REQUEST_SIZE] {
+        let (code, value, transition) = match command {
+            LightCommand::Switch => (0, 0, Duration::ZERO),
+            LightCommand::Status => (1, 0, Duration::ZERO),
+            LightCommand::On => (2, 0, Duration::ZERO),
+            LightCommand::Off => (3, 0, Duration::ZERO),
+            LightCommand::Brightness(brightness, transition) => (4, brightness as u16, transition),
+            LightCommand::ColorTemperature(value, transition) => (5, value, transition),
+            LightCommand::Unknown => (255, 0, Duration::ZERO),
+        };
+        let value = value.to_be_bytes();
+        let transition = (transition.as_millis().min(u16::MAX as u128) as u16).to_be_bytes();
+        [code, value[0], value[1], transition[0], transition[1]]
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LightResponse {
+    Ok,
+    Status {
+        is_on: bool,
+        brightness: u8,
+        color_temperature: u16,
+    },
+    Error,
+}
+
+impl Display for LightResponse {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LightResponse::Ok => write!(f, "Ok"),
+            LightResponse::Status {
+                is_on,
+                brightness,
+                color_temperature,
+            } => write!(
+                f,
+                "{}, {}%, {}K",
+                if *is_on { "Enabled" } else { "Disabled" },
+                brightness,
+                color_temperature
+            ),
+            LightResponse::Error => write!(f, "Error"),
+        }
+    }
+}
+
+impl From<[u8; RESPONSE_SIZE]> for LightResponse {
+    fn from(buffer: [u8; RESPONSE_SIZE]) -> Self {
+        let color_temperature = u16::from_be_bytes([buffer[2], buffer[3]]);
+        match buffer[0] {
+            0 => Self::Ok,
+            1 | 2 => Self::Status {
+                is_on: buffer[0] == 1,
+                brightness: buffer[1],
+                color_temperature,
+            },
+            _ => Self::Error,
+        }
+    }
+}
+
+impl From<LightResponse> for [u8; RESPONSE_SIZE] {
+    fn from(response: LightResponse) -> [u8; RESPONSE_SIZE] {
+        match response {
+            LightResponse::Ok => [0, 0, 0, 0],
+            LightResponse::Status {
+                is_on,
+                brightness,
+                color_temperature,
+            } => {
+                let color_temperature = color_temperature.to_be_bytes();
+                [
+                    if is_on { 1 } else { 2 },
+                    brightness,
+                    color_temperature[0],
+                    color_temperature[1],
+                ]
+            }
+            LightResponse::Error => [255, 0, 0, 0],
+        }
+    }
+}
+
+pub struct SmartLightClient {
+    stream: TcpStream,
+}
+
+impl SmartLightClient {
+    pub async fn init(address: impl ToSocketAddrs) -> Result<Self, Box<dyn Error>> {
+        let stream = TcpStream::connect(address).await?;
+
+        Ok(Self { stream })
+    }
+
+    pub async fn run_command(
+        &mut self,
+        command: LightCommand,
+    ) -> Result<LightResponse, Box<dyn Error>> {
+        let request: [u8; REQUEST_SIZE] = command.into();
+        self.stream.write_all(&request).await?;
+        let mut buffer = [0u8; RESPONSE_SIZE];
+        self.stream.read_exact(&mut buffer).await?;
+        Ok(buffer.into())
+    }
+}
+
+pub trait ExecLightCommand {
+    fn exec_command(&mut self, command: LightCommand) -> LightResponse;
+}
+
+impl ExecLightCommand for SmartLight {
+    fn exec_command(&mut self, command: LightCommand) -> LightResponse {
+        match command {
+            LightCommand::Switch => self.switch(),
+            LightCommand::On => self.turn_on(),
+            LightCommand::Off => self.turn_off(),
+            LightCommand::Status => {
+                return LightResponse::Status {
+                    is_on: self.is_on(),
+                    brightness: self.brightness(),
+                    color_temperature: self.color_temperature(),
+                }
+            }
+            LightCommand::Brightness(brightness, transition) => {
+                self.start_transition(brightness, self.color_temperature(), transition)
+            }
+            LightCommand::ColorTemperature(color_temperature, transition) => {
+                self.start_transition(self.brightness(), color_temperature, transition)
+            }
+            LightCommand::Unknown => return LightResponse::Error,
+        }
+
+        LightResponse::Ok
+    }
+}
+
+struct LightState {
+    light: SmartLight,
+    last_update: Instant,
+}
+
+pub struct SmartLightServer {
+    listener: TcpListener,
+    state: Arc<Mutex<LightState>>,
+}
+
+impl SmartLightServer {
+    pub async fn init(
+        address: impl ToSocketAddrs,
+        name: &str,
+        description: &str,
+    ) -> Result<Self, Box<dyn Error>> {
+        let listener = TcpListener::bind(address).await?;
+        let state = Arc::new(Mutex::new(LightState {
+            light: SmartLight::new(name, description),
+            last_update: Instant::now(),
+        }));
+        Ok(Self { listener, state })
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, std::io::Error> {
+        self.listener.local_addr()
+    }
+
+    pub async fn listen(&self) {
+        while let Ok((mut stream, addr)) = self.listener.accept().await {
+            let peer = addr.to_string();
+            info!("Accepted connection from: {peer}");
+
+            let state = self.state.clone();
+            tokio::spawn(async move {
+                let mut buffer = [0u8; REQUEST_SIZE];
+                while stream.read_exact(&mut buffer).await.is_ok() {
+                    let response = {
+                        let mut state = state.lock().await;
+                        let now = Instant::now();
+                        let elapsed = now - state.last_update;
+                        state.light.advance(elapsed);
+                        state.last_update = now;
+                        state.light.exec_command(buffer.into())
+                    };
+                    let response_buffer: [u8; RESPONSE_SIZE] = response.into();
+                    if let Err(e) = stream.write_all(&response_buffer).await {
+                        warn!("Failed to send response: {e}");
+                        break;
+                    }
+                }
+
+                info!("Connection with {peer} lost. Waiting for new connections...");
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_encoding() {
+        let commands = [
+            LightCommand::Switch,
+            LightCommand::Status,
+            LightCommand::On,
+            LightCommand::Off,
+            LightCommand::Brightness(42, Duration::from_millis(1500)),
+            LightCommand::ColorTemperature(4000, Duration::ZERO),
+        ];
+
+        for command in commands {
+            let buffer: [u8; REQUEST_SIZE] = command.clone().into();
+            assert_eq!(LightCommand::from(buffer), command);
+        }
+
+        assert_eq!(LightCommand::from([4, 1, 0, 0, 0]), LightCommand::Unknown);
+    }
+
+    #[tokio::test]
+    async fn test_client_server_interaction() {
+        let server = SmartLightServer::init("127.0.0.1:0", "test_light", "test description")
+            .await
+            .expect("Failed to initialize server");
+        let addr = server.local_addr().expect("Failed to get local address");
+
+        tokio::spawn(async move {
+            server.listen().await;
+        });
+
+        let mut client = SmartLightClient::init(addr)
+            .await
+            .expect("Failed to initialize client");
+
+        let response = client
+            .run_command(LightCommand::On)
+            .await
+            .expect("Failed to run command");
+        assert_eq!(response, LightResponse::Ok);
+
+        let response = client
+            .run_command(LightCommand::Brightness(30, Duration::ZERO))
+            .await
+            .expect("Failed to run command");
+        assert_eq!(response, LightResponse::Ok);
+
+        let response = client
+            .run_command(LightCommand::Status)
+            .await
+            .expect("Failed to run command");
+        assert_eq!(
+            response,
+            LightResponse::Status {
+                is_on: true,
+                brightness: 30,
+                color_temperature: 2700,
+            }
+        );
+
+        let response = client
+            .run_command(LightCommand::Unknown)
+            .await
+            .expect("Failed to run command");
+        assert_eq!(response, LightResponse::Error);
+    }
+}
@@ -1,2 +1,3 @@
+pub mod light;
 pub mod socket;
 pub mod thermo;
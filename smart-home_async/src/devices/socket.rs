@@ -1,5 +1,6 @@
 use std::{error::Error, fmt::Display, sync::Arc};
 
+use log::{info, warn};
 use smart_home::devices::socket::SmartSocket;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
@@ -139,7 +140,7 @@ impl SmartSocketServer {
     pub async fn listen(&self) {
         while let Ok((mut stream, addr)) = self.listener.accept().await {
             let peer = addr.to_string();
-            println!("Accepted connection from: {peer}");
+            info!("Accepted connection from: {peer}");
 
             let smart_socket = self.device.clone();
             tokio::spawn(async move {
@@ -148,12 +149,12 @@ impl SmartSocketServer {
                     let response = smart_socket.lock().await.exec_command(buffer[0].into());
                     let response_buffer: [u8; 1] = response.into();
                     if let Err(e) = stream.write_all(&response_buffer).await {
-                        eprintln!("Failed to send response: {e}");
+                        warn!("Failed to send response: {e}");
                         break;
                     }
                 }
 
-                println!("Connection with {peer} lost. Waiting for new connections...");
+                info!("Connection with {peer} lost. Waiting for new connections...");
             });
         }
     }
@@ -8,6 +8,7 @@ use std::{
     time::Duration,
 };
 
+use log::warn;
 use tokio::{
     net::{ToSocketAddrs, UdpSocket},
     sync::Mutex,
@@ -40,7 +41,7 @@ impl SmartThermoClient {
 
                 let mut buf = [0; 4];
                 if let Err(e) = time::timeout(timeout, socket_clone.recv_from(&mut buf)).await {
-                    println!("Can't receive datagram: {e}");
+                    warn!("Can't receive datagram: {e}");
                     continue;
                 }
 
@@ -110,7 +111,7 @@ impl TemperatureGeneratorServer {
             let send_result = self.listener.send_to(&bytes, receiver).await;
 
             if let Err(e) = send_result {
-                println!("Can't send temperature: {e}");
+                warn!("Can't send temperature: {e}");
             }
 
             let duration = Duration::from_secs_f32(0.5);
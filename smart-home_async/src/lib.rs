@@ -1 +1,2 @@
 pub mod devices;
+pub mod tcp;
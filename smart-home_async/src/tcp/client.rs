@@ -0,0 +1,198 @@
+use std::time::Duration;
+
+use smart_home_tcp_client::{
+    auth::Credentials,
+    devices::tcp_device::ConnectError,
+    protocol::{
+        handshake_error, handshake_read_error, io_error, ClientHandshake, ErrorResponse, Framing,
+        HandshakeStep, ProtocolCommand, ProtocolError, ServerInfo,
+    },
+};
+use tokio::{
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+    net::{TcpStream, ToSocketAddrs},
+    time,
+};
+
+use super::read_till_rn;
+
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub async fn send_handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    credentials: Option<&Credentials>,
+) -> Result<ServerInfo, ConnectError> {
+    let mut handshake = ClientHandshake::new(credentials, Framing::Lines);
+    write_handshake_line(stream, &handshake.hello()).await?;
+
+    loop {
+        let line = read_till_rn(stream).await.map_err(handshake_read_error)?;
+        match handshake.next_line(&line) {
+            HandshakeStep::Reply(reply) => write_handshake_line(stream, &reply).await?,
+            HandshakeStep::Done(choice, info) => {
+                write_handshake_line(stream, &choice).await?;
+                return Ok(info);
+            }
+            HandshakeStep::Reject(_, error) => return Err(error),
+        }
+    }
+}
+
+// Flushed, as the server answers only once it has the whole line.
+async fn write_handshake_line<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    line: &str,
+) -> Result<(), ProtocolError> {
+    stream
+        .write_all(line.as_bytes())
+        .await
+        .map_err(handshake_error)?;
+    stream.flush().await.map_err(handshake_error)
+}
+
+pub async fn send_command<S: AsyncRead + AsyncWrite + Unpin>(
+    command: impl ProtocolCommand,
+    stream: &mut S,
+) -> Result<(), ProtocolError> {
+    // Single commands are sent without credentials, use `AsyncTcpSession::open` for those.
+    send_handshake(stream, None)
+        .await
+        .map_err(|error| match error {
+            ConnectError::Protocol(error) => error,
+            error => ProtocolError::BadHandshake(error.to_string()),
+        })?;
+
+    write_command(stream, command).await
+}
+
+async fn write_command<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    command: impl ProtocolCommand,
+) -> Result<(), ProtocolError> {
+    stream
+        .write_all(command.to_string().as_bytes())
+        .await
+        .map_err(|e| io_error(e, ProtocolError::CouldNotSend))?;
+    stream
+        .flush()
+        .await
+        .map_err(|e| io_error(e, ProtocolError::CouldNotSend))
+}
+
+// Error responses of the server are returned as `ProtocolError::ServerError`.
+pub async fn receive_response<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<String, ProtocolError> {
+    let response = read_till_rn(reader).await?;
+    match ErrorResponse::from_line(&response) {
+        Some(error) => Err(ProtocolError::ServerError(error)),
+        None => Ok(response),
+    }
+}
+
+// A connection that handshakes once and then exchanges any number of commands.
+#[derive(Debug)]
+pub struct AsyncTcpSession<S = TcpStream> {
+    stream: S,
+    server_info: ServerInfo,
+}
+
+impl AsyncTcpSession {
+    pub async fn connect(addr: impl ToSocketAddrs) -> Result<Self, ConnectError> {
+        let stream = time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addr))
+            .await
+            .map_err(|_| ProtocolError::Timeout("connect".to_string()))?
+            .map_err(|e| io_error(e, ProtocolError::CouldNotSend))?;
+        Self::open(stream, None).await
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncTcpSession<S> {
+    // Handshakes over an open stream, e.g. a TLS one. Servers with keys
+    // require credentials and fail with `ConnectError::Unauthorized` without them.
+    pub async fn open(
+        mut stream: S,
+        credentials: Option<&Credentials>,
+    ) -> Result<Self, ConnectError> {
+        let server_info = send_handshake(&mut stream, credentials).await?;
+        Ok(Self {
+            stream,
+            server_info,
+        })
+    }
+
+    // Protocol version, device kind and commands agreed on in the handshake.
+    pub fn get_server_info(&self) -> &ServerInfo {
+        &self.server_info
+    }
+
+    pub async fn send(&mut self, command: impl ProtocolCommand) -> Result<String, ProtocolError> {
+        write_command(&mut self.stream, command).await?;
+        receive_response(&mut self.stream).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use smart_home::devices::socket::SmartSocket;
+    use smart_home_tcp_client::{
+        devices::{
+            socket::{SocketCommand, SocketServer},
+            tcp_device::TcpDevice,
+        },
+        protocol::{ErrorCode, OK},
+        server::ConcurrentServer,
+    };
+
+    use super::*;
+
+    // Talks to the blocking server `socket-tcp-server` runs.
+    #[tokio::test]
+    async fn test_session_with_blocking_server() {
+        let server = ConcurrentServer::new(
+            SocketServer::bind(
+                SmartSocket::new("Socket", "A smart socket", 100),
+                "127.0.0.1:0",
+            )
+            .unwrap(),
+            2,
+        );
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let mut session = AsyncTcpSession::connect(addr).await.unwrap();
+        assert_eq!(session.get_server_info().kind, "socket");
+        assert_eq!(session.send(SocketCommand::On).await.unwrap(), OK);
+        assert_eq!(session.send(SocketCommand::Status).await.unwrap(), "on\r\n");
+        assert_eq!(session.send(SocketCommand::Power).await.unwrap(), "100\r\n");
+
+        // A one-off command over a connection of its own.
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        send_command(SocketCommand::Off, &mut stream).await.unwrap();
+        assert_eq!(receive_response(&mut stream).await.unwrap(), OK);
+        assert_eq!(
+            session.send(SocketCommand::Status).await.unwrap(),
+            "off\r\n"
+        );
+
+        stream.write_all(b"dance\r\n").await.unwrap();
+        assert!(matches!(
+            receive_response(&mut stream).await,
+            Err(ProtocolError::ServerError(error)) if error.code == ErrorCode::UnknownCommand
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_connection_refused() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        assert!(matches!(
+            AsyncTcpSession::connect(addr).await,
+            Err(ConnectError::Protocol(ProtocolError::ConnectionRefused(_)))
+        ));
+    }
+}
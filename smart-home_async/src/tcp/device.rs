@@ -0,0 +1,287 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use log::{info, warn};
+use smart_home::devices::{lock::SmartLock, socket::SmartSocket};
+use smart_home_tcp_client::{
+    auth::KeyStore,
+    devices::{
+        lock::{self, LockCommand},
+        socket::{self, SocketCommand},
+        tcp_device::ConnectError,
+    },
+    protocol::{
+        parse_command, read_error_response, ErrorResponse, HandshakeStep, ProtocolCommand,
+        ProtocolError, ServerHandshake, CLIENT_HANDSHAKE,
+    },
+};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    net::{TcpListener, ToSocketAddrs},
+    sync::Mutex,
+};
+
+use super::{read_line_bytes, read_till_rn};
+
+// A device served over the text protocol, like `TcpDevice` of the blocking servers.
+pub trait AsyncTcpDevice: Send + 'static {
+    type Command: ProtocolCommand + Send;
+    // Announced to clients in the handshake.
+    const KIND: &'static str;
+    const COMMANDS: &'static [&'static str];
+
+    // Applies the command to the device and returns the response line.
+    fn execute(&mut self, command: Self::Command) -> String;
+}
+
+impl AsyncTcpDevice for SmartSocket {
+    type Command = SocketCommand;
+    const KIND: &'static str = "socket";
+    const COMMANDS: &'static [&'static str] = socket::COMMANDS;
+
+    fn execute(&mut self, command: SocketCommand) -> String {
+        socket::execute(self, command)
+    }
+}
+
+impl AsyncTcpDevice for SmartLock {
+    type Command = LockCommand;
+    const KIND: &'static str = "lock";
+    const COMMANDS: &'static [&'static str] = lock::COMMANDS;
+
+    fn execute(&mut self, command: LockCommand) -> String {
+        lock::execute(self, command)
+    }
+}
+
+// See `ServerHandshake`. Sessions always use lines, requests for other framings are declined.
+pub async fn try_handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    kind: &str,
+    commands: &[&str],
+    keys: Option<&KeyStore>,
+) -> Result<(), ConnectError> {
+    let mut handshake = ServerHandshake::new(kind, commands, keys).with_lines_only();
+    let mut tag = [0; CLIENT_HANDSHAKE.len()];
+    stream.read_exact(&mut tag).await?;
+    let mut step = handshake.start(&tag);
+    loop {
+        step = match step {
+            HandshakeStep::Reply(reply) => {
+                stream.write_all(reply.as_bytes()).await?;
+                let line = read_till_rn(stream)
+                    .await
+                    .map_err(|_| ConnectError::BadHandshake)?;
+                handshake.next_line(&line)
+            }
+            HandshakeStep::Done(reply, _) => {
+                stream.write_all(reply.as_bytes()).await?;
+                return Ok(());
+            }
+            HandshakeStep::Reject(reply, error) => {
+                stream.write_all(reply.as_bytes()).await?;
+                return Err(error);
+            }
+        };
+    }
+}
+
+// Commands that cannot be parsed are answered with an error response and
+// reported as `ProtocolError::InvalidCommand`, which does not end a session.
+// Overlong lines are answered as well, but end the session.
+pub async fn read_command<C: ProtocolCommand, S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+) -> Result<C, ProtocolError> {
+    let line = match read_line_bytes(stream).await {
+        Ok(line) => line,
+        Err(error) => {
+            if let Some(response) = read_error_response(&error) {
+                send_response(stream, &response.to_line()).await?;
+            }
+            return Err(error);
+        }
+    };
+    match parse_command(line) {
+        Ok(command) => Ok(command),
+        Err(error) => {
+            send_response(stream, &error.to_line()).await?;
+            Err(ProtocolError::InvalidCommand)
+        }
+    }
+}
+
+pub async fn send_response<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    response: &str,
+) -> Result<(), ProtocolError> {
+    stream
+        .write_all(response.as_bytes())
+        .await
+        .map_err(|_| ProtocolError::CouldNotSend)
+}
+
+// Handles commands of one client until it disconnects. The device is locked
+// only while a command is executed, so sessions run concurrently.
+pub async fn serve<D: AsyncTcpDevice, S: AsyncRead + AsyncWrite + Unpin>(
+    device: &Mutex<D>,
+    stream: &mut S,
+) -> Result<(), ProtocolError> {
+    loop {
+        let command = match read_command::<D::Command, S>(stream).await {
+            Ok(command) => command,
+            Err(ProtocolError::Closed) => return Ok(()),
+            Err(ProtocolError::InvalidCommand) => continue,
+            Err(error) => return Err(error),
+        };
+        if command.is_watch() {
+            let error = ErrorResponse::unsupported(&command.to_string());
+            send_response(stream, &error.to_line()).await?;
+            continue;
+        }
+        let response = device.lock().await.execute(command);
+        send_response(stream, &response).await?;
+    }
+}
+
+// Serves every connection in a task of its own.
+#[derive(Debug)]
+pub struct AsyncTcpServer<D> {
+    listener: TcpListener,
+    device: Arc<Mutex<D>>,
+    // Only clients with a key in the store are served, if set.
+    keys: Option<Arc<KeyStore>>,
+}
+
+impl<D: AsyncTcpDevice> AsyncTcpServer<D> {
+    pub async fn bind(device: D, addr: impl ToSocketAddrs) -> Result<Self, std::io::Error> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+            device: Arc::new(Mutex::new(device)),
+            keys: None,
+        })
+    }
+
+    pub fn with_keys(mut self, keys: KeyStore) -> Self {
+        self.keys = Some(Arc::new(keys));
+        self
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, std::io::Error> {
+        self.listener.local_addr()
+    }
+
+    pub fn get_device(&self) -> Arc<Mutex<D>> {
+        self.device.clone()
+    }
+
+    pub async fn run(self) {
+        while let Ok((mut stream, addr)) = self.listener.accept().await {
+            info!("Accepted connection from: {}", addr);
+            let device = self.device.clone();
+            let keys = self.keys.clone();
+            tokio::spawn(async move {
+                if let Err(error) =
+                    try_handshake(&mut stream, D::KIND, D::COMMANDS, keys.as_deref()).await
+                {
+                    warn!("Handshake with {} failed: {}", addr, error);
+                    return;
+                }
+                if let Err(error) = serve(&device, &mut stream).await {
+                    warn!("Connection error: {}", error);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use smart_home_tcp_client::{
+        auth::Credentials,
+        client::TcpSession,
+        protocol::{ErrorCode, OK},
+    };
+
+    use super::*;
+    use crate::tcp::client::AsyncTcpSession;
+
+    async fn server() -> AsyncTcpServer<SmartSocket> {
+        AsyncTcpServer::bind(
+            SmartSocket::new("Socket", "A smart socket", 100),
+            "127.0.0.1:0",
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_async_server() {
+        let server = server().await;
+        let addr = server.local_addr().unwrap();
+        let device = server.get_device();
+        tokio::spawn(server.run());
+
+        let mut first = AsyncTcpSession::connect(addr).await.unwrap();
+        let mut second = AsyncTcpSession::connect(addr).await.unwrap();
+        assert_eq!(first.get_server_info().kind, "socket");
+        assert_eq!(first.send(SocketCommand::Switch).await.unwrap(), OK);
+        assert_eq!(second.send(SocketCommand::Status).await.unwrap(), "on\r\n");
+        assert!(device.lock().await.is_on());
+
+        assert!(matches!(
+            second.send(LockCommand::Lock).await,
+            Err(ProtocolError::ServerError(error)) if error.code == ErrorCode::UnknownCommand
+        ));
+        assert_eq!(second.send(SocketCommand::Off).await.unwrap(), OK);
+
+        // Blocking clients are served as well.
+        let status = tokio::task::spawn_blocking(move || {
+            let mut session = TcpSession::connect(addr).unwrap();
+            session.send(SocketCommand::Status).unwrap()
+        })
+        .await
+        .unwrap();
+        assert_eq!(status, "off\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_authenticated_server() {
+        let mut keys = KeyStore::new();
+        keys.add_client("panel", b"secret");
+        let server = server().await.with_keys(keys);
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let credentials = Credentials::new("panel", b"secret");
+        let mut session = AsyncTcpSession::open(stream, Some(&credentials))
+            .await
+            .unwrap();
+        assert_eq!(
+            session.send(SocketCommand::Status).await.unwrap(),
+            "off\r\n"
+        );
+
+        // Every handshake line is flushed, also from buffered streams.
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let open = AsyncTcpSession::open(tokio::io::BufStream::new(stream), Some(&credentials));
+        let mut session = tokio::time::timeout(std::time::Duration::from_secs(5), open)
+            .await
+            .expect("Handshake stalled")
+            .unwrap();
+        assert_eq!(
+            session.send(SocketCommand::Status).await.unwrap(),
+            "off\r\n"
+        );
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let wrong = Credentials::new("panel", b"guess");
+        assert!(matches!(
+            AsyncTcpSession::open(stream, Some(&wrong)).await,
+            Err(ConnectError::Unauthorized(_))
+        ));
+        assert!(matches!(
+            AsyncTcpSession::connect(addr).await,
+            Err(ConnectError::Unauthorized(_))
+        ));
+    }
+}
@@ -0,0 +1,27 @@
+// The line-based text protocol of `smart-home_tcp`, for tokio applications.
+// Clients and servers here talk to the blocking ones and the other way round.
+pub mod client;
+pub mod device;
+
+use smart_home_tcp_client::protocol::{LineBuffer, ProtocolError};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub async fn read_till_rn<R: AsyncRead + Unpin>(reader: &mut R) -> Result<String, ProtocolError> {
+    String::from_utf8(read_line_bytes(reader).await?).map_err(|_| ProtocolError::InvalidResponse)
+}
+
+// Reads a raw line including the trailing `\r\n`, up to `MAX_LINE_LEN` bytes.
+pub async fn read_line_bytes<R: AsyncRead + Unpin>(
+    reader: &mut R,
+) -> Result<Vec<u8>, ProtocolError> {
+    let mut line = LineBuffer::default();
+    loop {
+        let byte = reader
+            .read_u8()
+            .await
+            .map_err(|error| line.read_error(error))?;
+        if line.push(byte)? {
+            return Ok(line.into_bytes());
+        }
+    }
+}
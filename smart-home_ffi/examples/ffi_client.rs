@@ -1,3 +1,5 @@
+use std::ffi::{c_char, c_void, CStr};
+
 use libloading::{Library, Symbol};
 
 #[repr(u8)]
@@ -7,10 +9,31 @@ pub enum ReturnCode {
     ConnectionError = 1,
     ParseError = 2,
     ProtocolError = 3,
+    ServerError = 4,
+    Timeout = 5,
 }
 
 type TogglePowerFn = unsafe extern "C" fn(addr: *const i8) -> ReturnCode;
 type GetStatusFn = unsafe extern "C" fn(addr: *const i8) -> ReturnCode;
+type SessionConnectFn = unsafe extern "C" fn(addr: *const i8) -> *mut c_void;
+type SessionCommandFn = unsafe extern "C" fn(session: *mut c_void) -> ReturnCode;
+type SessionCloseFn = unsafe extern "C" fn(session: *mut c_void);
+type LastErrorFn = unsafe extern "C" fn() -> *const c_char;
+
+fn print_result(name: &str, result: ReturnCode, last_error: LastErrorFn) {
+    match result {
+        ReturnCode::ServerError => {
+            let message = unsafe { CStr::from_ptr(last_error()) };
+            println!(
+                "{} result: {:?} ({})",
+                name,
+                result,
+                message.to_string_lossy()
+            );
+        }
+        result => println!("{} result: {:?}", name, result),
+    }
+}
 
 fn main() {
     let lib = unsafe { Library::new("../target/debug/libsmart_home_ffi.dylib") }
@@ -19,13 +42,38 @@ fn main() {
         unsafe { lib.get(b"toggle_power") }.expect("Failed to get toggle_power");
     let get_status: Symbol<'_, GetStatusFn> =
         unsafe { lib.get(b"get_status") }.expect("Failed to get get_status");
+    let last_error: Symbol<'_, LastErrorFn> =
+        unsafe { lib.get(b"last_error") }.expect("Failed to get last_error");
 
     let addr = "127.0.0.1:55331\0";
     let addr_ptr = addr.as_ptr() as *const i8;
 
     let result = unsafe { toggle_power(addr_ptr) };
-    println!("Toggle power result: {:?}", result);
+    print_result("Toggle power", result, *last_error);
 
     let result = unsafe { get_status(addr_ptr) };
-    println!("Get status result: {:?}", result);
+    print_result("Get status", result, *last_error);
+
+    let session_connect: Symbol<'_, SessionConnectFn> =
+        unsafe { lib.get(b"session_connect") }.expect("Failed to get session_connect");
+    let session_toggle_power: Symbol<'_, SessionCommandFn> =
+        unsafe { lib.get(b"session_toggle_power") }.expect("Failed to get session_toggle_power");
+    let session_get_status: Symbol<'_, SessionCommandFn> =
+        unsafe { lib.get(b"session_get_status") }.expect("Failed to get session_get_status");
+    let session_close: Symbol<'_, SessionCloseFn> =
+        unsafe { lib.get(b"session_close") }.expect("Failed to get session_close");
+
+    let session = unsafe { session_connect(addr_ptr) };
+    if session.is_null() {
+        println!("Failed to open session");
+        return;
+    }
+
+    // Both commands go over the same connection.
+    let result = unsafe { session_toggle_power(session) };
+    print_result("Session toggle power", result, *last_error);
+    let result = unsafe { session_get_status(session) };
+    print_result("Session get status", result, *last_error);
+
+    unsafe { session_close(session) };
 }
@@ -1,8 +1,13 @@
-use std::{ffi::CStr, net::TcpStream};
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+};
 
 use smart_home_tcp_client::{
-    client::{receive_response, send_command},
+    client::{connect, receive_response, send_command, TcpSession, Timeouts},
     devices::socket::SocketCommand,
+    protocol::{ProtocolCommand, ProtocolError},
+    retry::RetryPolicy,
 };
 
 #[repr(u8)]
@@ -11,44 +16,156 @@ pub enum ReturnCode {
     ConnectionError = 1,
     ParseError = 2,
     ProtocolError = 3,
+    // The server answered with an error response, see `last_error`.
+    ServerError = 4,
+    // The server did not answer in time.
+    Timeout = 5,
 }
 
-#[no_mangle]
-pub extern "C" fn toggle_power(addr: *const i8) -> ReturnCode {
-    let addr = unsafe { CStr::from_ptr(addr).to_string_lossy() };
-    let addr = addr.to_string();
-    let mut stream = match TcpStream::connect(addr) {
-        Ok(stream) => stream,
-        Err(_) => return ReturnCode::ConnectionError,
-    };
-    if let Err(_) = send_command(SocketCommand::Switch, &mut stream) {
-        return ReturnCode::ProtocolError;
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn response_error(error: ProtocolError) -> ReturnCode {
+    match error {
+        ProtocolError::ServerError(error) => {
+            let message = CString::new(error.to_string()).unwrap_or_default();
+            LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+            ReturnCode::ServerError
+        }
+        _ => ReturnCode::ParseError,
     }
+}
 
-    let response = receive_response(&mut stream);
-    if let Err(_) = response {
-        return ReturnCode::ParseError;
+fn error_code(error: ProtocolError) -> ReturnCode {
+    match error {
+        ProtocolError::ConnectionRefused(_) | ProtocolError::Closed => ReturnCode::ConnectionError,
+        ProtocolError::Timeout(_) => ReturnCode::Timeout,
+        error @ (ProtocolError::ServerError(_) | ProtocolError::InvalidResponse) => {
+            response_error(error)
+        }
+        _ => ReturnCode::ProtocolError,
     }
+}
 
-    ReturnCode::Ok
+// Sends the command over a connection of its own, retrying with the default policy.
+fn run_command(addr: &str, command: SocketCommand) -> ReturnCode {
+    let result = RetryPolicy::default().run(command.is_idempotent(), || {
+        let mut stream = connect(addr, &Timeouts::default())?;
+        send_command(command.clone(), &mut stream)?;
+        receive_response(&mut stream)
+    });
+    match result {
+        Ok(response) if command.parse_response(&response).is_ok() => ReturnCode::Ok,
+        Ok(_) => ReturnCode::ParseError,
+        Err(error) => error_code(error),
+    }
 }
 
+/// Returns the message of the last `ServerError` on this thread, or null if there was none.
+/// The string stays valid until the next call that fails with `ServerError`.
 #[no_mangle]
-pub extern "C" fn get_status(addr: *const i8) -> ReturnCode {
+pub extern "C" fn last_error() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(std::ptr::null(), |message| message.as_ptr())
+    })
+}
+
+/// # Safety
+///
+/// `addr` must be a valid pointer to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn toggle_power(addr: *const i8) -> ReturnCode {
+    let addr = unsafe { CStr::from_ptr(addr).to_string_lossy() };
+    run_command(&addr, SocketCommand::Switch)
+}
+
+/// # Safety
+///
+/// `addr` must be a valid pointer to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn get_status(addr: *const i8) -> ReturnCode {
     let addr = unsafe { CStr::from_ptr(addr).to_string_lossy() };
-    let addr = addr.to_string();
-    let mut stream = match TcpStream::connect(addr) {
-        Ok(stream) => stream,
-        Err(_) => return ReturnCode::ConnectionError,
+    run_command(&addr, SocketCommand::Status)
+}
+
+// A session opened by `session_connect`. It is dropped after any failure but an
+// error response, as e.g. a late answer to a timed-out command would otherwise be
+// read as the answer to the next one.
+pub struct Session {
+    session: Option<TcpSession>,
+}
+
+// `session` must be null or a pointer returned by `session_connect` and not yet closed.
+unsafe fn session_send(session: *mut Session, command: SocketCommand) -> ReturnCode {
+    let Some(session) = (unsafe { session.as_mut() }) else {
+        return ReturnCode::ConnectionError;
     };
-    if let Err(_) = send_command(SocketCommand::Status, &mut stream) {
-        return ReturnCode::ProtocolError;
+    let Some(current) = session.session.as_mut() else {
+        return ReturnCode::ConnectionError;
+    };
+    let result = current.send(command.clone());
+    // Error responses leave the session usable, anything else may not.
+    if !matches!(result, Ok(_) | Err(ProtocolError::ServerError(_))) {
+        session.session = None;
+    }
+    match result {
+        Ok(response) if command.parse_response(&response).is_ok() => ReturnCode::Ok,
+        Ok(_) => ReturnCode::ParseError,
+        Err(error) => error_code(error),
     }
+}
 
-    let response = receive_response(&mut stream);
-    if let Err(_) = response {
-        return ReturnCode::ParseError;
+/// Opens a session that keeps the connection for many commands.
+/// Returns null if the connection or the handshake failed.
+///
+/// # Safety
+///
+/// `addr` must be a valid pointer to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn session_connect(addr: *const i8) -> *mut Session {
+    let addr = unsafe { CStr::from_ptr(addr).to_string_lossy() };
+    match TcpSession::connect(addr.to_string()) {
+        Ok(session) => Box::into_raw(Box::new(Session {
+            session: Some(session),
+        })),
+        Err(_) => std::ptr::null_mut(),
     }
+}
 
-    ReturnCode::Ok
+/// Returns `ConnectionError` once a command failed with anything but `ServerError`,
+/// until the session is closed and opened again.
+///
+/// # Safety
+///
+/// `session` must be a pointer returned by `session_connect` and not yet closed.
+#[no_mangle]
+pub unsafe extern "C" fn session_toggle_power(session: *mut Session) -> ReturnCode {
+    unsafe { session_send(session, SocketCommand::Switch) }
+}
+
+/// Returns `ConnectionError` once a command failed with anything but `ServerError`,
+/// until the session is closed and opened again.
+///
+/// # Safety
+///
+/// `session` must be a pointer returned by `session_connect` and not yet closed.
+#[no_mangle]
+pub unsafe extern "C" fn session_get_status(session: *mut Session) -> ReturnCode {
+    unsafe { session_send(session, SocketCommand::Status) }
+}
+
+/// Closes the session and frees it.
+///
+/// # Safety
+///
+/// `session` must be a pointer returned by `session_connect` or null, and must
+/// not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn session_close(session: *mut Session) {
+    if !session.is_null() {
+        drop(unsafe { Box::from_raw(session) });
+    }
 }
@@ -3,20 +3,50 @@ use iced::{
     widget::{Button, Column, Container, Row, Text, TextInput},
     Alignment, Element, Length,
 };
-use smart_home_gui::tcp_client::SmartSocketClient;
+use smart_home::config::{Config, Setting};
+use smart_home_gui::tcp_client::{
+    MeterReading, SmartMeterClient, SmartSocketClient, ThermometerClient,
+};
 
+use std::error::Error;
 use std::sync::{Arc, Mutex};
 
-const DEFAULT_HOST: &str = "127.0.0.1";
-const DEFAULT_PORT: u16 = 55331;
+// Pre-filled in the form, the meter is read from the house server.
+const SETTINGS: &[Setting] = &[
+    Setting::addr("addr", "127.0.0.1:55331", "address of the socket server"),
+    Setting::addr(
+        "house_addr",
+        "127.0.0.1:55332",
+        "address of the house server",
+    ),
+    Setting::addr(
+        "thermometer_addr",
+        "127.0.0.1:55335",
+        "address of the thermometer server",
+    ),
+];
+
+pub fn main() -> Result<(), Box<dyn Error>> {
+    let config = Config::from_env("smart-home-gui", SETTINGS);
+    let (host, port) = split_addr(config.get_addr("addr"));
+    let (_, meter_port) = split_addr(config.get_addr("house_addr"));
+    let (_, thermometer_port) = split_addr(config.get_addr("thermometer_addr"));
+    config.init_logger();
 
-pub fn main() -> iced::Result {
     iced::application(
         "Smart Socket Control",
         SmartSocketApp::update,
         SmartSocketApp::view,
     )
-    .run_with(SmartSocketApp::new)
+    .run_with(move || SmartSocketApp::new(host, port, meter_port, thermometer_port))?;
+
+    Ok(())
+}
+
+// The address is validated by `Config::get_addr`.
+fn split_addr(addr: &str) -> (String, String) {
+    let (host, port) = addr.rsplit_once(':').unwrap_or((addr, ""));
+    (host.to_owned(), port.to_owned())
 }
 
 #[derive(Debug)]
@@ -26,6 +56,12 @@ struct SmartSocketApp {
     error_message: Option<String>,
     host: String,
     port: String,
+    meter_port: String,
+    meter_client: Option<SmartMeterClient>,
+    meter_reading: Option<MeterReading>,
+    thermometer_port: String,
+    thermometer_client: Option<ThermometerClient>,
+    temperature: Option<f32>,
 }
 
 #[derive(Debug, Clone)]
@@ -34,17 +70,32 @@ enum Message {
     Connect,
     HostInput(String),
     PortInput(String),
+    MeterPortInput(String),
+    ReadMeter,
+    ThermometerPortInput(String),
+    ReadTemperature,
 }
 
 impl SmartSocketApp {
-    fn new() -> (Self, Task<Message>) {
+    fn new(
+        host: String,
+        port: String,
+        meter_port: String,
+        thermometer_port: String,
+    ) -> (Self, Task<Message>) {
         (
             SmartSocketApp {
                 power_state: false,
                 client: None,
                 error_message: None,
-                host: DEFAULT_HOST.to_string(),
-                port: DEFAULT_PORT.to_string(),
+                host,
+                port,
+                meter_port,
+                meter_client: None,
+                meter_reading: None,
+                thermometer_port,
+                thermometer_client: None,
+                temperature: None,
             },
             Task::none(),
         )
@@ -60,9 +111,9 @@ impl SmartSocketApp {
                         self.error_message = None;
 
                         if let Ok(mut client) = self.client.as_ref().unwrap().lock() {
-                            if let Ok(status) = client.get_status() {
-                                println!("Status: {}", status);
-                                self.power_state = status == "on";
+                            if let Ok(is_on) = client.get_status() {
+                                println!("Status: {}", if is_on { "on" } else { "off" });
+                                self.power_state = is_on;
                             }
                         }
                     }
@@ -77,8 +128,8 @@ impl SmartSocketApp {
                     if let Ok(mut client) = client.lock() {
                         match client.toggle_power() {
                             Ok(_) => {
-                                if let Ok(status) = client.get_status() {
-                                    self.power_state = status == "on";
+                                if let Ok(is_on) = client.get_status() {
+                                    self.power_state = is_on;
                                 }
                             }
                             Err(e) => {
@@ -93,16 +144,65 @@ impl SmartSocketApp {
             }
             Message::HostInput(value) => {
                 self.host = value;
+                self.meter_client = None;
+                self.thermometer_client = None;
                 Task::none()
             }
             Message::PortInput(value) => {
                 self.port = value;
                 Task::none()
             }
+            Message::MeterPortInput(value) => {
+                self.meter_port = value;
+                self.meter_client = None;
+                Task::none()
+            }
+            Message::ReadMeter => {
+                let client = match self.meter_client.take() {
+                    Some(client) => Ok(client),
+                    None => SmartMeterClient::new(&format!("{}:{}", self.host, self.meter_port)),
+                };
+                // A failed session is dropped and reopened on the next click.
+                match client.and_then(|mut client| client.get_reading().map(|r| (client, r))) {
+                    Ok((client, reading)) => {
+                        self.meter_client = Some(client);
+                        self.meter_reading = Some(reading);
+                        self.error_message = None;
+                    }
+                    Err(e) => {
+                        self.error_message = Some(format!("Failed to read meter: {}", e));
+                    }
+                }
+                Task::none()
+            }
+            Message::ThermometerPortInput(value) => {
+                self.thermometer_port = value;
+                self.thermometer_client = None;
+                Task::none()
+            }
+            Message::ReadTemperature => {
+                let client = match self.thermometer_client.take() {
+                    Some(client) => Ok(client),
+                    None => {
+                        ThermometerClient::new(&format!("{}:{}", self.host, self.thermometer_port))
+                    }
+                };
+                match client.and_then(|mut client| client.get_temperature().map(|t| (client, t))) {
+                    Ok((client, temperature)) => {
+                        self.thermometer_client = Some(client);
+                        self.temperature = Some(temperature);
+                        self.error_message = None;
+                    }
+                    Err(e) => {
+                        self.error_message = Some(format!("Failed to read temperature: {}", e));
+                    }
+                }
+                Task::none()
+            }
         }
     }
 
-    fn view(&self) -> Element<Message> {
+    fn view(&self) -> Element<'_, Message> {
         let title = Text::new("Smart Socket Control").size(40);
 
         let host_settings = Row::new()
@@ -141,6 +241,43 @@ impl SmartSocketApp {
         }))
         .on_press(Message::TogglePower);
 
+        let meter_settings = Row::new()
+            .push(Text::new("Meter port: ").size(14))
+            .push(
+                TextInput::new("Meter port", &self.meter_port)
+                    .on_input(Message::MeterPortInput)
+                    .padding(5)
+                    .width(Length::Fixed(100.0)),
+            )
+            .push(Button::new(Text::new("Read meter")).on_press(Message::ReadMeter))
+            .spacing(10);
+
+        let meter_status = Text::new(match &self.meter_reading {
+            Some(reading) => format!(
+                "House power: {} W, energy: {:.1} Wh",
+                reading.power, reading.energy
+            ),
+            None => "House power: unknown".to_string(),
+        })
+        .size(20);
+
+        let thermometer_settings = Row::new()
+            .push(Text::new("Thermometer port: ").size(14))
+            .push(
+                TextInput::new("Thermometer port", &self.thermometer_port)
+                    .on_input(Message::ThermometerPortInput)
+                    .padding(5)
+                    .width(Length::Fixed(100.0)),
+            )
+            .push(Button::new(Text::new("Read temperature")).on_press(Message::ReadTemperature))
+            .spacing(10);
+
+        let thermometer_status = Text::new(match self.temperature {
+            Some(temperature) => format!("Temperature: {:.1} °C", temperature),
+            None => "Temperature: unknown".to_string(),
+        })
+        .size(20);
+
         let mut content = Column::new()
             .push(title)
             .push(host_settings)
@@ -148,6 +285,10 @@ impl SmartSocketApp {
             .push(connect_button)
             .push(power_status)
             .push(power_button)
+            .push(meter_settings)
+            .push(meter_status)
+            .push(thermometer_settings)
+            .push(thermometer_status)
             .spacing(24)
             .align_x(Alignment::Center);
 
@@ -1,46 +1,214 @@
 use smart_home_tcp_client::{
-    client::{receive_response, send_command},
-    devices::socket::SocketCommand,
-    protocol::{ParseError, ProtocolError},
+    client::TcpSession,
+    devices::{
+        socket::{SocketCommand, SocketResponse},
+        tcp_device::ConnectError,
+        thermometer::{ThermometerCommand, ThermometerResponse},
+    },
+    house::HouseCommand,
+    protocol::{ErrorResponse, ParseError, ProtocolCommand, ProtocolError},
+    retry::{RetryPolicy, Retryable},
 };
-use std::net::TcpStream;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum SocketError {
     #[error("Failed to connect to socket: {0}")]
     ConnectionError(#[from] std::io::Error),
+    #[error("Failed to open session: {0}")]
+    SessionError(#[from] ConnectError),
     #[error("Failed to parse response: {0}")]
     ParseError(#[from] ParseError),
     #[error("Protocol error: {0}")]
-    ProtocolError(#[from] ProtocolError),
+    ProtocolError(ProtocolError),
+    #[error("Server error: {0}")]
+    ServerError(ErrorResponse),
+    #[error("Unexpected response: {0}")]
+    UnexpectedResponse(String),
 }
 
+impl From<ProtocolError> for SocketError {
+    fn from(error: ProtocolError) -> Self {
+        match error {
+            ProtocolError::ServerError(error) => SocketError::ServerError(error),
+            error => SocketError::ProtocolError(error),
+        }
+    }
+}
+
+impl Retryable for SocketError {
+    fn is_retryable(&self, idempotent: bool) -> bool {
+        match self {
+            SocketError::ConnectionError(error) => error.is_retryable(idempotent),
+            SocketError::SessionError(error) => error.is_retryable(idempotent),
+            SocketError::ProtocolError(error) => error.is_retryable(idempotent),
+            _ => false,
+        }
+    }
+}
+
+// Keeps one session open, so clicks do not reconnect. A session that failed is
+// dropped and reopened on the next attempt.
 #[derive(Debug)]
-pub struct SmartSocketClient {
+struct RetryingSession {
     addr: String,
+    session: Option<TcpSession>,
+    policy: RetryPolicy,
+}
+
+impl RetryingSession {
+    fn connect(addr: &str) -> Result<Self, SocketError> {
+        let policy = RetryPolicy::default();
+        let session = policy.run(true, || TcpSession::connect(addr))?;
+        Ok(Self {
+            addr: addr.to_owned(),
+            session: Some(session),
+            policy,
+        })
+    }
+
+    fn send<C: ProtocolCommand + Clone>(&mut self, command: C) -> Result<String, SocketError> {
+        let Self {
+            addr,
+            session,
+            policy,
+        } = self;
+        policy.run(command.is_idempotent(), || {
+            let mut current = match session.take() {
+                Some(current) => current,
+                None => TcpSession::connect(addr.as_str())?,
+            };
+            let result = current.send(command.clone());
+            // Error responses leave the session usable, anything else may not.
+            if matches!(result, Ok(_) | Err(ProtocolError::ServerError(_))) {
+                *session = Some(current);
+            }
+            Ok(result?)
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct SmartSocketClient {
+    session: RetryingSession,
 }
 
 impl SmartSocketClient {
     pub fn new(addr: &str) -> Result<Self, SocketError> {
         Ok(Self {
-            addr: addr.to_string(),
+            session: RetryingSession::connect(addr)?,
+        })
+    }
+
+    fn run(&mut self, command: SocketCommand) -> Result<SocketResponse, SocketError> {
+        let response = self.session.send(command.clone())?;
+        Ok(command.parse_response(&response)?)
+    }
+
+    pub fn toggle_power(&mut self) -> Result<(), SocketError> {
+        self.run(SocketCommand::Switch)?;
+        Ok(())
+    }
+
+    // Whether the socket is on.
+    pub fn get_status(&mut self) -> Result<bool, SocketError> {
+        match self.run(SocketCommand::Status)? {
+            SocketResponse::Status(is_on) => Ok(is_on),
+            other => Err(SocketError::UnexpectedResponse(other.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeterReading {
+    // W
+    pub power: u32,
+    // Wh
+    pub energy: f64,
+}
+
+// Reads the smart meter of a house served by `house-tcp-server`.
+#[derive(Debug)]
+pub struct SmartMeterClient {
+    session: RetryingSession,
+}
+
+impl SmartMeterClient {
+    pub fn new(addr: &str) -> Result<Self, SocketError> {
+        Ok(Self {
+            session: RetryingSession::connect(addr)?,
+        })
+    }
+
+    pub fn get_reading(&mut self) -> Result<MeterReading, SocketError> {
+        let response = self.session.send(HouseCommand::Meter(None))?;
+        parse_reading(response.trim())
+    }
+}
+
+// Reads a thermometer served by `thermometer-tcp-server`.
+#[derive(Debug)]
+pub struct ThermometerClient {
+    session: RetryingSession,
+}
+
+impl ThermometerClient {
+    pub fn new(addr: &str) -> Result<Self, SocketError> {
+        Ok(Self {
+            session: RetryingSession::connect(addr)?,
         })
     }
 
-    pub fn toggle_power(&mut self) -> Result<String, SocketError> {
-        let mut stream = TcpStream::connect(&self.addr)?;
-        send_command(SocketCommand::Switch, &mut stream)?;
+    // °C
+    pub fn get_temperature(&mut self) -> Result<f32, SocketError> {
+        let response = self.session.send(ThermometerCommand::Temperature)?;
+        match ThermometerCommand::Temperature.parse_response(&response)? {
+            ThermometerResponse::Temperature(temperature) => Ok(temperature),
+            other => Err(SocketError::UnexpectedResponse(other.to_string())),
+        }
+    }
+}
+
+fn parse_reading(response: &str) -> Result<MeterReading, SocketError> {
+    let unexpected = || SocketError::UnexpectedResponse(response.to_string());
+    let (power, energy) = response.split_once(' ').ok_or_else(unexpected)?;
+    Ok(MeterReading {
+        power: power.parse().map_err(|_| unexpected())?,
+        energy: energy.parse().map_err(|_| unexpected())?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let response = receive_response(&mut stream)?;
-        Ok(response.trim().to_string())
+    #[test]
+    fn test_parse_reading() {
+        assert_eq!(
+            parse_reading("2150 12.500").unwrap(),
+            MeterReading {
+                power: 2150,
+                energy: 12.5
+            }
+        );
+        assert!(parse_reading("Device not found: smart meter").is_err());
     }
 
-    pub fn get_status(&mut self) -> Result<String, SocketError> {
-        let mut stream = TcpStream::connect(&self.addr)?;
-        send_command(SocketCommand::Status, &mut stream)?;
+    #[test]
+    fn test_server_error() {
+        use smart_home_tcp_client::protocol::ErrorCode;
 
-        let response = receive_response(&mut stream)?;
-        Ok(response.trim().to_string())
+        let error = SocketError::from(ProtocolError::ServerError(ErrorResponse::new(
+            ErrorCode::DeviceFault,
+            "Device not found: smart meter",
+        )));
+        assert_eq!(
+            error.to_string(),
+            "Server error: Device not found: smart meter (device fault)"
+        );
+        assert!(matches!(
+            SocketError::from(ProtocolError::CouldNotSend),
+            SocketError::ProtocolError(_)
+        ));
     }
 }
@@ -0,0 +1,60 @@
+use std::{error::Error, thread, time::Duration};
+
+use smart_home::{
+    config::{usage, Config, Setting},
+    devices::{socket::SmartSocket, thermometer::SmartThermometer},
+    house::House,
+};
+use smart_home_sim::{fault::Faults, fleet::Fleet};
+
+const SETTINGS: &[Setting] = &[
+    Setting::number("sockets", "1", "simulated sockets"),
+    Setting::number("thermometers", "1", "simulated thermometers"),
+    Setting::number("latency_ms", "0", "delay added to every message, ms"),
+    Setting::rate("drop_rate", "0", "probability of a datagram being lost"),
+    Setting::rate(
+        "disconnect_rate",
+        "0",
+        "probability of a connection being closed",
+    ),
+];
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let config = Config::from_env("smart-home-sim", SETTINGS);
+    config.init_logger();
+    if !config.get_args().is_empty() {
+        println!("{}", usage("smart-home-sim", SETTINGS));
+        return Ok(());
+    }
+
+    let (sockets, thermometers) = (
+        config.get_number("sockets"),
+        config.get_number("thermometers"),
+    );
+    let faults = Faults {
+        latency: Duration::from_millis(config.get_number("latency_ms").into()),
+        drop_rate: config.get_rate("drop_rate"),
+        disconnect_rate: config.get_rate("disconnect_rate"),
+    };
+
+    let mut house = House::new("Simulated house");
+    house.add_room("lab")?;
+    if let Some(lab) = house.get_room_mut("lab") {
+        for i in 1..=sockets {
+            let name = format!("socket-{i}");
+            lab.add_device(SmartSocket::new(&name, "Simulated socket", 1000).into())?;
+        }
+        for i in 1..=thermometers {
+            let name = format!("thermo-{i}");
+            lab.add_device(SmartThermometer::new(&name, "Simulated thermometer").into())?;
+        }
+    }
+
+    let fleet = Fleet::spawn(&house, faults)?;
+    print!("{}", fleet.publish());
+    println!("Send any datagram to a thermometer address to receive its readings.");
+
+    loop {
+        thread::sleep(Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,88 @@
+use std::{error::Error, time::Duration};
+
+use smart_home::{
+    config::{usage, Config, Setting},
+    devices::{socket::SmartSocket, thermometer::SmartThermometer},
+    house::House,
+    scene::{Scene, SceneAction},
+    thermal::Thermostat,
+};
+use smart_home_sim::simulation::{DailyCycle, Schedule, ScheduleAction, Simulation};
+
+const SETTINGS: &[Setting] = &[
+    Setting::number("days", "7", "virtual days to simulate"),
+    Setting::number("seed", "1", "seed of the random events"),
+    Setting::switch("timeline", "false", "print every event"),
+];
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let config = Config::from_env("smart-home-simulate", SETTINGS);
+    config.init_logger();
+    if !config.get_args().is_empty() {
+        println!("{}", usage("smart-home-simulate", SETTINGS));
+        return Ok(());
+    }
+    let (days, seed) = (config.get_number("days"), config.get_number("seed"));
+    let timeline = config.get_switch("timeline");
+
+    let mut house = House::new("sweet home");
+    house.add_room("kitchen")?;
+    house.add_room("bedroom")?;
+    if let Some(kitchen) = house.get_room_mut("kitchen") {
+        kitchen.add_device(SmartSocket::new("fridge", "Fridge socket", 150).into())?;
+        kitchen.add_device(SmartSocket::new("kettle", "Kettle socket", 2000).into())?;
+        kitchen.add_device(SmartThermometer::new("thermo", "Kitchen thermometer").into())?;
+    }
+    if let Some(bedroom) = house.get_room_mut("bedroom") {
+        bedroom.add_device(SmartSocket::new("lamp", "Bedside lamp socket", 40).into())?;
+        bedroom.add_device(SmartSocket::heater("heater", "Oil heater", 1500).into())?;
+        bedroom.add_device(SmartThermometer::new("thermo", "Bedroom thermometer").into())?;
+    }
+
+    let mut night = Scene::new("night");
+    night.add_action("kitchen", "fridge", SceneAction::TurnOn);
+    night.add_action("kitchen", "kettle", SceneAction::TurnOff);
+    night.add_action("bedroom", "lamp", SceneAction::TurnOff);
+    house.add_scene(night)?;
+
+    let mut simulation = Simulation::new(house, seed.into());
+    let outside = DailyCycle {
+        mean: 5.0,
+        amplitude: 4.0,
+        noise: 0.5,
+    };
+    simulation.enable_thermal_model(outside, Duration::from_secs(60));
+    simulation.add_thermostat(Thermostat::new("bedroom", "thermo", "heater", 21.0));
+    let device = |room: &str, device: &str, action| ScheduleAction::Device {
+        room: room.to_owned(),
+        device: device.to_owned(),
+        action,
+    };
+    simulation.add_schedule(Schedule::daily(
+        HOUR * 7,
+        device("kitchen", "kettle", SceneAction::TurnOn),
+    ));
+    simulation.add_schedule(Schedule::daily(
+        HOUR * 7 + Duration::from_secs(10 * 60),
+        device("kitchen", "kettle", SceneAction::TurnOff),
+    ));
+    simulation.add_schedule(Schedule::daily(
+        HOUR * 20,
+        device("bedroom", "lamp", SceneAction::TurnOn),
+    ));
+    simulation.add_schedule(Schedule::daily(
+        HOUR * 23,
+        ScheduleAction::Scene("night".to_owned()),
+    ));
+
+    let report = simulation.run(HOUR * 24 * days);
+    if timeline {
+        for event in &report.timeline {
+            println!("{event}");
+        }
+    }
+    print!("{}", report.summary());
+
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use std::{
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Faults {
+    /// Delay added to every forwarded TCP chunk and every UDP datagram.
+    pub latency: Duration,
+    /// Probability of a UDP datagram being lost.
+    pub drop_rate: f64,
+    /// Probability of a TCP connection being closed instead of forwarded.
+    pub disconnect_rate: f64,
+}
+
+impl Faults {
+    pub fn none() -> Self {
+        Self::default()
+    }
+}
+
+/// Xorshift generator: enough for fault injection and reproducible from a seed.
+#[derive(Debug)]
+pub struct Rng(Mutex<u64>);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(Mutex::new(seed.max(1)))
+    }
+
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(1);
+        Self::new(nanos)
+    }
+
+    pub fn next_u64(&self) -> u64 {
+        let mut state = self.0.lock().expect("Failed to lock mutex");
+        let mut x = *state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        *state = x;
+        x
+    }
+
+    pub fn next_f64(&self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn chance(&self, probability: f64) -> bool {
+        probability > 0.0 && self.next_f64() < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng_is_reproducible() {
+        let a = Rng::new(42);
+        let b = Rng::new(42);
+        for _ in 0..10 {
+            assert_eq!(a.next_u64(), b.next_u64());
+        }
+
+        let value = a.next_f64();
+        assert!((0.0..1.0).contains(&value));
+    }
+
+    #[test]
+    fn test_chance_bounds() {
+        let rng = Rng::new(7);
+        assert!(!rng.chance(0.0));
+        assert!(rng.chance(1.0));
+    }
+}
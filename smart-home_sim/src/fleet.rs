@@ -0,0 +1,288 @@
+use std::{collections::HashMap, fmt, net::SocketAddr, sync::Arc, thread, time::Duration};
+
+use smart_home::{devices::device::Device, house::House, room::RoomDevice};
+use smart_home_tcp_client::{
+    devices::{light::LightServer, lock::LockServer, socket::SocketServer, tcp_device::TcpDevice},
+    server::{ConcurrentServer, ShutdownHandle, DEFAULT_WORKERS},
+};
+
+use crate::{
+    fault::{Faults, Rng},
+    proxy::FaultProxy,
+    thermometer::SimulatedThermometer,
+};
+
+const THERMOMETER_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimulatedDeviceKind {
+    Socket,
+    Light,
+    Lock,
+    Thermometer,
+}
+
+impl fmt::Display for SimulatedDeviceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimulatedDeviceKind::Socket => write!(f, "tcp-socket"),
+            SimulatedDeviceKind::Light => write!(f, "tcp-light"),
+            SimulatedDeviceKind::Lock => write!(f, "tcp-lock"),
+            SimulatedDeviceKind::Thermometer => write!(f, "udp-thermometer"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SimulatedDevice {
+    pub room: String,
+    pub name: String,
+    pub kind: SimulatedDeviceKind,
+    pub addr: SocketAddr,
+}
+
+// Every device of a house served on its own ephemeral port on localhost.
+#[derive(Debug)]
+pub struct Fleet {
+    devices: Vec<SimulatedDevice>,
+    thermometers: HashMap<(String, String), SimulatedThermometer>,
+    proxies: Vec<FaultProxy>,
+    servers: Vec<ServerHandle>,
+}
+
+impl Fleet {
+    pub fn spawn(house: &House, faults: Faults) -> Result<Self, std::io::Error> {
+        Self::spawn_with_rng(house, faults, Rng::from_time())
+    }
+
+    pub fn spawn_with_rng(house: &House, faults: Faults, rng: Rng) -> Result<Self, std::io::Error> {
+        let rng = Arc::new(rng);
+        let mut fleet = Fleet {
+            devices: Vec::new(),
+            thermometers: HashMap::new(),
+            proxies: Vec::new(),
+            servers: Vec::new(),
+        };
+
+        let mut rooms = house.get_rooms().collect::<Vec<_>>();
+        rooms.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+
+        for room in rooms {
+            for device in room.get_devices() {
+                let (kind, addr) = match device {
+                    RoomDevice::SmartSocket(socket) => {
+                        let server = ServerHandle::spawn::<_, SocketServer>(socket.clone())?;
+                        let addr = fleet.serve(server, &faults, &rng)?;
+                        (SimulatedDeviceKind::Socket, addr)
+                    }
+                    RoomDevice::SmartLight(light) => {
+                        let server = ServerHandle::spawn::<_, LightServer>(light.clone())?;
+                        let addr = fleet.serve(server, &faults, &rng)?;
+                        (SimulatedDeviceKind::Light, addr)
+                    }
+                    RoomDevice::SmartLock(lock) => {
+                        let server = ServerHandle::spawn::<_, LockServer>(lock.clone())?;
+                        let addr = fleet.serve(server, &faults, &rng)?;
+                        (SimulatedDeviceKind::Lock, addr)
+                    }
+                    RoomDevice::Thermometer(_) => {
+                        let thermometer = SimulatedThermometer::spawn(
+                            "127.0.0.1:0",
+                            THERMOMETER_INTERVAL,
+                            faults.clone(),
+                            rng.clone(),
+                        )?;
+                        let addr = thermometer.local_addr();
+                        fleet.thermometers.insert(
+                            (room.get_name().to_owned(), device.get_name().to_owned()),
+                            thermometer,
+                        );
+                        (SimulatedDeviceKind::Thermometer, addr)
+                    }
+                    // These sensors push their readings to a receiver, nothing to serve.
+                    RoomDevice::BinarySensor(_) | RoomDevice::EnvironmentSensor(_) => continue,
+                    // Meters read the whole house and are queried through `HouseServer`.
+                    RoomDevice::SmartMeter(_) => continue,
+                };
+
+                fleet.devices.push(SimulatedDevice {
+                    room: room.get_name().to_owned(),
+                    name: device.get_name().to_owned(),
+                    kind,
+                    addr,
+                });
+            }
+        }
+
+        Ok(fleet)
+    }
+
+    fn serve(
+        &mut self,
+        server: ServerHandle,
+        faults: &Faults,
+        rng: &Arc<Rng>,
+    ) -> Result<SocketAddr, std::io::Error> {
+        let proxy = FaultProxy::spawn(server.addr, "127.0.0.1:0", faults.clone(), rng.clone())?;
+        let addr = proxy.local_addr();
+        self.servers.push(server);
+        self.proxies.push(proxy);
+        Ok(addr)
+    }
+
+    pub fn get_devices(&self) -> impl Iterator<Item = &SimulatedDevice> {
+        self.devices.iter()
+    }
+
+    pub fn get_addr(&self, room: &str, device: &str) -> Option<SocketAddr> {
+        self.devices
+            .iter()
+            .find(|d| d.room == room && d.name == device)
+            .map(|d| d.addr)
+    }
+
+    // Thermometers push readings, so receivers have to announce themselves first.
+    pub fn subscribe_thermometer(&self, room: &str, device: &str, receiver: SocketAddr) -> bool {
+        match self.thermometers.get(&(room.to_owned(), device.to_owned())) {
+            Some(thermometer) => {
+                thermometer.subscribe(receiver);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // One `<room>/<device> <kind> <addr>` line per device.
+    pub fn publish(&self) -> String {
+        self.devices
+            .iter()
+            .map(|d| format!("{}/{} {} {}\n", d.room, d.name, d.kind, d.addr))
+            .collect()
+    }
+}
+
+// A device served by a `ConcurrentServer` until the handle is dropped.
+#[derive(Debug)]
+struct ServerHandle {
+    addr: SocketAddr,
+    shutdown: ShutdownHandle,
+}
+
+impl ServerHandle {
+    fn spawn<D, S>(device: D) -> Result<Self, std::io::Error>
+    where
+        D: Device + Send + 'static,
+        S: TcpDevice<D> + Send + 'static,
+    {
+        let server = ConcurrentServer::new(S::bind(device, "127.0.0.1:0")?, DEFAULT_WORKERS);
+        let addr = server.local_addr()?;
+        let shutdown = server.shutdown_handle()?;
+        thread::spawn(move || server.run());
+
+        Ok(Self { addr, shutdown })
+    }
+}
+
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        self.shutdown.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{TcpStream, UdpSocket};
+
+    use smart_home::devices::{
+        light::SmartLight, socket::SmartSocket, thermometer::SmartThermometer,
+    };
+    use smart_home_tcp_client::devices::light::LightCommand;
+    use smart_home_tcp_client::{
+        client::{receive_response, send_command, TcpSession},
+        devices::socket::SocketCommand,
+        protocol::{Notification, ProtocolCommand},
+    };
+    use smart_home_udp_client::protocol::receive_message;
+
+    use super::*;
+
+    fn house() -> House {
+        let mut house = House::new("Simulated");
+        house.add_room("lab").unwrap();
+        let lab = house.get_room_mut("lab").unwrap();
+        lab.add_device(SmartSocket::new("socket-1", "Simulated socket", 100).into())
+            .unwrap();
+        lab.add_device(SmartThermometer::new("thermo-1", "Simulated thermometer").into())
+            .unwrap();
+        lab.add_device(SmartLight::new("light-1", "Simulated light").into())
+            .unwrap();
+        house
+    }
+
+    fn send(addr: SocketAddr, command: impl ProtocolCommand) -> String {
+        let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+        send_command(command, &mut stream).expect("Failed to send command");
+        receive_response(&mut stream).expect("Failed to receive response")
+    }
+
+    #[test]
+    fn test_fleet_end_to_end() {
+        let fleet = Fleet::spawn_with_rng(&house(), Faults::none(), Rng::new(1))
+            .expect("Failed to spawn fleet");
+        assert_eq!(fleet.get_devices().count(), 3);
+        assert_eq!(fleet.publish().lines().count(), 3);
+
+        let socket = fleet.get_addr("lab", "socket-1").expect("Socket expected");
+        assert_eq!(send(socket, SocketCommand::Status), "off\r\n");
+        assert_eq!(send(socket, SocketCommand::Switch), "ok\r\n");
+        assert_eq!(send(socket, SocketCommand::Status), "on\r\n");
+
+        let light = fleet.get_addr("lab", "light-1").expect("Light expected");
+        assert_eq!(send(light, LightCommand::On), "ok\r\n");
+        assert_eq!(send(light, LightCommand::Status), "on 100 2700\r\n");
+
+        let mut receiver = UdpSocket::bind("127.0.0.1:0").expect("Failed to bind receiver");
+        let receiver_addr = receiver.local_addr().expect("No local address");
+        assert!(fleet.subscribe_thermometer("lab", "thermo-1", receiver_addr));
+        assert!(!fleet.subscribe_thermometer("lab", "socket-1", receiver_addr));
+
+        let message = receive_message(&mut receiver).expect("Invalid message");
+        assert!(message.parse::<f32>().is_ok());
+    }
+
+    #[test]
+    fn test_fleet_concurrent_sessions() {
+        let fleet = Fleet::spawn_with_rng(&house(), Faults::none(), Rng::new(1))
+            .expect("Failed to spawn fleet");
+        let socket = fleet.get_addr("lab", "socket-1").expect("Socket expected");
+
+        // An open session and a watcher do not block other clients.
+        let mut session = TcpSession::connect(socket).expect("Failed to connect");
+        let mut watcher = TcpSession::connect(socket)
+            .expect("Failed to connect")
+            .watch(SocketCommand::Watch)
+            .expect("Failed to watch");
+        assert_eq!(send(socket, SocketCommand::Switch), "ok\r\n");
+        assert_eq!(session.send(SocketCommand::Status).unwrap(), "on\r\n");
+        for state in ["off", "on"] {
+            assert_eq!(
+                watcher.next_notification().unwrap(),
+                Notification::State(state.to_owned())
+            );
+        }
+    }
+
+    #[test]
+    fn test_fleet_disconnect_faults() {
+        let faults = Faults {
+            disconnect_rate: 1.0,
+            ..Faults::none()
+        };
+        let fleet =
+            Fleet::spawn_with_rng(&house(), faults, Rng::new(1)).expect("Failed to spawn fleet");
+        let socket = fleet.get_addr("lab", "socket-1").expect("Socket expected");
+
+        let mut stream = TcpStream::connect(socket).expect("Failed to connect");
+        assert!(send_command(SocketCommand::Status, &mut stream).is_err());
+    }
+}
@@ -0,0 +1,5 @@
+pub mod fault;
+pub mod fleet;
+pub mod proxy;
+pub mod simulation;
+pub mod thermometer;
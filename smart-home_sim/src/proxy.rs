@@ -0,0 +1,158 @@
+use std::{
+    io::{Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use log::warn;
+
+use crate::fault::{Faults, Rng};
+
+// TCP proxy published in front of a real device server, injecting latency and disconnects.
+#[derive(Debug)]
+pub struct FaultProxy {
+    addr: SocketAddr,
+    finished: Arc<AtomicBool>,
+}
+
+impl FaultProxy {
+    pub fn spawn(
+        upstream: SocketAddr,
+        addr: impl ToSocketAddrs,
+        faults: Faults,
+        rng: Arc<Rng>,
+    ) -> Result<Self, std::io::Error> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let finished = Arc::new(AtomicBool::new(false));
+
+        let finished_clone = finished.clone();
+        thread::spawn(move || {
+            for client in listener.incoming() {
+                if finished_clone.load(Ordering::SeqCst) {
+                    return;
+                }
+
+                let Ok(client) = client else {
+                    continue;
+                };
+
+                if rng.chance(faults.disconnect_rate) {
+                    let _ = client.shutdown(Shutdown::Both);
+                    continue;
+                }
+
+                let Ok(server) = TcpStream::connect(upstream) else {
+                    let _ = client.shutdown(Shutdown::Both);
+                    continue;
+                };
+
+                if let Err(error) = forward(client, server, &faults) {
+                    warn!("Proxy connection failed: {error}");
+                }
+            }
+        });
+
+        Ok(Self { addr, finished })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for FaultProxy {
+    fn drop(&mut self) {
+        self.finished.store(true, Ordering::SeqCst);
+        // Wake up the blocking accept so the proxy thread can exit.
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+fn forward(client: TcpStream, server: TcpStream, faults: &Faults) -> std::io::Result<()> {
+    let client_reader = client.try_clone()?;
+    let server_reader = server.try_clone()?;
+
+    let latency = faults.latency;
+    thread::spawn(move || pump(client_reader, server, latency));
+    thread::spawn(move || pump(server_reader, client, latency));
+
+    Ok(())
+}
+
+fn pump(mut from: TcpStream, mut to: TcpStream, latency: std::time::Duration) {
+    let mut buffer = [0u8; 1024];
+    loop {
+        let read = match from.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => read,
+        };
+
+        if !latency.is_zero() {
+            thread::sleep(latency);
+        }
+
+        if to.write_all(&buffer[..read]).is_err() {
+            break;
+        }
+    }
+
+    let _ = to.shutdown(Shutdown::Write);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn echo_server() -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind echo server");
+        let addr = listener.local_addr().expect("Failed to get local address");
+        thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut buf = [0u8; 4];
+                if stream.read_exact(&mut buf).is_ok() {
+                    let _ = stream.write_all(&buf);
+                }
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_proxy_forwards_with_latency() {
+        let faults = Faults {
+            latency: Duration::from_millis(50),
+            ..Faults::none()
+        };
+        let proxy = FaultProxy::spawn(echo_server(), "127.0.0.1:0", faults, Arc::new(Rng::new(1)))
+            .expect("Failed to spawn proxy");
+
+        let started = Instant::now();
+        let mut stream = TcpStream::connect(proxy.local_addr()).expect("Failed to connect");
+        stream.write_all(b"ping").expect("Failed to write");
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).expect("Failed to read");
+        assert_eq!(&buf, b"ping");
+        assert!(started.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_proxy_disconnects() {
+        let faults = Faults {
+            disconnect_rate: 1.0,
+            ..Faults::none()
+        };
+        let proxy = FaultProxy::spawn(echo_server(), "127.0.0.1:0", faults, Arc::new(Rng::new(1)))
+            .expect("Failed to spawn proxy");
+
+        let mut stream = TcpStream::connect(proxy.local_addr()).expect("Failed to connect");
+        let _ = stream.write_all(b"ping");
+        let mut buf = [0u8; 4];
+        assert!(stream.read_exact(&mut buf).is_err());
+    }
+}
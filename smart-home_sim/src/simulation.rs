@@ -0,0 +1,668 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fmt,
+    time::Duration,
+};
+
+use smart_home::{
+    devices::device::Device, errors::SmartHouseError, house::House, room::RoomDevice,
+    scene::SceneAction, thermal::Thermostat,
+};
+
+use crate::fault::Rng;
+
+const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+const DEFAULT_SAMPLE_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScheduleAction {
+    Device {
+        room: String,
+        device: String,
+        action: SceneAction,
+    },
+    Scene(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    at: Duration,
+    repeat: Option<Duration>,
+    action: ScheduleAction,
+}
+
+impl Schedule {
+    // Fires once at `at` of simulated time.
+    pub fn once(at: Duration, action: ScheduleAction) -> Self {
+        Self {
+            at,
+            repeat: None,
+            action,
+        }
+    }
+
+    // Fires every day at `time_of_day`.
+    pub fn daily(time_of_day: Duration, action: ScheduleAction) -> Self {
+        Self {
+            at: time_of_day,
+            repeat: Some(DAY),
+            action,
+        }
+    }
+}
+
+pub trait TemperatureSource {
+    fn temperature(&mut self, house: &House, now: Duration, rng: &Rng) -> f32;
+}
+
+// Daily sine with the minimum before dawn plus uniform noise.
+#[derive(Debug, Clone)]
+pub struct DailyCycle {
+    pub mean: f32,
+    pub amplitude: f32,
+    pub noise: f32,
+}
+
+impl Default for DailyCycle {
+    fn default() -> Self {
+        Self {
+            mean: 20.0,
+            amplitude: 2.0,
+            noise: 0.2,
+        }
+    }
+}
+
+impl TemperatureSource for DailyCycle {
+    fn temperature(&mut self, _house: &House, now: Duration, rng: &Rng) -> f32 {
+        let day_phase = (now.as_secs() % DAY.as_secs()) as f32 / DAY.as_secs() as f32;
+        let cycle = (2.0 * std::f32::consts::PI * (day_phase - 0.375)).sin();
+        let noise = (rng.next_f64() as f32 * 2.0 - 1.0) * self.noise;
+        self.mean + self.amplitude * cycle + noise
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimelineEventKind {
+    Switched { device: String, on: bool },
+    SceneApplied(String),
+    ScheduleFailed(String),
+    Temperature { device: String, value: f32 },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineEvent {
+    pub time: Duration,
+    pub kind: TimelineEventKind,
+}
+
+impl fmt::Display for TimelineEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.time.as_secs();
+        let (day, hours, minutes) = (
+            seconds / DAY.as_secs() + 1,
+            seconds % DAY.as_secs() / 3600,
+            seconds % 3600 / 60,
+        );
+        write!(f, "day {day} {hours:02}:{minutes:02} ")?;
+        match &self.kind {
+            TimelineEventKind::Switched { device, on } => {
+                write!(f, "{device} {}", if *on { "on" } else { "off" })
+            }
+            TimelineEventKind::SceneApplied(scene) => write!(f, "scene {scene}"),
+            TimelineEventKind::ScheduleFailed(error) => write!(f, "failed: {error}"),
+            TimelineEventKind::Temperature { device, value } => {
+                write!(f, "{device} {value:.1}°C")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TemperatureStats {
+    pub min: f32,
+    pub max: f32,
+    pub sum: f32,
+    pub samples: u32,
+}
+
+impl TemperatureStats {
+    fn add(&mut self, value: f32) {
+        if self.samples == 0 {
+            self.min = value;
+            self.max = value;
+        }
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        self.sum += value;
+        self.samples += 1;
+    }
+
+    pub fn average(&self) -> f32 {
+        if self.samples == 0 {
+            return 0.0;
+        }
+        self.sum / self.samples as f32
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SimulationReport {
+    pub duration: Duration,
+    pub timeline: Vec<TimelineEvent>,
+    // Watt-hours per `<room>/<device>` socket.
+    pub energy: HashMap<String, f64>,
+    pub temperatures: HashMap<String, TemperatureStats>,
+    pub switches: u32,
+}
+
+impl SimulationReport {
+    pub fn total_energy(&self) -> f64 {
+        self.energy.values().sum()
+    }
+
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "Simulated: {:.1} days\nSwitches: {}\nEnergy:\n",
+            self.duration.as_secs_f64() / DAY.as_secs_f64(),
+            self.switches
+        );
+
+        let mut energy = self.energy.iter().collect::<Vec<_>>();
+        energy.sort_by(|a, b| a.0.cmp(b.0));
+        for (device, wh) in energy {
+            summary.push_str(&format!("  {}: {:.3} kWh\n", device, wh / 1000.0));
+        }
+        summary.push_str(&format!(
+            "Total energy: {:.3} kWh\nTemperature:\n",
+            self.total_energy() / 1000.0
+        ));
+
+        let mut temperatures = self.temperatures.iter().collect::<Vec<_>>();
+        temperatures.sort_by(|a, b| a.0.cmp(b.0));
+        for (device, stats) in temperatures {
+            summary.push_str(&format!(
+                "  {}: min {:.1}, avg {:.1}, max {:.1}\n",
+                device,
+                stats.min,
+                stats.average(),
+                stats.max
+            ));
+        }
+
+        summary
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum EventKind {
+    Schedule(usize),
+    Thermal,
+    Sample,
+}
+
+struct ThermalModel {
+    outside: Box<dyn TemperatureSource>,
+    step: Duration,
+}
+
+// Discrete-event simulation of a house: nothing waits for real time, the
+// virtual clock jumps from one event to the next.
+pub struct Simulation {
+    house: House,
+    rng: Rng,
+    now: Duration,
+    sample_interval: Duration,
+    schedules: Vec<Schedule>,
+    sources: HashMap<String, Box<dyn TemperatureSource>>,
+    thermal: Option<ThermalModel>,
+    thermostats: Vec<Thermostat>,
+    queue: BinaryHeap<Reverse<(Duration, u64, EventKind)>>,
+    sequence: u64,
+}
+
+impl Simulation {
+    pub fn new(house: House, seed: u64) -> Self {
+        Self {
+            house,
+            rng: Rng::new(seed),
+            now: Duration::ZERO,
+            sample_interval: DEFAULT_SAMPLE_INTERVAL,
+            schedules: Vec::new(),
+            sources: HashMap::new(),
+            thermal: None,
+            thermostats: Vec::new(),
+            queue: BinaryHeap::new(),
+            sequence: 0,
+        }
+    }
+
+    pub fn set_sample_interval(&mut self, interval: Duration) {
+        self.sample_interval = interval;
+    }
+
+    pub fn add_schedule(&mut self, schedule: Schedule) {
+        self.schedules.push(schedule);
+    }
+
+    // Room temperatures evolve from heater sockets and losses to `outside` every `step`;
+    // thermometers without an explicit source then show their room temperature.
+    pub fn enable_thermal_model(
+        &mut self,
+        outside: impl TemperatureSource + 'static,
+        step: Duration,
+    ) {
+        self.thermal = Some(ThermalModel {
+            outside: Box::new(outside),
+            step,
+        });
+    }
+
+    // Thermostats are regulated on every thermal model step.
+    pub fn add_thermostat(&mut self, thermostat: Thermostat) {
+        self.thermostats.push(thermostat);
+    }
+
+    // Thermometers without a source fall back to their room temperature when the
+    // thermal model is enabled and to `DailyCycle::default()` otherwise.
+    pub fn set_temperature_source(
+        &mut self,
+        room: &str,
+        device: &str,
+        source: impl TemperatureSource + 'static,
+    ) {
+        self.sources
+            .insert(format!("{room}/{device}"), Box::new(source));
+    }
+
+    pub fn get_house(&self) -> &House {
+        &self.house
+    }
+
+    pub fn get_house_mut(&mut self) -> &mut House {
+        &mut self.house
+    }
+
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    pub fn run(&mut self, duration: Duration) -> SimulationReport {
+        let start = self.now;
+        let end = start + duration;
+        let mut report = SimulationReport {
+            duration,
+            ..SimulationReport::default()
+        };
+
+        self.queue.clear();
+        for index in 0..self.schedules.len() {
+            if let Some(at) = self.next_occurrence(index, start) {
+                self.push(at, EventKind::Schedule(index));
+            }
+        }
+        if let Some(step) = self.thermal.as_ref().map(|thermal| thermal.step) {
+            self.push(start + step, EventKind::Thermal);
+        }
+        if !self.sample_interval.is_zero() {
+            self.push(start, EventKind::Sample);
+        }
+
+        while let Some(Reverse((time, _, kind))) = self.queue.pop() {
+            if time > end {
+                break;
+            }
+
+            self.accumulate_energy(time, &mut report);
+            self.house.update_meters(time - self.now);
+            self.advance_lights(time);
+            self.now = time;
+
+            match kind {
+                EventKind::Schedule(index) => {
+                    self.run_schedule(index, &mut report);
+                    if let Some(repeat) = self.schedules[index].repeat {
+                        self.push(time + repeat, EventKind::Schedule(index));
+                    }
+                }
+                EventKind::Thermal => {
+                    if let Some(step) = self.step_thermal_model(&mut report) {
+                        self.push(time + step, EventKind::Thermal);
+                    }
+                }
+                EventKind::Sample => {
+                    self.sample_temperatures(&mut report);
+                    self.push(time + self.sample_interval, EventKind::Sample);
+                }
+            }
+
+            // Meters pick up the load changed by the event.
+            self.house.update_meters(Duration::ZERO);
+        }
+
+        self.accumulate_energy(end, &mut report);
+        self.house.update_meters(end - self.now);
+        self.advance_lights(end);
+        self.now = end;
+        report
+    }
+
+    fn push(&mut self, time: Duration, kind: EventKind) {
+        self.sequence += 1;
+        self.queue.push(Reverse((time, self.sequence, kind)));
+    }
+
+    fn next_occurrence(&self, index: usize, from: Duration) -> Option<Duration> {
+        let schedule = &self.schedules[index];
+        if schedule.at >= from {
+            return Some(schedule.at);
+        }
+
+        let repeat = schedule.repeat?;
+        let periods = (from - schedule.at).as_secs().div_ceil(repeat.as_secs());
+        Some(schedule.at + repeat * periods as u32)
+    }
+
+    fn run_schedule(&mut self, index: usize, report: &mut SimulationReport) {
+        let before: HashMap<String, bool> = self.switch_states().into_iter().collect();
+        let result = match self.schedules[index].action.clone() {
+            ScheduleAction::Device {
+                room,
+                device,
+                action,
+            } => self.apply_device_action(&room, &device, action),
+            ScheduleAction::Scene(name) => self.house.apply_scene(&name).map(|_| {
+                report.timeline.push(TimelineEvent {
+                    time: self.now,
+                    kind: TimelineEventKind::SceneApplied(name),
+                });
+            }),
+        };
+
+        if let Err(error) = result {
+            report.timeline.push(TimelineEvent {
+                time: self.now,
+                kind: TimelineEventKind::ScheduleFailed(error.to_string()),
+            });
+            return;
+        }
+
+        self.record_switches(before, report);
+    }
+
+    fn step_thermal_model(&mut self, report: &mut SimulationReport) -> Option<Duration> {
+        let thermal = self.thermal.as_mut()?;
+
+        let outside = thermal
+            .outside
+            .temperature(&self.house, self.now, &self.rng);
+        let step = thermal.step;
+        self.house.step_temperature(outside, step);
+
+        let before: HashMap<String, bool> = self.switch_states().into_iter().collect();
+        for index in 0..self.thermostats.len() {
+            if let Err(error) = self.thermostats[index].regulate(&mut self.house) {
+                report.timeline.push(TimelineEvent {
+                    time: self.now,
+                    kind: TimelineEventKind::ScheduleFailed(error.to_string()),
+                });
+            }
+        }
+        self.record_switches(before, report);
+
+        Some(step)
+    }
+
+    fn record_switches(&self, before: HashMap<String, bool>, report: &mut SimulationReport) {
+        for (device, on) in self.switch_states() {
+            if before.get(&device) != Some(&on) {
+                report.switches += 1;
+                report.timeline.push(TimelineEvent {
+                    time: self.now,
+                    kind: TimelineEventKind::Switched { device, on },
+                });
+            }
+        }
+    }
+
+    fn apply_device_action(
+        &mut self,
+        room: &str,
+        device: &str,
+        action: SceneAction,
+    ) -> Result<(), SmartHouseError> {
+        let device = self
+            .house
+            .get_room_mut(room)
+            .ok_or(SmartHouseError::RoomNotFoundError(room.to_string()))?
+            .get_device_mut(device)
+            .ok_or(SmartHouseError::DeviceNotFoundError(format!(
+                "{room}/{device}"
+            )))?;
+        action.apply(device)
+    }
+
+    fn sample_temperatures(&mut self, report: &mut SimulationReport) {
+        for (device, reading) in self.thermometers() {
+            let value = match self.sources.get_mut(&device) {
+                Some(source) => source.temperature(&self.house, self.now, &self.rng),
+                None if self.thermal.is_some() => reading,
+                None => self
+                    .sources
+                    .entry(device.clone())
+                    .or_insert_with(|| Box::new(DailyCycle::default()))
+                    .temperature(&self.house, self.now, &self.rng),
+            };
+
+            report
+                .temperatures
+                .entry(device.clone())
+                .or_default()
+                .add(value);
+            report.timeline.push(TimelineEvent {
+                time: self.now,
+                kind: TimelineEventKind::Temperature { device, value },
+            });
+        }
+    }
+
+    fn accumulate_energy(&self, until: Duration, report: &mut SimulationReport) {
+        let hours = (until - self.now).as_secs_f64() / 3600.0;
+        for room in self.house.get_rooms() {
+            for device in room.get_devices() {
+                if let RoomDevice::SmartSocket(socket) = device {
+                    let energy = report
+                        .energy
+                        .entry(format!("{}/{}", room.get_name(), socket.get_name()))
+                        .or_default();
+                    if socket.is_on() {
+                        *energy += socket.power_consumption() as f64 * hours;
+                    }
+                }
+            }
+        }
+    }
+
+    // Light transitions run in simulated time as well.
+    fn advance_lights(&mut self, until: Duration) {
+        let dt = until - self.now;
+        let rooms: Vec<String> = self
+            .house
+            .get_rooms()
+            .map(|r| r.get_name().to_owned())
+            .collect();
+        for name in rooms {
+            let Some(room) = self.house.get_room_mut(&name) else {
+                continue;
+            };
+            let lights: Vec<String> = room
+                .get_devices()
+                .filter(|device| matches!(device, RoomDevice::SmartLight(_)))
+                .map(|device| device.get_name().to_owned())
+                .collect();
+            for light in lights {
+                if let Some(RoomDevice::SmartLight(light)) = room.get_device_mut(&light) {
+                    light.advance(dt);
+                }
+            }
+        }
+    }
+
+    fn switch_states(&self) -> Vec<(String, bool)> {
+        let mut states = Vec::new();
+        for room in self.house.get_rooms() {
+            for device in room.get_devices() {
+                let is_on = match device {
+                    RoomDevice::SmartSocket(socket) => socket.is_on(),
+                    RoomDevice::SmartLight(light) => light.is_on(),
+                    RoomDevice::Thermometer(_)
+                    | RoomDevice::BinarySensor(_)
+                    | RoomDevice::SmartLock(_)
+                    | RoomDevice::EnvironmentSensor(_)
+                    | RoomDevice::SmartMeter(_) => continue,
+                };
+                states.push((format!("{}/{}", room.get_name(), device.get_name()), is_on));
+            }
+        }
+        states.sort();
+        states
+    }
+
+    fn thermometers(&self) -> Vec<(String, f32)> {
+        let mut thermometers = Vec::new();
+        for room in self.house.get_rooms() {
+            for device in room.get_devices() {
+                if let RoomDevice::Thermometer(thermometer) = device {
+                    thermometers.push((
+                        format!("{}/{}", room.get_name(), thermometer.get_name()),
+                        thermometer.get_temperature(),
+                    ));
+                }
+            }
+        }
+        thermometers.sort_by(|a, b| a.0.cmp(&b.0));
+        thermometers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use smart_home::{
+        devices::{socket::SmartSocket, thermometer::SmartThermometer},
+        scene::Scene,
+    };
+
+    struct Constant(f32);
+
+    impl TemperatureSource for Constant {
+        fn temperature(&mut self, _house: &House, _now: Duration, _rng: &Rng) -> f32 {
+            self.0
+        }
+    }
+
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    fn house() -> House {
+        let mut house = House::new("House");
+        house.add_room("kitchen").unwrap();
+        let kitchen = house.get_room_mut("kitchen").unwrap();
+        kitchen
+            .add_device(SmartSocket::new("kettle", "Kettle socket", 1000).into())
+            .unwrap();
+        kitchen
+            .add_device(SmartThermometer::new("thermo", "Kitchen thermometer").into())
+            .unwrap();
+
+        let mut night = Scene::new("night");
+        night.add_action("kitchen", "kettle", SceneAction::TurnOff);
+        house.add_scene(night).unwrap();
+        house
+    }
+
+    fn simulation(seed: u64) -> Simulation {
+        let mut simulation = Simulation::new(house(), seed);
+        simulation.add_schedule(Schedule::daily(
+            HOUR * 7,
+            ScheduleAction::Device {
+                room: "kitchen".to_owned(),
+                device: "kettle".to_owned(),
+                action: SceneAction::TurnOn,
+            },
+        ));
+        simulation.add_schedule(Schedule::daily(
+            HOUR * 9,
+            ScheduleAction::Scene("night".to_owned()),
+        ));
+        simulation
+    }
+
+    #[test]
+    fn test_energy_accumulation() {
+        let report = simulation(1).run(DAY * 3);
+
+        assert_eq!(report.switches, 6);
+        assert!((report.total_energy() - 3.0 * 2.0 * 1000.0).abs() < 1e-6);
+        assert_eq!(
+            report.temperatures["kitchen/thermo"].samples,
+            3 * 24 * 4 + 1
+        );
+    }
+
+    #[test]
+    fn test_simulation_is_reproducible() {
+        let first = simulation(42).run(DAY * 2);
+        let second = simulation(42).run(DAY * 2);
+        let other = simulation(43).run(DAY * 2);
+
+        assert_eq!(first, second);
+        assert_eq!(first.summary(), second.summary());
+        assert_ne!(first.timeline, other.timeline);
+    }
+
+    #[test]
+    fn test_failed_schedule_is_reported() {
+        let mut simulation = Simulation::new(house(), 1);
+        simulation.set_sample_interval(Duration::ZERO);
+        simulation.add_schedule(Schedule::once(
+            HOUR,
+            ScheduleAction::Scene("away".to_owned()),
+        ));
+
+        let report = simulation.run(DAY);
+        assert_eq!(report.timeline.len(), 1);
+        assert!(matches!(
+            report.timeline[0].kind,
+            TimelineEventKind::ScheduleFailed(_)
+        ));
+        assert_eq!(
+            report.timeline[0].to_string(),
+            "day 1 01:00 failed: Scene not found: away"
+        );
+    }
+
+    #[test]
+    fn test_thermal_model_with_thermostat() {
+        let mut house = House::new("House");
+        house.add_room("bedroom").unwrap();
+        let bedroom = house.get_room_mut("bedroom").unwrap();
+        bedroom
+            .add_device(SmartSocket::heater("heater", "Oil heater", 1500).into())
+            .unwrap();
+        bedroom
+            .add_device(SmartThermometer::new("thermo", "Bedroom thermometer").into())
+            .unwrap();
+
+        let mut simulation = Simulation::new(house, 1);
+        simulation.enable_thermal_model(Constant(0.0), Duration::from_secs(60));
+        simulation.add_thermostat(Thermostat::new("bedroom", "thermo", "heater", 21.0));
+
+        let report = simulation.run(DAY);
+        let stats = &report.temperatures["bedroom/thermo"];
+        assert!(stats.min >= 20.0 && stats.max <= 22.0, "{stats:?}");
+        assert!(report.switches > 2);
+        assert!(report.energy["bedroom/heater"] > 0.0);
+    }
+}
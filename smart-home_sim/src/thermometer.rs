@@ -0,0 +1,188 @@
+use std::{
+    io,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use smart_home_udp_client::protocol::send_message;
+
+use crate::fault::{Faults, Rng};
+
+const RECEIVE_TIMEOUT: Duration = Duration::from_millis(200);
+
+// Sends generated temperatures to every subscribed receiver, like
+// `thermometer-generator-udp-server` but on an ephemeral port. Any datagram
+// sent to the thermometer subscribes its sender.
+#[derive(Debug)]
+pub struct SimulatedThermometer {
+    addr: SocketAddr,
+    subscribers: Arc<Mutex<Vec<SocketAddr>>>,
+    finished: Arc<AtomicBool>,
+}
+
+impl SimulatedThermometer {
+    pub fn spawn(
+        addr: impl ToSocketAddrs,
+        interval: Duration,
+        faults: Faults,
+        rng: Arc<Rng>,
+    ) -> Result<Self, std::io::Error> {
+        let mut socket = UdpSocket::bind(addr)?;
+        let addr = socket.local_addr()?;
+        let subscribers = Arc::new(Mutex::new(Vec::<SocketAddr>::new()));
+        let finished = Arc::new(AtomicBool::new(false));
+
+        let listener = socket.try_clone()?;
+        // The timeout lets the thread notice that the thermometer was dropped.
+        listener.set_read_timeout(Some(RECEIVE_TIMEOUT))?;
+        let subscribers_clone = subscribers.clone();
+        let finished_clone = finished.clone();
+        thread::spawn(move || {
+            let mut buf = [0u8; 64];
+            while !finished_clone.load(Ordering::SeqCst) {
+                let sender = match listener.recv_from(&mut buf) {
+                    Ok((_, sender)) => sender,
+                    Err(error)
+                        if matches!(
+                            error.kind(),
+                            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                        ) =>
+                    {
+                        continue
+                    }
+                    Err(_) => return,
+                };
+
+                let mut subscribers = subscribers_clone.lock().expect("Failed to lock mutex");
+                if !subscribers.contains(&sender) {
+                    subscribers.push(sender);
+                }
+            }
+        });
+
+        let subscribers_clone = subscribers.clone();
+        let finished_clone = finished.clone();
+        let started = Instant::now();
+        thread::spawn(move || loop {
+            if finished_clone.load(Ordering::SeqCst) {
+                return;
+            }
+
+            let temperature = 20.0 + ((Instant::now() - started).as_secs_f32() / 2.0).sin();
+            let receivers = subscribers_clone
+                .lock()
+                .expect("Failed to lock mutex")
+                .clone();
+
+            if !faults.latency.is_zero() {
+                thread::sleep(faults.latency);
+            }
+
+            for receiver in receivers {
+                if rng.chance(faults.drop_rate) {
+                    continue;
+                }
+
+                if let Err(error) = send_message(&mut socket, receiver, &temperature.to_string()) {
+                    warn!("Error sending message: {:?}", error);
+                }
+            }
+
+            thread::sleep(interval);
+        });
+
+        Ok(Self {
+            addr,
+            subscribers,
+            finished,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    pub fn subscribe(&self, receiver: SocketAddr) {
+        let mut subscribers = self.subscribers.lock().expect("Failed to lock mutex");
+        if !subscribers.contains(&receiver) {
+            subscribers.push(receiver);
+        }
+    }
+}
+
+impl Drop for SimulatedThermometer {
+    fn drop(&mut self) {
+        self.finished.store(true, Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smart_home_udp_client::protocol::receive_message;
+
+    #[test]
+    fn test_thermometer_sends_to_subscribers() {
+        let thermometer = SimulatedThermometer::spawn(
+            "127.0.0.1:0",
+            Duration::from_millis(10),
+            Faults::none(),
+            Arc::new(Rng::new(1)),
+        )
+        .expect("Failed to spawn thermometer");
+
+        let mut receiver = UdpSocket::bind("127.0.0.1:0").expect("Failed to bind receiver");
+        thermometer.subscribe(receiver.local_addr().expect("No local address"));
+
+        let message = receive_message(&mut receiver).expect("Invalid message");
+        let temperature = message.parse::<f32>().expect("Invalid temperature");
+        assert!((19.0..=21.0).contains(&temperature));
+    }
+
+    #[test]
+    fn test_drop_releases_port() {
+        let thermometer = SimulatedThermometer::spawn(
+            "127.0.0.1:0",
+            Duration::from_millis(10),
+            Faults::none(),
+            Arc::new(Rng::new(1)),
+        )
+        .expect("Failed to spawn thermometer");
+        let addr = thermometer.local_addr();
+        drop(thermometer);
+
+        let mut released = false;
+        for _ in 0..50 {
+            thread::sleep(Duration::from_millis(20));
+            if UdpSocket::bind(addr).is_ok() {
+                released = true;
+                break;
+            }
+        }
+        assert!(released);
+    }
+
+    #[test]
+    fn test_thermometer_subscribes_senders() {
+        let thermometer = SimulatedThermometer::spawn(
+            "127.0.0.1:0",
+            Duration::from_millis(10),
+            Faults::none(),
+            Arc::new(Rng::new(1)),
+        )
+        .expect("Failed to spawn thermometer");
+
+        let mut receiver = UdpSocket::bind("127.0.0.1:0").expect("Failed to bind receiver");
+        receiver
+            .send_to(b"subscribe", thermometer.local_addr())
+            .expect("Failed to subscribe");
+
+        assert!(receive_message(&mut receiver).is_ok());
+    }
+}
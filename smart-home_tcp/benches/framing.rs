@@ -0,0 +1,72 @@
+use std::{
+    io::Write,
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use smart_home_tcp_client::{
+    framing::FramedStream,
+    protocol::{read_line_bytes, Framing},
+};
+
+const MESSAGE: &[u8] = b"brightness 40\r\n";
+const MESSAGES_PER_ITER: u64 = 1000;
+
+// A loopback connection whose other end writes the same message until it is closed.
+fn endless_stream(framing: Framing) -> TcpStream {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+    let addr = listener.local_addr().expect("No local address");
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().expect("Failed to accept");
+        let mut writer = FramedStream::new(stream);
+        writer.set_framing(framing);
+        // Many messages per write keep the writer ahead of the reader.
+        let mut chunk = FramedStream::new(std::io::Cursor::new(Vec::new()));
+        chunk.set_framing(framing);
+        for _ in 0..256 {
+            chunk.write_message(MESSAGE).unwrap();
+        }
+        let chunk = chunk.get_ref().get_ref().clone();
+        while writer.write_all(&chunk).is_ok() {}
+    });
+    TcpStream::connect(addr).expect("Failed to connect")
+}
+
+fn bench_framing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read_messages");
+    group.throughput(Throughput::Elements(MESSAGES_PER_ITER));
+
+    let mut stream = endless_stream(Framing::Lines);
+    group.bench_function("lines_byte_at_a_time", |b| {
+        b.iter(|| {
+            for _ in 0..MESSAGES_PER_ITER {
+                read_line_bytes(&mut stream).unwrap();
+            }
+        })
+    });
+
+    let mut stream = FramedStream::new(endless_stream(Framing::Lines));
+    group.bench_function("lines_buffered", |b| {
+        b.iter(|| {
+            for _ in 0..MESSAGES_PER_ITER {
+                stream.read_message().unwrap();
+            }
+        })
+    });
+
+    let mut stream = FramedStream::new(endless_stream(Framing::LengthPrefixed));
+    stream.set_framing(Framing::LengthPrefixed);
+    group.bench_function("length_prefixed", |b| {
+        b.iter(|| {
+            for _ in 0..MESSAGES_PER_ITER {
+                stream.read_message().unwrap();
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_framing);
+criterion_main!(benches);
@@ -0,0 +1,283 @@
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+use log::info;
+use ring::{
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use smart_home::config::{Config, Setting};
+use thiserror::Error;
+
+use crate::protocol::HELLO;
+
+// After version negotiation a server with keys sends `smhp challenge <hex nonce>`,
+// the client answers `<client id> <hex HMAC-SHA256 of the nonce>` and the server
+// goes on with the regular handshake, or answers `smhp unauthorized` and closes.
+pub const CHALLENGE: &str = "challenge";
+pub const UNAUTHORIZED: &str = "unauthorized";
+pub const CHALLENGE_LEN: usize = 32;
+
+// Only clients listed here are served if the file exists.
+pub const SERVER_SETTINGS: &[Setting] = &[Setting::new(
+    "client_keys",
+    "settings/client_keys",
+    "keys of accepted clients",
+)];
+
+// `<client id> <hex key>`, needed if the server only serves provisioned clients.
+pub const CLIENT_SETTINGS: &[Setting] = &[Setting::new(
+    "client_key",
+    "settings/client_key",
+    "key of this client",
+)];
+
+#[derive(Debug, Error)]
+pub enum KeyError {
+    #[error("Invalid key entry: {0}")]
+    InvalidEntry(String),
+
+    #[error("No client keys in {0}")]
+    NoKeys(String),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+// `<client id> <hex key>`, as in key files.
+fn parse_entry(line: &str) -> Result<(String, Vec<u8>), KeyError> {
+    let invalid = || KeyError::InvalidEntry(line.trim().to_owned());
+    let (client_id, key) = line.trim().split_once(' ').ok_or_else(invalid)?;
+    let key = from_hex(key.trim()).filter(|key| !key.is_empty());
+    Ok((client_id.to_owned(), key.ok_or_else(invalid)?))
+}
+
+pub fn challenge_line(challenge: &[u8]) -> String {
+    format!("{} {} {}\r\n", HELLO, CHALLENGE, to_hex(challenge))
+}
+
+pub fn unauthorized_line() -> String {
+    format!("{} {}\r\n", HELLO, UNAUTHORIZED)
+}
+
+// The nonce of a challenge line, None for any other line.
+pub fn parse_challenge(line: &str) -> Option<Vec<u8>> {
+    let nonce = line
+        .trim_end()
+        .strip_prefix(HELLO)?
+        .strip_prefix(' ')?
+        .strip_prefix(CHALLENGE)?
+        .strip_prefix(' ')?;
+    from_hex(nonce)
+}
+
+pub fn new_challenge() -> [u8; CHALLENGE_LEN] {
+    let mut challenge = [0; CHALLENGE_LEN];
+    SystemRandom::new()
+        .fill(&mut challenge)
+        .expect("Failed to generate challenge");
+    challenge
+}
+
+// Identity and pre-shared key of a provisioned client.
+#[derive(Clone)]
+pub struct Credentials {
+    client_id: String,
+    key: hmac::Key,
+}
+
+impl Credentials {
+    pub fn new(client_id: &str, key: &[u8]) -> Self {
+        Self {
+            client_id: client_id.to_owned(),
+            key: hmac::Key::new(hmac::HMAC_SHA256, key),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, KeyError> {
+        let (client_id, key) = parse_entry(&fs::read_to_string(path)?)?;
+        Ok(Self::new(&client_id, &key))
+    }
+
+    // From `CLIENT_SETTINGS`, None if there is no key file.
+    pub fn from_config(config: &Config) -> Result<Option<Self>, KeyError> {
+        let path = config.get("client_key");
+        if !Path::new(path).exists() {
+            return Ok(None);
+        }
+        Self::load(path).map(Some)
+    }
+
+    pub fn get_client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    // The answer to a challenge: `<client id> <hex signature>\r\n`.
+    pub fn respond(&self, challenge: &[u8]) -> String {
+        let tag = hmac::sign(&self.key, challenge);
+        format!("{} {}\r\n", self.client_id, to_hex(tag.as_ref()))
+    }
+}
+
+// The key must not end up in logs.
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("client_id", &self.client_id)
+            .finish_non_exhaustive()
+    }
+}
+
+// Keys of all clients a server accepts.
+#[derive(Clone, Default)]
+pub struct KeyStore {
+    keys: HashMap<String, hmac::Key>,
+}
+
+impl KeyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // One `<client id> <hex key>` entry per line, empty lines and `#` comments are skipped.
+    // A file without entries is an error, as a server using it would accept nobody.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, KeyError> {
+        let path = path.as_ref();
+        let mut store = Self::new();
+        for line in fs::read_to_string(path)?.lines() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            let (client_id, key) = parse_entry(line)?;
+            store.add_client(&client_id, &key);
+        }
+        if store.keys.is_empty() {
+            return Err(KeyError::NoKeys(path.display().to_string()));
+        }
+        Ok(store)
+    }
+
+    // From `SERVER_SETTINGS`, None if there is no key file.
+    pub fn from_config(config: &Config) -> Result<Option<Self>, KeyError> {
+        let path = config.get("client_keys");
+        if !Path::new(path).exists() {
+            return Ok(None);
+        }
+        let store = Self::load(path)?;
+        info!("Serving only clients listed in {}", path);
+        Ok(Some(store))
+    }
+
+    // Replaces the key of the same client, if any.
+    pub fn add_client(&mut self, client_id: &str, key: &[u8]) {
+        self.keys
+            .insert(client_id.to_owned(), hmac::Key::new(hmac::HMAC_SHA256, key));
+    }
+
+    pub fn remove_client(&mut self, client_id: &str) -> bool {
+        self.keys.remove(client_id).is_some()
+    }
+
+    pub fn get_client_ids(&self) -> impl Iterator<Item = &str> {
+        self.keys.keys().map(String::as_str)
+    }
+
+    // Checks a response line to the challenge, returns the client id if it is valid.
+    pub fn verify(&self, challenge: &[u8], response: &str) -> Option<String> {
+        let (client_id, signature) = response.trim_end().split_once(' ')?;
+        let key = self.keys.get(client_id)?;
+        hmac::verify(key, challenge, &from_hex(signature)?).ok()?;
+        Some(client_id.to_owned())
+    }
+}
+
+impl fmt::Debug for KeyStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyStore")
+            .field("clients", &self.keys.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(to_hex(&[0, 15, 255]), "000fff");
+        assert_eq!(from_hex("000fff"), Some(vec![0, 15, 255]));
+        assert_eq!(from_hex("0f0"), None);
+        assert_eq!(from_hex("zz"), None);
+    }
+
+    #[test]
+    fn test_challenge_response() {
+        let mut store = KeyStore::new();
+        store.add_client("kitchen-panel", b"secret");
+        let challenge = new_challenge();
+        assert_ne!(challenge, new_challenge());
+
+        let line = challenge_line(&challenge);
+        assert_eq!(parse_challenge(&line).unwrap(), challenge);
+        assert_eq!(parse_challenge(&unauthorized_line()), None);
+
+        let credentials = Credentials::new("kitchen-panel", b"secret");
+        let response = credentials.respond(&challenge);
+        assert_eq!(
+            store.verify(&challenge, &response).as_deref(),
+            Some("kitchen-panel")
+        );
+        // A response to another challenge is not accepted.
+        assert_eq!(store.verify(&new_challenge(), &response), None);
+
+        let wrong_key = Credentials::new("kitchen-panel", b"guess");
+        assert_eq!(
+            store.verify(&challenge, &wrong_key.respond(&challenge)),
+            None
+        );
+        let unknown = Credentials::new("stranger", b"secret");
+        assert_eq!(store.verify(&challenge, &unknown.respond(&challenge)), None);
+
+        assert!(store.remove_client("kitchen-panel"));
+        assert_eq!(store.verify(&challenge, &response), None);
+    }
+
+    #[test]
+    fn test_parse_entry() {
+        assert_eq!(
+            parse_entry("panel 00ff\n").unwrap(),
+            ("panel".to_owned(), vec![0, 255])
+        );
+        assert!(parse_entry("panel").is_err());
+        assert!(parse_entry("panel xyz").is_err());
+        assert!(format!("{:?}", Credentials::new("panel", b"secret")).contains("panel"));
+    }
+
+    #[test]
+    fn test_load_store() {
+        let path = std::env::temp_dir().join(format!("smart-home-keys-{}", std::process::id()));
+        fs::write(&path, "# provisioned clients\n\npanel 00ff\nphone 0102\n").unwrap();
+        let store = KeyStore::load(&path).unwrap();
+        let mut clients = store.get_client_ids().collect::<Vec<_>>();
+        clients.sort();
+        assert_eq!(clients, ["panel", "phone"]);
+
+        fs::write(&path, "# nobody yet\n").unwrap();
+        assert!(matches!(KeyStore::load(&path), Err(KeyError::NoKeys(_))));
+        fs::remove_file(path).unwrap();
+    }
+}
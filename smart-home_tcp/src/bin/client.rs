@@ -1,37 +1,88 @@
-use std::env;
 use std::error::Error;
-use std::fs;
-use std::net::TcpStream;
+use std::io::{Read, Write};
+use std::sync::Arc;
 
-use smart_home_tcp_client::client::{receive_response, send_command};
-use smart_home_tcp_client::devices::socket::SocketCommand;
+use rustls::ClientConfig;
+
+use smart_home::config::{Config, Setting};
+use smart_home_tcp_client::auth::{self, Credentials};
+use smart_home_tcp_client::client::TcpSession;
+use smart_home_tcp_client::devices::socket::{SocketCommand, COMMANDS};
+use smart_home_tcp_client::devices::tcp_device::ConnectError;
+use smart_home_tcp_client::protocol::{ProtocolCommand, ProtocolError};
+use smart_home_tcp_client::retry::RetryPolicy;
+use smart_home_tcp_client::tls;
+
+const SETTINGS: &[Setting] = &[Setting::addr(
+    "addr",
+    "127.0.0.1:55331",
+    "address of the socket server",
+)];
+
+// One attempt over a new connection.
+fn send(
+    addr: &str,
+    tls_config: Option<&Arc<ClientConfig>>,
+    credentials: Option<&Credentials>,
+    command: SocketCommand,
+) -> Result<String, ConnectError> {
+    Ok(TcpSession::connect_with(addr, tls_config, credentials)?.send(command)?)
+}
+
+// Prints notifications until the server closes the connection.
+fn watch<S: Read + Write>(session: TcpSession<S>) -> Result<(), ProtocolError> {
+    let mut watcher = session.watch(SocketCommand::Watch)?;
+    loop {
+        match watcher.next_notification() {
+            Ok(notification) => println!("{:?}", notification),
+            // Nothing has changed.
+            Err(ProtocolError::Timeout(_)) => {}
+            Err(error) => return Err(error),
+        }
+    }
+}
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let addr =
-        fs::read_to_string("settings/addr").unwrap_or_else(|_| String::from("127.0.0.1:55331"));
+    let settings = [SETTINGS, tls::CLIENT_SETTINGS, auth::CLIENT_SETTINGS].concat();
+    let config = Config::from_env("socket-tcp-client", &settings);
+    let addr = config.get_addr("addr");
+    config.init_logger();
 
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: {} <command>", args[0]);
-        println!("Available commands: switch, status");
+    let [command] = config.get_args() else {
+        println!("Usage: socket-tcp-client [--<setting> <value>...] <command>");
+        println!("Available commands: {}", COMMANDS.join(", "));
         return Ok(());
-    }
+    };
 
-    let command = match args[1].as_str() {
-        "switch" => SocketCommand::Switch,
-        "status" => SocketCommand::Status,
-        _ => {
-            println!("Unknown command. Available commands: switch, status");
-            return Ok(());
-        }
+    let Ok(command) = SocketCommand::from_str(command) else {
+        println!(
+            "Unknown command. Available commands: {}",
+            COMMANDS.join(", ")
+        );
+        return Ok(());
     };
 
-    println!("Sending command: {:?}", command);
-    let mut stream = TcpStream::connect(&addr)?;
-    send_command(command.clone(), &mut stream)?;
-    println!("Sent command: {:?} to: {}", command, addr);
-    let response = receive_response(&mut stream)?;
-    println!("{}", response);
+    let tls_config = tls::client_config_from(&config)?;
+    let credentials = Credentials::from_config(&config)?;
+
+    if command.is_watch() {
+        println!("Watching: {}", addr);
+        let session = TcpSession::connect_with(addr, tls_config.as_ref(), credentials.as_ref())?;
+        watch(session)?;
+        return Ok(());
+    }
+
+    println!("Sending command: {:?} to: {}", command, addr);
+    // Only idempotent commands are repeated after a timeout.
+    let response = RetryPolicy::default().run(command.is_idempotent(), || {
+        send(
+            addr,
+            tls_config.as_ref(),
+            credentials.as_ref(),
+            command.clone(),
+        )
+    })?;
+    println!("{:?}", command.parse_response(&response)?);
 
     Ok(())
 }
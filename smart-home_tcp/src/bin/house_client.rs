@@ -0,0 +1,46 @@
+use std::error::Error;
+
+use smart_home::config::{Config, Setting};
+use smart_home_tcp_client::auth::{self, Credentials};
+use smart_home_tcp_client::client::TcpSession;
+use smart_home_tcp_client::house::HouseCommand;
+use smart_home_tcp_client::protocol::ProtocolCommand;
+use smart_home_tcp_client::tls;
+
+const USAGE: &str =
+    "scenes, scene <name>, meter [name], rooms, devices <room>, <room>/<device> <command>";
+
+const SETTINGS: &[Setting] = &[Setting::addr(
+    "house_addr",
+    "127.0.0.1:55332",
+    "address of the house server",
+)];
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let settings = [SETTINGS, tls::CLIENT_SETTINGS, auth::CLIENT_SETTINGS].concat();
+    let config = Config::from_env("house-tcp-client", &settings);
+    let addr = config.get_addr("house_addr");
+    config.init_logger();
+
+    let args = config.get_args();
+    if args.is_empty() {
+        println!("Usage: house-tcp-client [--<setting> <value>...] <command>");
+        println!("Available commands: {}", USAGE);
+        return Ok(());
+    }
+
+    let Ok(command) = HouseCommand::from_str(&args.join(" ")) else {
+        println!("Unknown command. Available commands: {}", USAGE);
+        return Ok(());
+    };
+
+    println!("Sending command: {:?}", command);
+    let tls_config = tls::client_config_from(&config)?;
+    let credentials = Credentials::from_config(&config)?;
+    let mut session = TcpSession::connect_with(addr, tls_config.as_ref(), credentials.as_ref())?;
+    let response = session.send(command.clone())?;
+    println!("Sent command: {:?} to: {}", command, addr);
+    println!("{}", response);
+
+    Ok(())
+}
@@ -0,0 +1,76 @@
+use std::error::Error;
+
+use log::info;
+use smart_home::config::{Config, Setting};
+use smart_home::devices::light::SmartLight;
+use smart_home::devices::lock::{PinCode, SmartLock};
+use smart_home::devices::meter::SmartMeter;
+use smart_home::devices::socket::SmartSocket;
+use smart_home::house::House;
+use smart_home::scene::{Scene, SceneAction};
+use smart_home_tcp_client::auth::{self, KeyStore};
+use smart_home_tcp_client::devices::tcp_device::TcpDevice;
+use smart_home_tcp_client::house::HouseServer;
+use smart_home_tcp_client::server::ConcurrentServer;
+use smart_home_tcp_client::tls;
+
+const SETTINGS: &[Setting] = &[
+    Setting::addr("house_addr", "127.0.0.1:55332", "address to listen on"),
+    Setting::new("name", "sweet home", "house name"),
+    Setting::count("workers", "4", "connections served at the same time"),
+];
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let settings = [SETTINGS, tls::SERVER_SETTINGS, auth::SERVER_SETTINGS].concat();
+    let config = Config::from_env("house-tcp-server", &settings);
+    let addr = config.get_addr("house_addr");
+    let workers = config.get_count("workers");
+    config.init_logger();
+
+    let mut house = House::new(config.get("name"));
+    house.add_room("kitchen")?;
+    house.add_room("bedroom")?;
+    house.add_room("hall")?;
+    if let Some(kitchen) = house.get_room_mut("kitchen") {
+        kitchen.add_device(SmartSocket::new("fridge", "Fridge socket", 150).into())?;
+        kitchen.add_device(SmartSocket::new("kettle", "Kettle socket", 2000).into())?;
+    }
+    if let Some(bedroom) = house.get_room_mut("bedroom") {
+        bedroom.add_device(SmartSocket::new("lamp", "Bedside lamp socket", 40).into())?;
+        bedroom.add_device(SmartLight::new("ceiling", "Dimmable ceiling light").into())?;
+    }
+    if let Some(hall) = house.get_room_mut("hall") {
+        hall.add_device(SmartMeter::new("meter", "Main electricity meter", 200).into())?;
+        let mut door = SmartLock::new("door", "Front door lock");
+        door.add_pin(PinCode::new("owner", "1234"));
+        hall.add_device(door.into())?;
+    }
+
+    let mut night = Scene::new("night");
+    night.add_action("kitchen", "fridge", SceneAction::TurnOn);
+    night.add_action("kitchen", "kettle", SceneAction::TurnOff);
+    night.add_action("bedroom", "lamp", SceneAction::TurnOff);
+    house.add_scene(night)?;
+
+    let mut away = Scene::new("away");
+    away.add_action("kitchen", "kettle", SceneAction::TurnOff);
+    away.add_action("bedroom", "lamp", SceneAction::TurnOff);
+    house.add_scene(away)?;
+
+    // Plain socket commands control the kettle.
+    let house = HouseServer::bind(house, addr)?.with_default_device("kitchen", "kettle");
+    let mut house_server = ConcurrentServer::new(house, workers);
+    if let Some(tls_config) = tls::server_config_from(&config)? {
+        house_server = house_server.with_tls(tls_config);
+    }
+    if let Some(keys) = KeyStore::from_config(&config)? {
+        house_server = house_server.with_keys(keys);
+    }
+
+    info!("Server started on {} with {} workers", addr, workers);
+
+    house_server.shutdown_handle()?.on_signal()?;
+    house_server.run();
+
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use std::error::Error;
+use std::sync::Arc;
+
+use rustls::ClientConfig;
+use smart_home::config::{Config, Setting};
+use smart_home_tcp_client::auth::{self, Credentials};
+use smart_home_tcp_client::client::{self, TcpSession, Timeouts};
+use smart_home_tcp_client::devices::light::LightCommand;
+use smart_home_tcp_client::devices::tcp_device::ConnectError;
+use smart_home_tcp_client::protocol::ProtocolCommand;
+use smart_home_tcp_client::retry::RetryPolicy;
+use smart_home_tcp_client::tls;
+
+const COMMANDS: &str =
+    "on, off, switch, status, brightness <0-100> [ms], temperature <2700-6500> [ms]";
+
+const SETTINGS: &[Setting] = &[Setting::addr(
+    "light_addr",
+    "127.0.0.1:55333",
+    "address of the light server",
+)];
+
+// One attempt over a new connection.
+fn send(
+    addr: &str,
+    tls_config: Option<&Arc<ClientConfig>>,
+    credentials: Option<&Credentials>,
+    command: LightCommand,
+) -> Result<String, ConnectError> {
+    let stream = client::connect(addr, &Timeouts::default())?;
+    let stream = tls::connect_optional(stream, addr, tls_config)?;
+    Ok(TcpSession::open(stream, credentials)?.send(command)?)
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let settings = [SETTINGS, tls::CLIENT_SETTINGS, auth::CLIENT_SETTINGS].concat();
+    let config = Config::from_env("light-tcp-client", &settings);
+    let addr = config.get_addr("light_addr");
+    config.init_logger();
+
+    let args = config.get_args();
+    if args.is_empty() {
+        println!("Usage: light-tcp-client [--<setting> <value>...] <command>");
+        println!("Available commands: {}", COMMANDS);
+        return Ok(());
+    }
+
+    let Ok(command) = LightCommand::from_str(&args.join(" ")) else {
+        println!("Unknown command. Available commands: {}", COMMANDS);
+        return Ok(());
+    };
+
+    println!("Sending command: {:?}", command);
+    let tls_config = tls::client_config_from(&config)?;
+    let credentials = Credentials::from_config(&config)?;
+    // Only idempotent commands are repeated after a timeout.
+    let response = RetryPolicy::default().run(command.is_idempotent(), || {
+        send(
+            addr,
+            tls_config.as_ref(),
+            credentials.as_ref(),
+            command.clone(),
+        )
+    })?;
+    println!("Sent command: {:?} to: {}", command, addr);
+    println!("{}", response);
+
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use std::error::Error;
+
+use log::info;
+use smart_home::config::{Config, Setting};
+use smart_home::devices::light::SmartLight;
+use smart_home_tcp_client::auth::{self, KeyStore};
+use smart_home_tcp_client::devices::light::LightServer;
+use smart_home_tcp_client::devices::tcp_device::TcpDevice;
+use smart_home_tcp_client::server::ConcurrentServer;
+use smart_home_tcp_client::state::StateFile;
+use smart_home_tcp_client::tls;
+
+const SETTINGS: &[Setting] = &[
+    Setting::addr("light_addr", "127.0.0.1:55333", "address to listen on"),
+    Setting::new("name", "light", "device name"),
+    Setting::new("description", "light", "device description"),
+    Setting::count("workers", "4", "connections served at the same time"),
+    // Whether the light is on, its brightness and colour temperature, restored on start.
+    Setting::new("state_file", "settings/light_state", "saved light state"),
+];
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let settings = [SETTINGS, tls::SERVER_SETTINGS, auth::SERVER_SETTINGS].concat();
+    let config = Config::from_env("light-tcp-server", &settings);
+    let addr = config.get_addr("light_addr");
+    let workers = config.get_count("workers");
+    let device = SmartLight::new(config.get("name"), config.get("description"));
+    config.init_logger();
+
+    let mut smart_light_server = ConcurrentServer::new(LightServer::bind(device, addr)?, workers)
+        .with_state_file(StateFile::new(config.get("state_file")))?;
+    if let Some(tls_config) = tls::server_config_from(&config)? {
+        smart_light_server = smart_light_server.with_tls(tls_config);
+    }
+    if let Some(keys) = KeyStore::from_config(&config)? {
+        smart_light_server = smart_light_server.with_keys(keys);
+    }
+
+    info!("Server started on {} with {} workers", addr, workers);
+
+    smart_light_server.shutdown_handle()?.on_signal()?;
+    smart_light_server.run();
+
+    Ok(())
+}
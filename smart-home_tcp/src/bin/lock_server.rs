@@ -0,0 +1,51 @@
+use std::error::Error;
+use std::time::{Duration, SystemTime};
+
+use log::info;
+use smart_home::config::{Config, Setting};
+use smart_home::devices::lock::{PinCode, SmartLock};
+use smart_home_tcp_client::auth::{self, KeyStore};
+use smart_home_tcp_client::devices::lock::LockServer;
+use smart_home_tcp_client::devices::tcp_device::TcpDevice;
+use smart_home_tcp_client::server::ConcurrentServer;
+use smart_home_tcp_client::state::StateFile;
+use smart_home_tcp_client::tls;
+
+const SETTINGS: &[Setting] = &[
+    Setting::addr("lock_addr", "127.0.0.1:55334", "address to listen on"),
+    Setting::new("name", "lock", "device name"),
+    Setting::new("description", "Front door lock", "device description"),
+    Setting::count("workers", "4", "connections served at the same time"),
+    // Whether the door is locked, restored on start.
+    Setting::new("state_file", "settings/lock_state", "saved lock state"),
+];
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let settings = [SETTINGS, tls::SERVER_SETTINGS, auth::SERVER_SETTINGS].concat();
+    let config = Config::from_env("lock-tcp-server", &settings);
+    let addr = config.get_addr("lock_addr");
+    let workers = config.get_count("workers");
+    config.init_logger();
+
+    let mut device = SmartLock::new(config.get("name"), config.get("description"));
+    device.add_pin(PinCode::new("owner", "1234"));
+    let now = SystemTime::now();
+    device
+        .add_pin(PinCode::new("guest", "4321").with_validity(now, now + Duration::from_secs(3600)));
+    device.set_auto_relock(Some(Duration::from_secs(30)));
+    let mut smart_lock_server = ConcurrentServer::new(LockServer::bind(device, addr)?, workers)
+        .with_state_file(StateFile::new(config.get("state_file")))?;
+    if let Some(tls_config) = tls::server_config_from(&config)? {
+        smart_lock_server = smart_lock_server.with_tls(tls_config);
+    }
+    if let Some(keys) = KeyStore::from_config(&config)? {
+        smart_lock_server = smart_lock_server.with_keys(keys);
+    }
+
+    info!("Server started on {} with {} workers", addr, workers);
+
+    smart_lock_server.shutdown_handle()?.on_signal()?;
+    smart_lock_server.run();
+
+    Ok(())
+}
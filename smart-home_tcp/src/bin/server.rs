@@ -1,24 +1,50 @@
 use std::error::Error;
-use std::fs;
 
+use log::info;
+use smart_home::config::{Config, Setting};
 use smart_home::devices::socket::SmartSocket;
+use smart_home_tcp_client::auth::{self, KeyStore};
 use smart_home_tcp_client::devices::socket::SocketServer;
 use smart_home_tcp_client::devices::tcp_device::TcpDevice;
+use smart_home_tcp_client::server::ConcurrentServer;
+use smart_home_tcp_client::state::StateFile;
+use smart_home_tcp_client::tls;
+
+const SETTINGS: &[Setting] = &[
+    Setting::addr("addr", "127.0.0.1:55331", "address to listen on"),
+    Setting::new("name", "socket", "device name"),
+    Setting::new("description", "socket", "device description"),
+    Setting::number("power", "1000", "power consumption, W"),
+    Setting::count("workers", "4", "connections served at the same time"),
+    // Whether the socket is on, restored on start.
+    Setting::new("state_file", "settings/socket_state", "saved socket state"),
+];
 
 fn main() -> Result<(), Box<dyn Error>> {
-    let addr =
-        fs::read_to_string("settings/addr").unwrap_or_else(|_| String::from("127.0.0.1:55331"));
-    let device = SmartSocket::new("socket", "socket", 1000);
-    let mut smart_socket_server = SocketServer::bind(device, addr.clone())?;
+    let settings = [SETTINGS, tls::SERVER_SETTINGS, auth::SERVER_SETTINGS].concat();
+    let config = Config::from_env("socket-tcp-server", &settings);
+    let addr = config.get_addr("addr");
+    let workers = config.get_count("workers");
+    let device = SmartSocket::new(
+        config.get("name"),
+        config.get("description"),
+        config.get_number("power"),
+    );
+    config.init_logger();
 
-    println!("Server started on {}", addr);
+    let mut smart_socket_server = ConcurrentServer::new(SocketServer::bind(device, addr)?, workers)
+        .with_state_file(StateFile::new(config.get("state_file")))?;
+    if let Some(tls_config) = tls::server_config_from(&config)? {
+        smart_socket_server = smart_socket_server.with_tls(tls_config);
+    }
+    if let Some(keys) = KeyStore::from_config(&config)? {
+        smart_socket_server = smart_socket_server.with_keys(keys);
+    }
 
-    loop {
-        let Ok(mut connection) = smart_socket_server.accept() else {
-            println!("Connection failed");
-            continue;
-        };
+    info!("Server started on {} with {} workers", addr, workers);
 
-        smart_socket_server.handle(&mut connection)?;
-    }
+    smart_socket_server.shutdown_handle()?.on_signal()?;
+    smart_socket_server.run();
+
+    Ok(())
 }
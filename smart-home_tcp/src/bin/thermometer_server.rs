@@ -0,0 +1,63 @@
+use std::error::Error;
+
+use log::info;
+use smart_home::config::{Config, Setting};
+use smart_home::devices::thermometer::SmartThermometer;
+use smart_home_tcp_client::auth::{self, KeyStore};
+use smart_home_tcp_client::devices::tcp_device::TcpDevice;
+use smart_home_tcp_client::devices::thermometer::{ThermometerTcpServer, UdpTemperatureSource};
+use smart_home_tcp_client::server::ConcurrentServer;
+use smart_home_tcp_client::tls;
+
+const SETTINGS: &[Setting] = &[
+    Setting::addr(
+        "thermometer_addr",
+        "127.0.0.1:55335",
+        "address to listen on",
+    ),
+    // Where `thermometer-generator-udp-server` sends its readings.
+    Setting::addr(
+        "thermometer_udp_addr",
+        "127.0.0.1:4322",
+        "address readings arrive at",
+    ),
+    Setting::new("name", "thermometer", "device name"),
+    Setting::new(
+        "description",
+        "Living room thermometer",
+        "device description",
+    ),
+    Setting::count("workers", "4", "connections served at the same time"),
+];
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let settings = [SETTINGS, tls::SERVER_SETTINGS, auth::SERVER_SETTINGS].concat();
+    let config = Config::from_env("thermometer-tcp-server", &settings);
+    let addr = config.get_addr("thermometer_addr");
+    let udp_addr = config.get_addr("thermometer_udp_addr");
+    let workers = config.get_count("workers");
+    config.init_logger();
+
+    let device = SmartThermometer::new(config.get("name"), config.get("description"));
+    let source = UdpTemperatureSource::bind(udp_addr)?;
+    let mut server = ConcurrentServer::new(
+        ThermometerTcpServer::bind(device, addr)?.with_source(source),
+        workers,
+    );
+    if let Some(tls_config) = tls::server_config_from(&config)? {
+        server = server.with_tls(tls_config);
+    }
+    if let Some(keys) = KeyStore::from_config(&config)? {
+        server = server.with_keys(keys);
+    }
+
+    info!(
+        "Server started on {}, reading temperature from {}",
+        addr, udp_addr
+    );
+
+    server.shutdown_handle()?.on_signal()?;
+    server.run();
+
+    Ok(())
+}
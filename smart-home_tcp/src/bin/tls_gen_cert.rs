@@ -0,0 +1,20 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use smart_home::config::Config;
+use smart_home_tcp_client::tls;
+
+// Self-signed certificate for local development, picked up by the TCP servers and clients.
+fn main() -> Result<(), Box<dyn Error>> {
+    let config = Config::from_env("tls-gen-cert", tls::SERVER_SETTINGS);
+    let (tls_cert, tls_key) = (config.get("tls_cert"), config.get("tls_key"));
+    for path in [tls_cert, tls_key] {
+        if let Some(dir) = Path::new(path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+    }
+    tls::generate_self_signed(&["localhost", "127.0.0.1"], tls_cert, tls_key)?;
+    println!("Certificate written to {} and key to {}", tls_cert, tls_key);
+    Ok(())
+}
@@ -1,47 +1,234 @@
 use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
 
+use rustls::ClientConfig;
+
+use crate::auth::Credentials;
+use crate::devices::tcp_device::ConnectError;
+use crate::framing::FramedStream;
 use crate::protocol::{
-    read_till_rn, ProtocolCommand, ProtocolError, CLIENT_HANDSHAKE, SERVER_HANDSHAKE,
+    handshake_error, handshake_read_error, io_error, read_till_rn, ClientHandshake, ErrorResponse,
+    Framing, HandshakeStep, Notification, ProtocolCommand, ProtocolError, ServerInfo,
 };
+use crate::tls::{self, ClientStream, ClientTlsStream};
 
-fn send_handshake<Stream: Write + Read>(stream: &mut Stream) -> Result<(), ProtocolError> {
-    stream
-        .write_all(CLIENT_HANDSHAKE)
-        .map_err(|e| ProtocolError::BadHandshake(e.to_string()))?;
-    stream
-        .flush()
-        .map_err(|e| ProtocolError::BadHandshake(e.to_string()))?;
+// Durations must not be zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timeouts {
+    pub connect: Duration,
+    pub read: Duration,
+    pub write: Duration,
+}
 
-    let mut buf = vec![0; SERVER_HANDSHAKE.len()];
-    stream
-        .read_exact(&mut buf)
-        .map_err(|e| ProtocolError::BadHandshake(e.to_string()))?;
+impl Default for Timeouts {
+    fn default() -> Self {
+        Self {
+            connect: Duration::from_secs(3),
+            read: Duration::from_secs(5),
+            write: Duration::from_secs(5),
+        }
+    }
+}
 
-    if buf != *SERVER_HANDSHAKE {
-        return Err(ProtocolError::BadHandshake(
-            "Handshake mismatch".to_string(),
-        ));
+// Connects to the first reachable address. Reads and writes on the stream fail
+// with `ProtocolError::Timeout` instead of blocking forever.
+pub fn connect(addr: impl ToSocketAddrs, timeouts: &Timeouts) -> Result<TcpStream, ProtocolError> {
+    let mut last_error = None;
+    let addrs = addr
+        .to_socket_addrs()
+        .map_err(|e| io_error(e, ProtocolError::CouldNotSend))?;
+    for addr in addrs {
+        match TcpStream::connect_timeout(&addr, timeouts.connect) {
+            Ok(stream) => {
+                stream
+                    .set_read_timeout(Some(timeouts.read))
+                    .and_then(|()| stream.set_write_timeout(Some(timeouts.write)))
+                    .map_err(|e| io_error(e, ProtocolError::CouldNotSend))?;
+                return Ok(stream);
+            }
+            Err(error) => last_error = Some(error),
+        }
+    }
+    Err(last_error.map_or(ProtocolError::CouldNotSend, |error| {
+        io_error(error, ProtocolError::CouldNotSend)
+    }))
+}
+
+// Servers that do not offer the requested framing use lines.
+fn send_handshake<Stream: Write + Read>(
+    stream: &mut Stream,
+    credentials: Option<&Credentials>,
+    framing: Framing,
+) -> Result<ServerInfo, ConnectError> {
+    let mut handshake = ClientHandshake::new(credentials, framing);
+    write_handshake_line(stream, &handshake.hello())?;
+
+    loop {
+        let line = read_till_rn(stream).map_err(handshake_read_error)?;
+        match handshake.next_line(&line) {
+            HandshakeStep::Reply(reply) => write_handshake_line(stream, &reply)?,
+            HandshakeStep::Done(choice, info) => {
+                write_handshake_line(stream, &choice)?;
+                return Ok(info);
+            }
+            HandshakeStep::Reject(_, error) => return Err(error),
+        }
     }
-    Ok(())
+}
+
+// Flushed, as the server answers only once it has the whole line.
+fn write_handshake_line<Stream: Write>(
+    stream: &mut Stream,
+    line: &str,
+) -> Result<(), ProtocolError> {
+    stream.write_all(line.as_bytes()).map_err(handshake_error)?;
+    stream.flush().map_err(handshake_error)
 }
 
 pub fn send_command<Stream: Write + Read>(
     command: impl ProtocolCommand,
     stream: &mut Stream,
 ) -> Result<(), ProtocolError> {
-    send_handshake(stream)?;
+    // Single commands are sent without credentials, use `TcpSession::open` for those.
+    send_handshake(stream, None, Framing::Lines).map_err(|error| match error {
+        ConnectError::Protocol(error) => error,
+        error => ProtocolError::BadHandshake(error.to_string()),
+    })?;
     let binding = command.to_string();
     let bytes = binding.as_bytes();
 
     stream
         .write_all(bytes)
-        .map_err(|_| ProtocolError::CouldNotSend)?;
+        .map_err(|e| io_error(e, ProtocolError::CouldNotSend))?;
 
     Ok(())
 }
 
+// Error responses of the server are returned as `ProtocolError::ServerError`.
 pub fn receive_response<Reader: Read>(reader: &mut Reader) -> Result<String, ProtocolError> {
-    read_till_rn(reader).map_err(|_| ProtocolError::InvalidResponse)
+    parse_response(read_till_rn(reader)?)
+}
+
+fn parse_response(response: String) -> Result<String, ProtocolError> {
+    match ErrorResponse::from_line(&response) {
+        Some(error) => Err(ProtocolError::ServerError(error)),
+        None => Ok(response),
+    }
+}
+
+// A connection that handshakes once and then exchanges any number of commands.
+#[derive(Debug)]
+pub struct TcpSession<S = TcpStream> {
+    stream: FramedStream<S>,
+    server_info: ServerInfo,
+}
+
+impl TcpSession {
+    pub fn connect(addr: impl ToSocketAddrs) -> Result<Self, ConnectError> {
+        Self::open(connect(addr, &Timeouts::default())?, None)
+    }
+}
+
+impl TcpSession<ClientTlsStream> {
+    // `server_name` must match the server certificate.
+    pub fn connect_tls(
+        addr: impl ToSocketAddrs,
+        server_name: &str,
+        config: &Arc<ClientConfig>,
+    ) -> Result<Self, ConnectError> {
+        let stream = connect(addr, &Timeouts::default())?;
+        Self::open(tls::connect(stream, server_name, config)?, None)
+    }
+}
+
+impl TcpSession<ClientStream> {
+    // Connects with the default timeouts, over TLS if there is a configuration, see
+    // `tls::connect_optional`.
+    pub fn connect_with(
+        addr: &str,
+        tls_config: Option<&Arc<ClientConfig>>,
+        credentials: Option<&Credentials>,
+    ) -> Result<Self, ConnectError> {
+        let stream = connect(addr, &Timeouts::default())?;
+        Self::open(
+            tls::connect_optional(stream, addr, tls_config)?,
+            credentials,
+        )
+    }
+}
+
+impl<S: Read + Write> TcpSession<S> {
+    // Handshakes over an open stream, plain or TLS. Servers with keys
+    // require credentials and fail with `ConnectError::Unauthorized` without them.
+    pub fn open(stream: S, credentials: Option<&Credentials>) -> Result<Self, ConnectError> {
+        Self::open_framed(stream, credentials, Framing::Lines)
+    }
+
+    // Asks for another framing than lines, which the server may decline.
+    // `get_server_info` tells the framing in use.
+    pub fn open_framed(
+        stream: S,
+        credentials: Option<&Credentials>,
+        framing: Framing,
+    ) -> Result<Self, ConnectError> {
+        let mut stream = FramedStream::new(stream);
+        let server_info = send_handshake(&mut stream, credentials, framing)?;
+        stream.set_framing(server_info.framing);
+        Ok(Self {
+            stream,
+            server_info,
+        })
+    }
+
+    // Protocol version, device kind and commands agreed on in the handshake.
+    pub fn get_server_info(&self) -> &ServerInfo {
+        &self.server_info
+    }
+
+    pub fn send(&mut self, command: impl ProtocolCommand) -> Result<String, ProtocolError> {
+        self.stream.write_message(command.to_string().as_bytes())?;
+        parse_response(read_message(&mut self.stream)?)
+    }
+
+    // Sends a watch command, e.g. `SocketCommand::Watch`, after which the
+    // connection only carries notifications.
+    pub fn watch(mut self, command: impl ProtocolCommand) -> Result<Watcher<S>, ProtocolError> {
+        self.send(command)?;
+        Ok(Watcher {
+            stream: self.stream,
+        })
+    }
+}
+
+fn read_message<S: Read>(stream: &mut FramedStream<S>) -> Result<String, ProtocolError> {
+    String::from_utf8(stream.read_message()?).map_err(|_| ProtocolError::InvalidResponse)
+}
+
+// Notifications pushed by the server, starting with the current state.
+#[derive(Debug)]
+pub struct Watcher<S = TcpStream> {
+    stream: FramedStream<S>,
+}
+
+impl<S: Read> Watcher<S> {
+    // Blocks until the next notification, keepalives are skipped. The read timeout of
+    // the stream still applies, `ProtocolError::Timeout` then only means nothing has changed.
+    pub fn next_notification(&mut self) -> Result<Notification, ProtocolError> {
+        loop {
+            let line = read_message(&mut self.stream)?;
+            match Notification::from_line(&line) {
+                Some(Notification::Keepalive) => {}
+                Some(notification) => return Ok(notification),
+                None => return Err(ProtocolError::InvalidResponse),
+            }
+        }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        self.stream.get_ref()
+    }
 }
 
 #[cfg(test)]
@@ -50,11 +237,32 @@ mod tests {
     use std::net::{TcpListener, TcpStream};
     use std::thread;
 
+    use smart_home::devices::socket::SmartSocket;
+
+    use std::time::Duration;
+
     use crate::{
-        client::{receive_response, send_command},
-        protocol::{ParseError, ProtocolCommand, CLIENT_HANDSHAKE, SERVER_HANDSHAKE},
+        client::{connect, receive_response, send_command, TcpSession, Timeouts},
+        devices::{
+            socket::{SocketCommand, SocketServer},
+            tcp_device::{ConnectError, TcpDevice},
+        },
+        protocol::{
+            format_hello, read_till_rn, ErrorCode, ErrorResponse, Framing, ParseError,
+            ProtocolCommand, ProtocolError, ServerInfo, CLIENT_HANDSHAKE, LEGACY_VERSION,
+            MAX_LINE_LEN, PROTOCOL_VERSION, SERVER_HANDSHAKE,
+        },
     };
 
+    fn test_server_info() -> ServerInfo {
+        ServerInfo {
+            version: PROTOCOL_VERSION,
+            kind: "test".to_owned(),
+            commands: vec!["switch".to_owned()],
+            framing: Framing::Lines,
+        }
+    }
+
     #[derive(Debug)]
     enum TestCommand {
         Switch,
@@ -81,15 +289,17 @@ mod tests {
         let server_thread = thread::spawn(move || {
             let (mut stream, _) = listener.accept().expect("Failed to accept connection");
 
-            let mut buf = [0u8; 4];
-            stream
-                .read_exact(&mut buf)
-                .expect("Failed to read handshake");
-            assert_eq!(&buf, CLIENT_HANDSHAKE);
+            let hello = read_till_rn(&mut stream).expect("Failed to read handshake");
+            assert_eq!(
+                hello,
+                format_hello(LEGACY_VERSION, PROTOCOL_VERSION, Framing::Lines)
+            );
 
             stream
-                .write_all(SERVER_HANDSHAKE)
+                .write_all(test_server_info().to_line().as_bytes())
                 .expect("Failed to write handshake");
+            let choice = read_till_rn(&mut stream).expect("Failed to read framing");
+            assert_eq!(choice, "smhp framing=lines\r\n");
 
             let mut buf = [0u8; 8]; // "switch\r\n" is 8 bytes
             stream.read_exact(&mut buf).expect("Failed to read command");
@@ -114,16 +324,18 @@ mod tests {
             let (mut stream, _) = listener.accept().expect("Failed to accept connection");
 
             // Read client handshake
-            let mut buf = [0u8; 4];
-            stream
-                .read_exact(&mut buf)
-                .expect("Failed to read handshake");
-            assert_eq!(&buf, CLIENT_HANDSHAKE);
+            let hello = read_till_rn(&mut stream).expect("Failed to read handshake");
+            assert_eq!(
+                hello,
+                format_hello(LEGACY_VERSION, PROTOCOL_VERSION, Framing::Lines)
+            );
 
             // Send server handshake
             stream
-                .write_all(SERVER_HANDSHAKE)
+                .write_all(test_server_info().to_line().as_bytes())
                 .expect("Failed to write handshake");
+            let choice = read_till_rn(&mut stream).expect("Failed to read framing");
+            assert_eq!(choice, "smhp framing=lines\r\n");
 
             // Read command
             let mut buf = [0u8; 8]; // "switch\r\n" is 8 bytes
@@ -146,4 +358,229 @@ mod tests {
 
         server_thread.join().expect("Failed to join server thread");
     }
+
+    #[test]
+    fn test_session() {
+        let mut server = SocketServer::bind(
+            SmartSocket::new("Socket", "A smart socket", 100),
+            "127.0.0.1:0",
+        )
+        .expect("Failed to bind server");
+        let addr = server
+            .get_listener()
+            .local_addr()
+            .expect("No local address");
+
+        let server_thread = thread::spawn(move || {
+            for _ in 0..2 {
+                let mut stream = server.accept().expect("Failed to accept");
+                server.serve(&mut stream).expect("Failed to serve session");
+            }
+        });
+
+        let mut session = TcpSession::connect(addr).expect("Failed to connect");
+        let info = session.get_server_info();
+        assert_eq!(info.version, PROTOCOL_VERSION);
+        assert_eq!(info.kind, "socket");
+        assert!(info.supports("power"));
+        assert_eq!(session.send(SocketCommand::Status).unwrap(), "off\r\n");
+        assert_eq!(session.send(SocketCommand::Switch).unwrap(), "ok\r\n");
+        assert_eq!(session.send(SocketCommand::Status).unwrap(), "on\r\n");
+        // Watching needs a server serving other connections at the same time.
+        assert!(matches!(
+            session.send(SocketCommand::Watch),
+            Err(ProtocolError::ServerError(ErrorResponse {
+                code: ErrorCode::UnknownCommand,
+                ..
+            }))
+        ));
+        drop(session);
+
+        // The server takes the next client once the session is closed.
+        let mut session = TcpSession::connect(addr).expect("Failed to connect");
+        assert_eq!(session.send(SocketCommand::Status).unwrap(), "on\r\n");
+        drop(session);
+
+        server_thread.join().expect("Failed to join server thread");
+    }
+
+    #[test]
+    fn test_handshake_versions() {
+        let mut server = SocketServer::bind(
+            SmartSocket::new("Socket", "A smart socket", 100),
+            "127.0.0.1:0",
+        )
+        .expect("Failed to bind server");
+        let addr = server
+            .get_listener()
+            .local_addr()
+            .expect("No local address");
+
+        let server_thread = thread::spawn(move || {
+            let mut stream = server.accept().expect("Failed to accept");
+            server.serve(&mut stream).expect("Failed to serve session");
+
+            let error = server.accept().expect_err("Version mismatch expected");
+            assert!(matches!(
+                error,
+                ConnectError::Protocol(ProtocolError::UnsupportedVersion(_))
+            ));
+        });
+
+        // Old clients with the bare handshake are still served.
+        let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+        stream.write_all(CLIENT_HANDSHAKE).unwrap();
+        let mut buf = [0u8; 4];
+        stream.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, SERVER_HANDSHAKE);
+        stream.write_all(b"status\r\n").unwrap();
+        assert_eq!(receive_response(&mut stream).unwrap(), "off\r\n");
+        drop(stream);
+
+        // A client that only speaks newer versions gets an error line.
+        let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+        stream
+            .write_all(format_hello(4, 5, Framing::Lines).as_bytes())
+            .unwrap();
+        let line = read_till_rn(&mut stream).unwrap();
+        assert!(matches!(
+            ServerInfo::from_line(&line),
+            Err(ProtocolError::UnsupportedVersion(_))
+        ));
+
+        server_thread.join().expect("Failed to join server thread");
+    }
+
+    // Servers of version 2 reject a hello asking for a framing, and only ever use lines.
+    #[test]
+    fn test_framing_with_version_2_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind listener");
+        let addr = listener.local_addr().expect("Failed to get local address");
+        let server_thread = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("Failed to accept connection");
+            let hello = read_till_rn(&mut stream).expect("Failed to read handshake");
+            assert_eq!(
+                hello,
+                format_hello(LEGACY_VERSION, PROTOCOL_VERSION, Framing::Lines)
+            );
+            let info = ServerInfo {
+                version: 2,
+                ..test_server_info()
+            };
+            stream.write_all(info.to_line().as_bytes()).unwrap();
+            assert_eq!(read_till_rn(&mut stream).unwrap(), "switch\r\n");
+            stream.write_all(b"ok\r\n").unwrap();
+        });
+
+        let stream = TcpStream::connect(addr).expect("Failed to connect");
+        let mut session = TcpSession::open_framed(stream, None, Framing::LengthPrefixed)
+            .expect("Failed to connect");
+        assert_eq!(session.get_server_info().version, 2);
+        assert_eq!(session.get_server_info().framing, Framing::Lines);
+        assert_eq!(session.send(TestCommand::Switch).unwrap(), "ok\r\n");
+        server_thread.join().expect("Failed to join server thread");
+    }
+
+    #[test]
+    fn test_length_prefixed_session() {
+        let mut server = SocketServer::bind(
+            SmartSocket::new("Socket", "A smart socket", 100),
+            "127.0.0.1:0",
+        )
+        .expect("Failed to bind server");
+        let addr = server
+            .get_listener()
+            .local_addr()
+            .expect("No local address");
+
+        let server_thread = thread::spawn(move || {
+            for _ in 0..2 {
+                let mut stream = server.accept().expect("Failed to accept");
+                let result = server.serve(&mut stream);
+                if stream.get_framing() == Framing::Lines {
+                    assert!(matches!(result, Err(ProtocolError::MessageTooLong(_))));
+                }
+            }
+        });
+
+        let stream = TcpStream::connect(addr).expect("Failed to connect");
+        let mut session = TcpSession::open_framed(stream, None, Framing::LengthPrefixed)
+            .expect("Failed to connect");
+        assert_eq!(session.get_server_info().framing, Framing::LengthPrefixed);
+        assert_eq!(session.send(SocketCommand::Switch).unwrap(), "ok\r\n");
+        assert_eq!(session.send(SocketCommand::Status).unwrap(), "on\r\n");
+        drop(session);
+
+        // A line that never ends is answered with an error and the connection closed.
+        let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+        send_command(SocketCommand::Status, &mut stream).unwrap();
+        assert_eq!(receive_response(&mut stream).unwrap(), "on\r\n");
+        stream.write_all(&vec![b'a'; MAX_LINE_LEN + 1]).unwrap();
+        assert!(matches!(
+            receive_response(&mut stream),
+            Err(ProtocolError::ServerError(ErrorResponse {
+                code: ErrorCode::MalformedInput,
+                ..
+            }))
+        ));
+        assert!(receive_response(&mut stream).is_err());
+
+        server_thread.join().expect("Failed to join server thread");
+    }
+
+    #[test]
+    fn test_connect_to_unsupported_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind listener");
+        let addr = listener.local_addr().expect("Failed to get local address");
+
+        let server_thread = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("Failed to accept connection");
+            read_till_rn(&mut stream).expect("Failed to read handshake");
+            stream
+                .write_all(b"smhp error server supports 5-6\r\n")
+                .expect("Failed to write handshake");
+        });
+
+        let error = TcpSession::connect(addr).expect_err("Version mismatch expected");
+        assert!(matches!(
+            error,
+            ConnectError::Protocol(ProtocolError::UnsupportedVersion(message))
+                if message == "server supports 5-6"
+        ));
+
+        server_thread.join().expect("Failed to join server thread");
+    }
+
+    #[test]
+    fn test_timeouts() {
+        let timeouts = Timeouts {
+            read: Duration::from_millis(100),
+            ..Timeouts::default()
+        };
+
+        // The server accepts the connection but never answers the handshake.
+        let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind listener");
+        let addr = listener.local_addr().expect("Failed to get local address");
+        let server_thread = thread::spawn(move || listener.accept().unwrap());
+
+        let mut stream = connect(addr, &timeouts).expect("Failed to connect");
+        assert!(matches!(
+            send_command(SocketCommand::Status, &mut stream),
+            Err(ProtocolError::Timeout(_))
+        ));
+        drop(server_thread.join());
+
+        // Nothing listens on the port any more.
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .and_then(|listener| listener.local_addr())
+            .unwrap();
+        assert!(matches!(
+            connect(addr, &timeouts),
+            Err(ProtocolError::ConnectionRefused(_))
+        ));
+        assert!(matches!(
+            TcpSession::connect(addr),
+            Err(ConnectError::Protocol(ProtocolError::ConnectionRefused(_)))
+        ));
+    }
 }
@@ -0,0 +1,251 @@
+use smart_home::devices::light::SmartLight;
+use std::{
+    net::{TcpListener, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    protocol::{ParseError, ProtocolCommand, OK},
+    state::StateError,
+};
+
+use super::tcp_device::TcpDevice;
+
+pub const COMMANDS: &[&str] = &["on", "off", "switch", "status", "brightness", "temperature"];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LightCommand {
+    On,
+    Off,
+    Switch,
+    Status,
+    // Target value and transition time in milliseconds.
+    Brightness(u8, u64),
+    ColorTemperature(u16, u64),
+}
+
+impl ProtocolCommand for LightCommand {
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let unknown = || ParseError::UnknownCommand(s.trim().to_owned());
+        let malformed = || ParseError::MalformedCommand(s.trim().to_owned());
+        let mut parts = s.split_whitespace();
+        let command = parts.next().ok_or_else(unknown)?;
+        if !matches!(
+            command,
+            "on" | "off" | "switch" | "status" | "brightness" | "temperature"
+        ) {
+            return Err(unknown());
+        }
+        let value = parts.next();
+        let transition = match parts.next() {
+            Some(ms) => ms.parse().map_err(|_| malformed())?,
+            None => 0,
+        };
+        if parts.next().is_some() {
+            return Err(malformed());
+        }
+
+        match (command, value) {
+            ("on", None) => Ok(LightCommand::On),
+            ("off", None) => Ok(LightCommand::Off),
+            ("switch", None) => Ok(LightCommand::Switch),
+            ("status", None) => Ok(LightCommand::Status),
+            ("brightness", Some(value)) => Ok(LightCommand::Brightness(
+                value.parse().map_err(|_| malformed())?,
+                transition,
+            )),
+            ("temperature", Some(value)) => Ok(LightCommand::ColorTemperature(
+                value.parse().map_err(|_| malformed())?,
+                transition,
+            )),
+            _ => Err(malformed()),
+        }
+    }
+
+    fn to_string(&self) -> String {
+        match self {
+            LightCommand::On => "on\r\n".to_owned(),
+            LightCommand::Off => "off\r\n".to_owned(),
+            LightCommand::Switch => "switch\r\n".to_owned(),
+            LightCommand::Status => "status\r\n".to_owned(),
+            LightCommand::Brightness(value, ms) => format!("brightness {} {}\r\n", value, ms),
+            LightCommand::ColorTemperature(value, ms) => {
+                format!("temperature {} {}\r\n", value, ms)
+            }
+        }
+    }
+
+    fn is_idempotent(&self) -> bool {
+        !matches!(self, LightCommand::Switch)
+    }
+}
+
+// `<on|off> <brightness> <colour temperature>`, as `status` answers it.
+pub fn state(light: &SmartLight) -> String {
+    let status = if light.is_on() { "on" } else { "off" };
+    format!(
+        "{} {} {}",
+        status,
+        light.brightness(),
+        light.color_temperature()
+    )
+}
+
+// Applies the command to the light and returns the response line.
+pub fn execute(light: &mut SmartLight, command: LightCommand) -> String {
+    match command {
+        LightCommand::On => {
+            light.turn_on();
+            OK.to_owned()
+        }
+        LightCommand::Off => {
+            light.turn_off();
+            OK.to_owned()
+        }
+        LightCommand::Switch => {
+            light.switch();
+            OK.to_owned()
+        }
+        LightCommand::Status => format!("{}\r\n", state(light)),
+        LightCommand::Brightness(value, ms) => {
+            let color_temperature = light.color_temperature();
+            light.start_transition(value, color_temperature, Duration::from_millis(ms));
+            OK.to_owned()
+        }
+        LightCommand::ColorTemperature(value, ms) => {
+            let brightness = light.brightness();
+            light.start_transition(brightness, value, Duration::from_millis(ms));
+            OK.to_owned()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LightServer {
+    listener: TcpListener,
+    device: SmartLight,
+    last_update: Instant,
+}
+
+impl LightServer {
+    pub fn get_device(&self) -> &SmartLight {
+        &self.device
+    }
+
+    // Transitions run in real time between commands.
+    fn advance(&mut self) {
+        let now = Instant::now();
+        self.device.advance(now - self.last_update);
+        self.last_update = now;
+    }
+}
+
+impl TcpDevice<SmartLight> for LightServer {
+    type Command = LightCommand;
+    const KIND: &'static str = "light";
+    const COMMANDS: &'static [&'static str] = COMMANDS;
+
+    fn bind<Addrs: ToSocketAddrs>(
+        device: SmartLight,
+        addr: Addrs,
+    ) -> Result<LightServer, std::io::Error> {
+        let listener = TcpListener::bind(addr)?;
+        Ok(LightServer {
+            listener,
+            device,
+            last_update: Instant::now(),
+        })
+    }
+
+    fn get_listener(&self) -> TcpListener {
+        self.listener.try_clone().expect("Failed to clone listener")
+    }
+
+    fn execute(&mut self, command: LightCommand) -> String {
+        self.advance();
+        execute(&mut self.device, command)
+    }
+
+    // A transition in progress is saved as far as it got.
+    fn get_state(&self) -> Option<String> {
+        Some(state(&self.device))
+    }
+
+    fn set_state(&mut self, state: &str) -> Result<(), StateError> {
+        let invalid = || StateError::Invalid(state.to_owned());
+        let [status, brightness, color_temperature] =
+            state.split_whitespace().collect::<Vec<_>>()[..]
+        else {
+            return Err(invalid());
+        };
+        let brightness = brightness.parse().map_err(|_| invalid())?;
+        let color_temperature = color_temperature.parse().map_err(|_| invalid())?;
+        match status {
+            "on" => self.device.turn_on(),
+            "off" => self.device.turn_off(),
+            _ => return Err(invalid()),
+        }
+        self.device.set_brightness(brightness);
+        self.device.set_color_temperature(color_temperature);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_light_command_parse() {
+        assert_eq!(LightCommand::from_str("on\r\n").unwrap(), LightCommand::On);
+        assert_eq!(
+            LightCommand::from_str("brightness 40\r\n").unwrap(),
+            LightCommand::Brightness(40, 0)
+        );
+        assert_eq!(
+            LightCommand::from_str("temperature 4000 500\r\n").unwrap(),
+            LightCommand::ColorTemperature(4000, 500)
+        );
+        assert!(LightCommand::from_str("brightness\r\n").is_err());
+        assert!(LightCommand::from_str("brightness high\r\n").is_err());
+        assert!(LightCommand::from_str("on 1\r\n").is_err());
+        assert!(matches!(
+            LightCommand::from_str("brightness high\r\n"),
+            Err(ParseError::MalformedCommand(_))
+        ));
+        assert!(matches!(
+            LightCommand::from_str("dim 10\r\n"),
+            Err(ParseError::UnknownCommand(_))
+        ));
+
+        let command = LightCommand::Brightness(10, 200);
+        assert_eq!(
+            LightCommand::from_str(&command.to_string()).unwrap(),
+            command
+        );
+    }
+
+    #[test]
+    fn test_light_state() {
+        let bind = || {
+            LightServer::bind(SmartLight::new("Light", "Ceiling light"), "127.0.0.1:0")
+                .expect("Failed to bind")
+        };
+        let mut server = bind();
+        server.execute(LightCommand::On);
+        server.execute(LightCommand::Brightness(40, 0));
+        let state = server.get_state().unwrap();
+        assert_eq!(state, "on 40 2700");
+
+        let mut restored = bind();
+        restored.set_state(&state).unwrap();
+        assert_eq!(restored.execute(LightCommand::Status), "on 40 2700\r\n");
+
+        for state in ["on", "dim 40 2700", "on bright 2700", "on 40 2700 1"] {
+            assert!(matches!(
+                restored.set_state(state),
+                Err(StateError::Invalid(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use smart_home::devices::lock::{AccessEntry, SmartLock};
+use std::{
+    net::{TcpListener, ToSocketAddrs},
+    time::SystemTime,
+};
+
+use crate::{
+    protocol::{ParseError, ProtocolCommand, OK},
+    state::StateError,
+};
+
+use super::tcp_device::TcpDevice;
+
+pub const COMMANDS: &[&str] = &["lock", "unlock", "status", "log"];
+pub const DENIED: &str = "denied\r\n";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LockCommand {
+    Lock,
+    Unlock(String),
+    Status,
+    Log,
+}
+
+impl ProtocolCommand for LockCommand {
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let parts = s.split_whitespace().collect::<Vec<_>>();
+        match parts.as_slice() {
+            ["lock"] => Ok(LockCommand::Lock),
+            ["unlock", pin] => Ok(LockCommand::Unlock((*pin).to_owned())),
+            ["status"] => Ok(LockCommand::Status),
+            ["log"] => Ok(LockCommand::Log),
+            ["lock" | "unlock" | "status" | "log", ..] => {
+                Err(ParseError::MalformedCommand(s.trim().to_owned()))
+            }
+            _ => Err(ParseError::UnknownCommand(s.trim().to_owned())),
+        }
+    }
+
+    fn to_string(&self) -> String {
+        match self {
+            LockCommand::Lock => "lock\r\n".to_owned(),
+            LockCommand::Unlock(pin) => format!("unlock {}\r\n", pin),
+            LockCommand::Status => "status\r\n".to_owned(),
+            LockCommand::Log => "log\r\n".to_owned(),
+        }
+    }
+
+    // Every unlock attempt is logged, so it is not repeated.
+    fn is_idempotent(&self) -> bool {
+        !matches!(self, LockCommand::Unlock(_))
+    }
+}
+
+// `<unix time> <user or -> <action> <granted|denied>`
+pub fn format_access_entry(entry: &AccessEntry) -> String {
+    let seconds = entry
+        .timestamp
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    format!(
+        "{} {} {} {}",
+        seconds,
+        entry.user.as_deref().unwrap_or("-"),
+        entry.action,
+        if entry.granted { "granted" } else { "denied" }
+    )
+}
+
+// `locked` or `unlocked`, as `status` answers it.
+pub fn state(lock: &SmartLock) -> String {
+    let state = if lock.is_locked() {
+        "locked"
+    } else {
+        "unlocked"
+    };
+    state.to_owned()
+}
+
+// Applies the command to the lock and returns the response line.
+pub fn execute(lock: &mut SmartLock, command: LockCommand) -> String {
+    // The auto-relock timer is checked lazily, before every command.
+    let now = SystemTime::now();
+    lock.tick(now);
+    match command {
+        LockCommand::Lock => {
+            lock.lock_at(now);
+            OK.to_owned()
+        }
+        LockCommand::Unlock(pin) => match lock.unlock_at(&pin, now) {
+            Ok(()) => OK.to_owned(),
+            Err(_) => DENIED.to_owned(),
+        },
+        LockCommand::Status => format!("{}\r\n", state(lock)),
+        LockCommand::Log => {
+            let entries = lock
+                .get_access_log()
+                .map(format_access_entry)
+                .collect::<Vec<_>>();
+            format!("{}\r\n", entries.join("; "))
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct LockServer {
+    listener: TcpListener,
+    device: SmartLock,
+}
+
+impl LockServer {
+    pub fn get_device(&self) -> &SmartLock {
+        &self.device
+    }
+}
+
+impl TcpDevice<SmartLock> for LockServer {
+    type Command = LockCommand;
+    const KIND: &'static str = "lock";
+    const COMMANDS: &'static [&'static str] = COMMANDS;
+
+    fn bind<Addrs: ToSocketAddrs>(
+        device: SmartLock,
+        addr: Addrs,
+    ) -> Result<LockServer, std::io::Error> {
+        let listener = TcpListener::bind(addr)?;
+        Ok(LockServer { listener, device })
+    }
+
+    fn get_listener(&self) -> TcpListener {
+        self.listener.try_clone().expect("Failed to clone listener")
+    }
+
+    fn execute(&mut self, command: LockCommand) -> String {
+        execute(&mut self.device, command)
+    }
+
+    fn get_state(&self) -> Option<String> {
+        Some(state(&self.device))
+    }
+
+    // A lock restored unlocked relocks after the auto-relock delay, counted from now.
+    fn set_state(&mut self, state: &str) -> Result<(), StateError> {
+        let locked = match state {
+            "locked" => true,
+            "unlocked" => false,
+            other => return Err(StateError::Invalid(other.to_owned())),
+        };
+        self.device.restore_at(locked, SystemTime::now());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpStream, thread, time::Duration};
+
+    use smart_home::devices::lock::PinCode;
+
+    use super::*;
+    use crate::client::{receive_response, send_command};
+
+    #[test]
+    fn test_lock_command_parse() {
+        assert_eq!(
+            LockCommand::from_str("lock\r\n").unwrap(),
+            LockCommand::Lock
+        );
+        assert_eq!(
+            LockCommand::from_str("unlock 1234\r\n").unwrap(),
+            LockCommand::Unlock("1234".to_owned())
+        );
+        assert!(LockCommand::from_str("unlock\r\n").is_err());
+        assert!(matches!(
+            LockCommand::from_str("unlock 1 2\r\n"),
+            Err(ParseError::MalformedCommand(_))
+        ));
+
+        let command = LockCommand::Unlock("0000".to_owned());
+        assert_eq!(
+            LockCommand::from_str(&command.to_string()).unwrap(),
+            command
+        );
+    }
+
+    #[test]
+    fn test_lock_state() {
+        let bind = || {
+            let mut lock = SmartLock::new("Lock", "Front door lock");
+            lock.add_pin(PinCode::new("Alice", "1234"));
+            LockServer::bind(lock, "127.0.0.1:0").unwrap()
+        };
+        let mut server = bind();
+        assert_eq!(server.get_state().as_deref(), Some("locked"));
+        server.execute(LockCommand::Unlock("1234".to_owned()));
+        let state = server.get_state().unwrap();
+        assert_eq!(state, "unlocked");
+
+        let mut restored = bind();
+        restored.set_state(&state).unwrap();
+        assert_eq!(restored.execute(LockCommand::Status), "unlocked\r\n");
+        assert!(matches!(
+            restored.set_state("open"),
+            Err(StateError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_lock_server() {
+        let mut lock = SmartLock::new("Lock", "Front door lock");
+        lock.add_pin(PinCode::new("Alice", "1234"));
+        lock.set_auto_relock(Some(Duration::from_millis(200)));
+        let mut server = LockServer::bind(lock, "127.0.0.1:0").unwrap();
+        let addr = server.get_listener().local_addr().unwrap();
+        thread::spawn(move || loop {
+            let mut stream = server.accept().unwrap();
+            server.handle(&mut stream).unwrap();
+        });
+
+        let send = |command: LockCommand| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            send_command(command, &mut stream).unwrap();
+            receive_response(&mut stream).unwrap()
+        };
+
+        assert_eq!(send(LockCommand::Unlock("0000".to_owned())), "denied\r\n");
+        assert_eq!(send(LockCommand::Unlock("1234".to_owned())), "ok\r\n");
+        assert_eq!(send(LockCommand::Status), "unlocked\r\n");
+
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(send(LockCommand::Status), "locked\r\n");
+
+        let log = send(LockCommand::Log);
+        let entries = log.trim_end().split("; ").collect::<Vec<_>>();
+        assert_eq!(entries.len(), 3);
+        assert!(entries[0].ends_with(" - unlock denied"));
+        assert!(entries[1].ends_with(" Alice unlock granted"));
+        assert!(entries[2].ends_with(" - auto-lock granted"));
+    }
+}
@@ -1,3 +1,6 @@
+pub mod light;
+pub mod lock;
 pub mod socket;
+pub mod thermometer;
 
 pub mod tcp_device;
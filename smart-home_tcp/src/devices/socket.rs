@@ -1,17 +1,32 @@
-use smart_home::devices::socket::SmartSocket;
+use smart_home::devices::{device::Device, socket::SmartSocket};
 use std::{
-    io::Write,
-    net::{TcpListener, TcpStream, ToSocketAddrs},
+    fmt,
+    net::{TcpListener, ToSocketAddrs},
 };
 
-use crate::protocol::{read_till_rn, ParseError, ProtocolCommand, ProtocolError, OK};
+use crate::{
+    protocol::{ParseError, ProtocolCommand, OK},
+    state::StateError,
+};
 
 use super::tcp_device::TcpDevice;
 
-#[derive(Clone, Debug)]
+pub const COMMANDS: &[&str] = &[
+    "switch", "status", "on", "off", "power", "info", "help", "watch",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SocketCommand {
     Switch,
     Status,
+    // Unlike `Switch`, repeating these does not change the state.
+    On,
+    Off,
+    Power,
+    Info,
+    Help,
+    // Answered with `ok`, then the connection gets a notification for each change of state.
+    Watch,
 }
 
 impl ProtocolCommand for SocketCommand {
@@ -19,6 +34,12 @@ impl ProtocolCommand for SocketCommand {
         match s.trim() {
             "switch" => Ok(SocketCommand::Switch),
             "status" => Ok(SocketCommand::Status),
+            "on" => Ok(SocketCommand::On),
+            "off" => Ok(SocketCommand::Off),
+            "power" => Ok(SocketCommand::Power),
+            "info" => Ok(SocketCommand::Info),
+            "help" => Ok(SocketCommand::Help),
+            "watch" => Ok(SocketCommand::Watch),
             other => Err(ParseError::UnknownCommand(other.to_owned())),
         }
     }
@@ -27,8 +48,120 @@ impl ProtocolCommand for SocketCommand {
         match self {
             SocketCommand::Switch => "switch\r\n".to_owned(),
             SocketCommand::Status => "status\r\n".to_owned(),
+            SocketCommand::On => "on\r\n".to_owned(),
+            SocketCommand::Off => "off\r\n".to_owned(),
+            SocketCommand::Power => "power\r\n".to_owned(),
+            SocketCommand::Info => "info\r\n".to_owned(),
+            SocketCommand::Help => "help\r\n".to_owned(),
+            SocketCommand::Watch => "watch\r\n".to_owned(),
         }
     }
+
+    fn is_idempotent(&self) -> bool {
+        !matches!(self, SocketCommand::Switch)
+    }
+
+    fn is_watch(&self) -> bool {
+        matches!(self, SocketCommand::Watch)
+    }
+}
+
+impl SocketCommand {
+    // Responses are plain lines, their meaning depends on the command sent.
+    pub fn parse_response(&self, response: &str) -> Result<SocketResponse, ParseError> {
+        let line = response.trim_end_matches("\r\n");
+        let unexpected = || ParseError::UnexpectedResponse(line.to_owned());
+        match self {
+            SocketCommand::Switch
+            | SocketCommand::On
+            | SocketCommand::Off
+            | SocketCommand::Watch => match line {
+                "ok" => Ok(SocketResponse::Ok),
+                _ => Err(unexpected()),
+            },
+            SocketCommand::Status => match line {
+                "on" => Ok(SocketResponse::Status(true)),
+                "off" => Ok(SocketResponse::Status(false)),
+                _ => Err(unexpected()),
+            },
+            SocketCommand::Power => line
+                .parse()
+                .map(SocketResponse::Power)
+                .map_err(|_| unexpected()),
+            SocketCommand::Info => {
+                let (name, description) = line.split_once(": ").ok_or_else(unexpected)?;
+                Ok(SocketResponse::Info {
+                    name: name.to_owned(),
+                    description: description.to_owned(),
+                })
+            }
+            SocketCommand::Help => Ok(SocketResponse::Help(
+                line.split(", ").map(str::to_owned).collect(),
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SocketResponse {
+    Ok,
+    Status(bool),
+    // Power consumption, W.
+    Power(u32),
+    Info { name: String, description: String },
+    Help(Vec<String>),
+}
+
+// The response line as sent by the server.
+impl fmt::Display for SocketResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SocketResponse::Ok => write!(f, "{}", OK),
+            SocketResponse::Status(true) => write!(f, "on\r\n"),
+            SocketResponse::Status(false) => write!(f, "off\r\n"),
+            SocketResponse::Power(power) => write!(f, "{}\r\n", power),
+            SocketResponse::Info { name, description } => {
+                write!(f, "{}: {}\r\n", name, description)
+            }
+            SocketResponse::Help(commands) => write!(f, "{}\r\n", commands.join(", ")),
+        }
+    }
+}
+
+// What watching clients are notified of.
+pub fn state(socket: &SmartSocket) -> String {
+    let state = if socket.is_on() { "on" } else { "off" };
+    state.to_owned()
+}
+
+// Applies the command to the socket and returns the response line.
+pub fn execute(socket: &mut SmartSocket, command: SocketCommand) -> String {
+    let response = match command {
+        SocketCommand::Switch => {
+            socket.switch();
+            SocketResponse::Ok
+        }
+        SocketCommand::Status => SocketResponse::Status(socket.is_on()),
+        SocketCommand::On => {
+            socket.turn_on();
+            SocketResponse::Ok
+        }
+        SocketCommand::Off => {
+            socket.turn_off();
+            SocketResponse::Ok
+        }
+        SocketCommand::Power => SocketResponse::Power(socket.power_consumption()),
+        SocketCommand::Info => SocketResponse::Info {
+            name: socket.get_name().to_owned(),
+            description: socket.get_description().to_owned(),
+        },
+        SocketCommand::Help => {
+            SocketResponse::Help(COMMANDS.iter().map(|&name| name.to_owned()).collect())
+        }
+        // Subscribing is up to the server, the device has nothing to do.
+        SocketCommand::Watch => SocketResponse::Ok,
+    };
+    response.to_string()
 }
 
 #[derive(Debug)]
@@ -37,7 +170,17 @@ pub struct SocketServer {
     device: SmartSocket,
 }
 
+impl SocketServer {
+    pub fn get_device(&self) -> &SmartSocket {
+        &self.device
+    }
+}
+
 impl TcpDevice<SmartSocket> for SocketServer {
+    type Command = SocketCommand;
+    const KIND: &'static str = "socket";
+    const COMMANDS: &'static [&'static str] = COMMANDS;
+
     fn bind<Addrs: ToSocketAddrs>(
         device: SmartSocket,
         addr: Addrs,
@@ -50,28 +193,78 @@ impl TcpDevice<SmartSocket> for SocketServer {
         self.listener.try_clone().expect("Failed to clone listener")
     }
 
-    fn handle(&mut self, stream: &mut TcpStream) -> Result<(), ProtocolError> {
-        let command = read_till_rn(stream).map_err(|_| ProtocolError::InvalidResponse)?;
-        println!("Received command: {}", command);
+    fn execute(&mut self, command: SocketCommand) -> String {
+        execute(&mut self.device, command)
+    }
 
-        let command =
-            SocketCommand::from_str(&command).map_err(|_| ProtocolError::InvalidCommand)?;
+    fn get_state(&self) -> Option<String> {
+        Some(state(&self.device))
+    }
 
-        let result = match command {
-            SocketCommand::Switch => {
-                self.device.switch();
-                OK.to_owned()
-            }
-            SocketCommand::Status => {
-                let status = if self.device.is_on() { "on" } else { "off" };
-                format!("{}\r\n", status)
+    fn set_state(&mut self, state: &str) -> Result<(), StateError> {
+        match state {
+            "on" => self.device.turn_on(),
+            "off" => self.device.turn_off(),
+            other => return Err(StateError::Invalid(other.to_owned())),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server() -> SocketServer {
+        let socket = SmartSocket::new("Socket", "A smart socket", 1000);
+        SocketServer::bind(socket, "127.0.0.1:0").expect("Failed to bind")
+    }
+
+    fn execute(server: &mut SocketServer, command: SocketCommand) -> SocketResponse {
+        let response = server.execute(command.clone());
+        command
+            .parse_response(&response)
+            .expect("Failed to parse response")
+    }
+
+    #[test]
+    fn test_socket_commands() {
+        let mut server = server();
+        assert_eq!(execute(&mut server, SocketCommand::On), SocketResponse::Ok);
+        assert_eq!(execute(&mut server, SocketCommand::On), SocketResponse::Ok);
+        assert_eq!(
+            execute(&mut server, SocketCommand::Status),
+            SocketResponse::Status(true)
+        );
+        execute(&mut server, SocketCommand::Off);
+        assert!(!server.get_device().is_on());
+
+        assert_eq!(
+            execute(&mut server, SocketCommand::Power),
+            SocketResponse::Power(1000)
+        );
+        assert_eq!(
+            execute(&mut server, SocketCommand::Info),
+            SocketResponse::Info {
+                name: "Socket".to_owned(),
+                description: "A smart socket".to_owned(),
             }
+        );
+        let SocketResponse::Help(commands) = execute(&mut server, SocketCommand::Help) else {
+            panic!("Help expected");
         };
+        for command in &commands {
+            assert!(SocketCommand::from_str(command).is_ok());
+        }
+        assert_eq!(commands.len(), 8);
+    }
 
-        stream
-            .write_all(result.as_bytes())
-            .map_err(|_| ProtocolError::CouldNotSend)?;
-
-        Ok(())
+    #[test]
+    fn test_backward_compatible_responses() {
+        let mut server = server();
+        assert_eq!(server.execute(SocketCommand::Switch), "ok\r\n");
+        assert_eq!(server.execute(SocketCommand::Status), "on\r\n");
+        assert!(SocketCommand::Status.parse_response("maybe\r\n").is_err());
+        assert!(SocketCommand::Power.parse_response("lots\r\n").is_err());
     }
 }
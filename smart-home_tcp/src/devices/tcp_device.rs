@@ -1,42 +1,205 @@
 use std::{
     io::{Read, Write},
     net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::Arc,
 };
 
-use smart_home::devices::device::Device;
+use log::info;
+use rustls::ServerConfig;
+
 use thiserror::Error;
 
-use crate::protocol::{ProtocolError, CLIENT_HANDSHAKE, SERVER_HANDSHAKE};
+use crate::{
+    auth::KeyStore,
+    framing::FramedStream,
+    protocol::{
+        decode_command_line, read_error_response, read_till_rn, ErrorResponse, HandshakeStep,
+        ParseError, ProtocolCommand, ProtocolError, ServerHandshake, CLIENT_HANDSHAKE,
+    },
+    state::StateError,
+    tls::{self, ServerTlsStream, TlsError},
+};
 
 #[derive(Debug, Error)]
 pub enum ConnectError {
     #[error("Bad handshake")]
     BadHandshake,
 
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    #[error(transparent)]
+    Protocol(#[from] ProtocolError),
+
+    #[error(transparent)]
+    Tls(#[from] TlsError),
+
     #[error(transparent)]
     Io(#[from] std::io::Error),
 }
 
-fn try_handshake(mut stream: TcpStream) -> Result<TcpStream, ConnectError> {
-    let mut buf = [0; CLIENT_HANDSHAKE.len()];
-    stream.read_exact(&mut buf)?;
-    if buf != *CLIENT_HANDSHAKE {
-        return Err(ConnectError::BadHandshake);
+// See `ServerHandshake`. The returned stream uses the framing the client asked for.
+pub(crate) fn try_handshake<S: Read + Write>(
+    stream: S,
+    kind: &str,
+    commands: &[&str],
+    keys: Option<&KeyStore>,
+) -> Result<FramedStream<S>, ConnectError> {
+    let mut stream = FramedStream::new(stream);
+    let mut handshake = ServerHandshake::new(kind, commands, keys);
+    let mut tag = [0; CLIENT_HANDSHAKE.len()];
+    stream.read_exact(&mut tag)?;
+    let mut step = handshake.start(&tag);
+    loop {
+        step = match step {
+            HandshakeStep::Reply(reply) => {
+                stream.write_all(reply.as_bytes())?;
+                let line = read_till_rn(&mut stream).map_err(|_| ConnectError::BadHandshake)?;
+                handshake.next_line(&line)
+            }
+            HandshakeStep::Done(reply, framing) => {
+                stream.write_all(reply.as_bytes())?;
+                stream.set_framing(framing);
+                return Ok(stream);
+            }
+            HandshakeStep::Reject(reply, error) => {
+                stream.write_all(reply.as_bytes())?;
+                return Err(error);
+            }
+        };
     }
-    stream.write_all(SERVER_HANDSHAKE)?;
-    Ok(stream)
 }
 
-pub trait TcpDevice<D: Device>: Sized {
+// Lines that are not UTF-8 are answered with an error response. Overlong ones
+// are answered as well, but end the session as the rest of the line is unread.
+pub(crate) fn read_line<S: Read + Write>(
+    stream: &mut FramedStream<S>,
+) -> Result<String, ProtocolError> {
+    let line = match stream.read_message() {
+        Ok(line) => line,
+        Err(error) => {
+            if let Some(response) = read_error_response(&error) {
+                send_response(stream, &response.to_line())?;
+            }
+            return Err(error);
+        }
+    };
+    decode_command_line(line).or_else(|error| reject(stream, error))
+}
+
+// Answers with the error response and reports `ProtocolError::InvalidCommand`,
+// which does not end a session.
+pub(crate) fn reject<T, S: Write>(
+    stream: &mut FramedStream<S>,
+    error: ErrorResponse,
+) -> Result<T, ProtocolError> {
+    send_response(stream, &error.to_line())?;
+    Err(ProtocolError::InvalidCommand)
+}
+
+pub(crate) fn send_response<S: Write>(
+    stream: &mut FramedStream<S>,
+    response: &str,
+) -> Result<(), ProtocolError> {
+    stream
+        .write_message(response.as_bytes())
+        .map_err(|_| ProtocolError::CouldNotSend)
+}
+
+// Handles commands of one client until it disconnects.
+pub(crate) fn serve_session<S>(
+    stream: &mut S,
+    mut handle: impl FnMut(&mut S) -> Result<(), ProtocolError>,
+) -> Result<(), ProtocolError> {
+    loop {
+        match handle(stream) {
+            Ok(()) => {}
+            Err(ProtocolError::Closed) => return Ok(()),
+            Err(ProtocolError::InvalidCommand) => {}
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+// `D` is the model the server controls, a device or e.g. a whole house.
+pub trait TcpDevice<D>: Sized {
+    type Command: ProtocolCommand;
+    // Announced to clients in the handshake.
+    const KIND: &'static str;
+    const COMMANDS: &'static [&'static str];
+
     fn bind<Addrs>(device: D, addr: Addrs) -> Result<Self, std::io::Error>
     where
         Addrs: ToSocketAddrs;
-    fn handle(&mut self, stream: &mut TcpStream) -> Result<(), ProtocolError>;
     fn get_listener(&self) -> TcpListener;
+    // Applies the command to the device and returns the response line.
+    fn execute(&mut self, command: Self::Command) -> String;
 
-    fn accept(&self) -> Result<TcpStream, ConnectError> {
+    // Lines that cannot be parsed are rejected with an error response.
+    fn parse_command(&self, line: &str) -> Result<Self::Command, ParseError> {
+        Self::Command::from_str(line)
+    }
+
+    // Pushed to watching clients whenever it changes, None if the device cannot be watched.
+    fn get_state(&self) -> Option<String> {
+        None
+    }
+
+    // Restores a state `get_state` returned, e.g. after a restart.
+    fn set_state(&mut self, state: &str) -> Result<(), StateError> {
+        Err(StateError::Invalid(state.to_owned()))
+    }
+
+    // Watching needs other connections to be served at the same time, see `ConcurrentServer`.
+    fn handle<S: Read + Write>(
+        &mut self,
+        stream: &mut FramedStream<S>,
+    ) -> Result<(), ProtocolError> {
+        let line = read_line(stream)?;
+        let command = match self.parse_command(&line) {
+            Ok(command) => command,
+            Err(error) => return reject(stream, error.into()),
+        };
+        if command.is_watch() {
+            return reject(stream, ErrorResponse::unsupported(&command.to_string()));
+        }
+        let response = self.execute(command);
+        send_response(stream, &response)
+    }
+
+    fn accept(&self) -> Result<FramedStream<TcpStream>, ConnectError> {
         let (stream, _) = self.get_listener().accept()?;
-        println!("Accepted connection from: {}", stream.peer_addr()?);
-        try_handshake(stream)
+        info!("Accepted connection from: {}", stream.peer_addr()?);
+        try_handshake(stream, Self::KIND, Self::COMMANDS, None)
+    }
+
+    fn accept_authenticated(
+        &self,
+        keys: &KeyStore,
+    ) -> Result<FramedStream<TcpStream>, ConnectError> {
+        let (stream, _) = self.get_listener().accept()?;
+        info!("Accepted connection from: {}", stream.peer_addr()?);
+        try_handshake(stream, Self::KIND, Self::COMMANDS, Some(keys))
+    }
+
+    fn accept_tls(
+        &self,
+        config: &Arc<ServerConfig>,
+    ) -> Result<FramedStream<ServerTlsStream>, ConnectError> {
+        let (stream, _) = self.get_listener().accept()?;
+        info!("Accepted TLS connection from: {}", stream.peer_addr()?);
+        try_handshake(
+            tls::accept(stream, config)?,
+            Self::KIND,
+            Self::COMMANDS,
+            None,
+        )
+    }
+
+    fn serve<S: Read + Write>(
+        &mut self,
+        stream: &mut FramedStream<S>,
+    ) -> Result<(), ProtocolError> {
+        serve_session(stream, |stream| self.handle(stream))
     }
 }
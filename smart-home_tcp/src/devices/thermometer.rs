@@ -0,0 +1,374 @@
+use log::warn;
+use smart_home::devices::{device::Device, thermometer::SmartThermometer};
+use smart_home_udp_client::protocol::receive_message;
+use std::{
+    collections::VecDeque,
+    fmt,
+    net::{SocketAddr, TcpListener, ToSocketAddrs, UdpSocket},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use crate::protocol::{ErrorCode, ErrorResponse, ParseError, ProtocolCommand};
+
+use super::tcp_device::TcpDevice;
+
+pub const COMMANDS: &[&str] = &["temperature", "history", "info"];
+// Readings kept for `history`, the oldest are dropped first.
+pub const HISTORY_LEN: usize = 60;
+
+// Where the readings of a thermometer come from.
+pub trait TemperatureSource: Send {
+    // The next new reading, None if there is none yet.
+    fn read(&mut self) -> Option<f32>;
+}
+
+impl<F: FnMut() -> Option<f32> + Send> TemperatureSource for F {
+    fn read(&mut self) -> Option<f32> {
+        self()
+    }
+}
+
+// Readings sent over UDP, e.g. by `thermometer-generator-udp-server`.
+#[derive(Debug)]
+pub struct UdpTemperatureSource {
+    addr: SocketAddr,
+    readings: Receiver<f32>,
+}
+
+impl UdpTemperatureSource {
+    pub fn bind<Addrs: ToSocketAddrs>(addr: Addrs) -> Result<Self, std::io::Error> {
+        let mut socket = UdpSocket::bind(addr)?;
+        let addr = socket.local_addr()?;
+        let (sender, readings) = mpsc::channel();
+        thread::spawn(move || loop {
+            match receive_message(&mut socket) {
+                Ok(message) => {
+                    let Ok(temperature) = message.trim().parse() else {
+                        warn!("Invalid temperature: {}", message);
+                        continue;
+                    };
+                    // The source was dropped.
+                    if sender.send(temperature).is_err() {
+                        return;
+                    }
+                }
+                Err(error) => warn!("Error receiving temperature: {:?}", error),
+            }
+        });
+        Ok(Self { addr, readings })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl TemperatureSource for UdpTemperatureSource {
+    fn read(&mut self) -> Option<f32> {
+        self.readings.try_recv().ok()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ThermometerCommand {
+    Temperature,
+    History,
+    Info,
+}
+
+impl ProtocolCommand for ThermometerCommand {
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        match s.trim() {
+            "temperature" => Ok(ThermometerCommand::Temperature),
+            "history" => Ok(ThermometerCommand::History),
+            "info" => Ok(ThermometerCommand::Info),
+            other => Err(ParseError::UnknownCommand(other.to_owned())),
+        }
+    }
+
+    fn to_string(&self) -> String {
+        match self {
+            ThermometerCommand::Temperature => "temperature\r\n".to_owned(),
+            ThermometerCommand::History => "history\r\n".to_owned(),
+            ThermometerCommand::Info => "info\r\n".to_owned(),
+        }
+    }
+
+    fn is_idempotent(&self) -> bool {
+        true
+    }
+}
+
+impl ThermometerCommand {
+    pub fn parse_response(&self, response: &str) -> Result<ThermometerResponse, ParseError> {
+        let line = response.trim_end_matches("\r\n");
+        let unexpected = || ParseError::UnexpectedResponse(line.to_owned());
+        match self {
+            ThermometerCommand::Temperature => line
+                .parse()
+                .map(ThermometerResponse::Temperature)
+                .map_err(|_| unexpected()),
+            ThermometerCommand::History => line
+                .split(',')
+                .filter(|value| !value.is_empty())
+                .map(|value| value.parse().map_err(|_| unexpected()))
+                .collect::<Result<_, _>>()
+                .map(ThermometerResponse::History),
+            ThermometerCommand::Info => {
+                let (name, description) = line.split_once(": ").ok_or_else(unexpected)?;
+                Ok(ThermometerResponse::Info {
+                    name: name.to_owned(),
+                    description: description.to_owned(),
+                })
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ThermometerResponse {
+    // °C
+    Temperature(f32),
+    // Oldest first.
+    History(Vec<f32>),
+    Info { name: String, description: String },
+}
+
+// The response line as sent by the server.
+impl fmt::Display for ThermometerResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThermometerResponse::Temperature(temperature) => write!(f, "{}\r\n", temperature),
+            ThermometerResponse::History(readings) => {
+                let readings = readings
+                    .iter()
+                    .map(|reading| reading.to_string())
+                    .collect::<Vec<_>>();
+                write!(f, "{}\r\n", readings.join(","))
+            }
+            ThermometerResponse::Info { name, description } => {
+                write!(f, "{}: {}\r\n", name, description)
+            }
+        }
+    }
+}
+
+pub struct ThermometerTcpServer {
+    listener: TcpListener,
+    device: SmartThermometer,
+    source: Option<Box<dyn TemperatureSource>>,
+    history: VecDeque<f32>,
+}
+
+impl ThermometerTcpServer {
+    pub fn with_source(mut self, source: impl TemperatureSource + 'static) -> Self {
+        self.source = Some(Box::new(source));
+        self
+    }
+
+    pub fn get_device(&self) -> &SmartThermometer {
+        &self.device
+    }
+
+    pub fn get_history(&self) -> impl Iterator<Item = f32> + '_ {
+        self.history.iter().copied()
+    }
+
+    // Takes the readings that arrived since the last command.
+    fn poll(&mut self) {
+        let Some(source) = self.source.as_mut() else {
+            return;
+        };
+        while let Some(temperature) = source.read() {
+            self.device.set_temperature(temperature);
+            if self.history.len() == HISTORY_LEN {
+                self.history.pop_front();
+            }
+            self.history.push_back(temperature);
+        }
+    }
+}
+
+impl fmt::Debug for ThermometerTcpServer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThermometerTcpServer")
+            .field("listener", &self.listener)
+            .field("device", &self.device)
+            .field("history", &self.history)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TcpDevice<SmartThermometer> for ThermometerTcpServer {
+    type Command = ThermometerCommand;
+    const KIND: &'static str = "thermometer";
+    const COMMANDS: &'static [&'static str] = COMMANDS;
+
+    fn bind<Addrs: ToSocketAddrs>(
+        device: SmartThermometer,
+        addr: Addrs,
+    ) -> Result<ThermometerTcpServer, std::io::Error> {
+        let listener = TcpListener::bind(addr)?;
+        Ok(ThermometerTcpServer {
+            listener,
+            device,
+            source: None,
+            history: VecDeque::with_capacity(HISTORY_LEN),
+        })
+    }
+
+    fn get_listener(&self) -> TcpListener {
+        self.listener.try_clone().expect("Failed to clone listener")
+    }
+
+    fn execute(&mut self, command: ThermometerCommand) -> String {
+        self.poll();
+        let response = match command {
+            // The device only has its initial temperature until the first reading arrives.
+            ThermometerCommand::Temperature if self.history.is_empty() => {
+                return ErrorResponse::new(ErrorCode::DeviceFault, "No reading yet").to_line();
+            }
+            ThermometerCommand::Temperature => {
+                ThermometerResponse::Temperature(self.device.get_temperature())
+            }
+            ThermometerCommand::History => {
+                ThermometerResponse::History(self.history.iter().copied().collect())
+            }
+            ThermometerCommand::Info => ThermometerResponse::Info {
+                name: self.device.get_name().to_owned(),
+                description: self.device.get_description().to_owned(),
+            },
+        };
+        response.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::UdpSocket, time::Duration};
+
+    use smart_home_udp_client::protocol::send_message;
+
+    use super::*;
+    use crate::{client::TcpSession, server::ConcurrentServer};
+
+    fn execute(
+        server: &mut ThermometerTcpServer,
+        command: ThermometerCommand,
+    ) -> ThermometerResponse {
+        let response = server.execute(command.clone());
+        command
+            .parse_response(&response)
+            .expect("Failed to parse response")
+    }
+
+    #[test]
+    fn test_thermometer_commands() {
+        let mut readings = vec![21.5, 20.5, 20.0];
+        let mut server = ThermometerTcpServer::bind(
+            SmartThermometer::new("Thermometer", "Living room"),
+            "127.0.0.1:0",
+        )
+        .expect("Failed to bind")
+        .with_source(move || readings.pop());
+
+        assert_eq!(
+            execute(&mut server, ThermometerCommand::Temperature),
+            ThermometerResponse::Temperature(21.5)
+        );
+        assert_eq!(
+            execute(&mut server, ThermometerCommand::History),
+            ThermometerResponse::History(vec![20.0, 20.5, 21.5])
+        );
+        assert_eq!(
+            execute(&mut server, ThermometerCommand::Info),
+            ThermometerResponse::Info {
+                name: "Thermometer".to_owned(),
+                description: "Living room".to_owned(),
+            }
+        );
+        assert!(ThermometerCommand::from_str("humidity\r\n").is_err());
+    }
+
+    #[test]
+    fn test_no_reading_yet() {
+        let (readings, receiver) = mpsc::channel();
+        let mut server = ThermometerTcpServer::bind(
+            SmartThermometer::new("Thermometer", "Living room"),
+            "127.0.0.1:0",
+        )
+        .expect("Failed to bind")
+        .with_source(move || receiver.try_recv().ok());
+
+        let response = server.execute(ThermometerCommand::Temperature);
+        assert_eq!(
+            ErrorResponse::from_line(&response),
+            Some(ErrorResponse::new(ErrorCode::DeviceFault, "No reading yet"))
+        );
+        assert_eq!(
+            execute(&mut server, ThermometerCommand::History),
+            ThermometerResponse::History(Vec::new())
+        );
+
+        readings.send(19.5).unwrap();
+        assert_eq!(
+            execute(&mut server, ThermometerCommand::Temperature),
+            ThermometerResponse::Temperature(19.5)
+        );
+    }
+
+    #[test]
+    fn test_history_is_bounded() {
+        let mut next = 0.0;
+        let mut server = ThermometerTcpServer::bind(
+            SmartThermometer::new("Thermometer", "Living room"),
+            "127.0.0.1:0",
+        )
+        .expect("Failed to bind")
+        .with_source(move || {
+            next += 1.0;
+            (next <= 100.0).then_some(next)
+        });
+        server.execute(ThermometerCommand::Temperature);
+
+        let history = server.get_history().collect::<Vec<_>>();
+        assert_eq!(history.len(), HISTORY_LEN);
+        assert_eq!(history.first(), Some(&41.0));
+        assert_eq!(history.last(), Some(&100.0));
+        assert_eq!(
+            ThermometerCommand::History.parse_response("\r\n").unwrap(),
+            ThermometerResponse::History(Vec::new())
+        );
+    }
+
+    #[test]
+    fn test_udp_source_over_tcp() {
+        let source = UdpTemperatureSource::bind("127.0.0.1:0").expect("Failed to bind UDP");
+        let mut sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+        send_message(&mut sender, source.local_addr(), "22.5").unwrap();
+        thread::sleep(Duration::from_millis(100));
+
+        let server = ConcurrentServer::new(
+            ThermometerTcpServer::bind(
+                SmartThermometer::new("Thermometer", "Living room"),
+                "127.0.0.1:0",
+            )
+            .expect("Failed to bind")
+            .with_source(source),
+            1,
+        );
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let mut session = TcpSession::connect(addr).expect("Failed to connect");
+        assert_eq!(session.get_server_info().kind, "thermometer");
+        let response = session.send(ThermometerCommand::Temperature).unwrap();
+        assert_eq!(
+            ThermometerCommand::Temperature
+                .parse_response(&response)
+                .unwrap(),
+            ThermometerResponse::Temperature(22.5)
+        );
+    }
+}
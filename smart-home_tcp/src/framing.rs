@@ -0,0 +1,227 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::protocol::{io_error, Framing, ProtocolError, MAX_LINE_LEN};
+
+pub const LENGTH_PREFIX_LEN: usize = 4;
+
+// Reads a line including the trailing `\r\n` from a buffered reader, failing
+// once it gets longer than `max_len` instead of growing without limit.
+pub fn read_line_buffered<R: BufRead>(
+    reader: &mut R,
+    max_len: usize,
+) -> Result<Vec<u8>, ProtocolError> {
+    let mut line = Vec::with_capacity(64);
+    loop {
+        let available = match reader.fill_buf() {
+            Ok(available) if !available.is_empty() => available,
+            // The peer has closed the connection, possibly in the middle of the line.
+            Ok(_) if line.is_empty() => return Err(ProtocolError::Closed),
+            Ok(_) => return Err(ProtocolError::InvalidResponse),
+            Err(error) if line.is_empty() => return Err(closed_or(error)),
+            Err(error) => return Err(io_error(error, ProtocolError::InvalidResponse)),
+        };
+
+        // `\r` and `\n` may arrive in different reads, so the line is checked as a whole.
+        let (used, found) = match available.iter().position(|&byte| byte == b'\n') {
+            Some(end) => (end + 1, true),
+            None => (available.len(), false),
+        };
+        line.extend_from_slice(&available[..used]);
+        reader.consume(used);
+
+        if line.len() > max_len {
+            return Err(ProtocolError::MessageTooLong(max_len));
+        }
+        if found && line.ends_with(b"\r\n") {
+            return Ok(line);
+        }
+    }
+}
+
+// TLS reports a peer that left without `close_notify` as an unexpected EOF.
+fn closed_or(error: io::Error) -> ProtocolError {
+    match error.kind() {
+        io::ErrorKind::UnexpectedEof => ProtocolError::Closed,
+        _ => io_error(error, ProtocolError::InvalidResponse),
+    }
+}
+
+// A stream read through a buffer, so a message costs a few syscalls instead of
+// one per byte. Messages are lines until a handshake agrees on another framing.
+#[derive(Debug)]
+pub struct FramedStream<S> {
+    reader: BufReader<S>,
+    framing: Framing,
+    max_len: usize,
+}
+
+impl<S: Read> FramedStream<S> {
+    pub fn new(stream: S) -> Self {
+        Self {
+            reader: BufReader::new(stream),
+            framing: Framing::Lines,
+            max_len: MAX_LINE_LEN,
+        }
+    }
+
+    pub fn with_max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    pub fn read_message(&mut self) -> Result<Vec<u8>, ProtocolError> {
+        match self.framing {
+            Framing::Lines => read_line_buffered(&mut self.reader, self.max_len),
+            Framing::LengthPrefixed => {
+                if self.reader.fill_buf().map_err(closed_or)?.is_empty() {
+                    return Err(ProtocolError::Closed);
+                }
+                let mut prefix = [0; LENGTH_PREFIX_LEN];
+                self.read_exact_message(&mut prefix)?;
+                let len = u32::from_be_bytes(prefix) as usize;
+                if len > self.max_len {
+                    return Err(ProtocolError::MessageTooLong(self.max_len));
+                }
+                let mut message = vec![0; len];
+                self.read_exact_message(&mut message)?;
+                Ok(message)
+            }
+        }
+    }
+
+    fn read_exact_message(&mut self, buffer: &mut [u8]) -> Result<(), ProtocolError> {
+        self.reader
+            .read_exact(buffer)
+            .map_err(|error| io_error(error, ProtocolError::InvalidResponse))
+    }
+}
+
+impl<S> FramedStream<S> {
+    pub fn get_framing(&self) -> Framing {
+        self.framing
+    }
+
+    pub fn set_framing(&mut self, framing: Framing) {
+        self.framing = framing;
+    }
+
+    pub fn get_ref(&self) -> &S {
+        self.reader.get_ref()
+    }
+
+    pub fn get_mut(&mut self) -> &mut S {
+        self.reader.get_mut()
+    }
+}
+
+impl<S: Write> FramedStream<S> {
+    // The message as sent with line framing, including the trailing `\r\n`.
+    pub fn write_message(&mut self, message: &[u8]) -> Result<(), ProtocolError> {
+        let result = match self.framing {
+            Framing::Lines => self.get_mut().write_all(message),
+            Framing::LengthPrefixed => {
+                let mut frame = Vec::with_capacity(LENGTH_PREFIX_LEN + message.len());
+                frame.extend_from_slice(&(message.len() as u32).to_be_bytes());
+                frame.extend_from_slice(message);
+                self.get_mut().write_all(&frame)
+            }
+        };
+        result.map_err(|error| io_error(error, ProtocolError::CouldNotSend))
+    }
+}
+
+// Raw reads and writes, e.g. for the handshake, go through the same buffer.
+impl<S: Read> Read for FramedStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf)
+    }
+}
+
+impl<S: Read> BufRead for FramedStream<S> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.reader.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.reader.consume(amt)
+    }
+}
+
+impl<S: Write> Write for FramedStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.get_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.get_mut().flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    // Hands out at most a few bytes per read, like a slow network.
+    struct Trickle<R>(R);
+
+    impl<R: Read> Read for Trickle<R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = buf.len().min(3);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    #[test]
+    fn test_read_lines() {
+        let mut stream = FramedStream::new(Trickle(Cursor::new(
+            b"status\r\nbright\rness 40\r\non\r\n".to_vec(),
+        )));
+        assert_eq!(stream.read_message().unwrap(), b"status\r\n");
+        assert_eq!(stream.read_message().unwrap(), b"bright\rness 40\r\n");
+        assert_eq!(stream.read_message().unwrap(), b"on\r\n");
+        assert!(matches!(stream.read_message(), Err(ProtocolError::Closed)));
+
+        let mut stream = FramedStream::new(Cursor::new(b"stat".to_vec()));
+        assert!(matches!(
+            stream.read_message(),
+            Err(ProtocolError::InvalidResponse)
+        ));
+    }
+
+    #[test]
+    fn test_line_too_long() {
+        let mut stream = FramedStream::new(Cursor::new(vec![b'a'; 100])).with_max_len(16);
+        assert!(matches!(
+            stream.read_message(),
+            Err(ProtocolError::MessageTooLong(16))
+        ));
+
+        let mut stream =
+            FramedStream::new(Cursor::new(b"0123456789\r\n".to_vec())).with_max_len(12);
+        assert_eq!(stream.read_message().unwrap(), b"0123456789\r\n");
+    }
+
+    #[test]
+    fn test_length_prefixed() {
+        let mut writer = FramedStream::new(Cursor::new(Vec::new()));
+        writer.set_framing(Framing::LengthPrefixed);
+        writer.write_message(b"status\r\n").unwrap();
+        writer.write_message(b"on\r\n").unwrap();
+        let bytes = writer.get_ref().get_ref().clone();
+        assert_eq!(&bytes[..LENGTH_PREFIX_LEN], &[0, 0, 0, 8]);
+
+        let mut reader = FramedStream::new(Trickle(Cursor::new(bytes)));
+        reader.set_framing(Framing::LengthPrefixed);
+        assert_eq!(reader.read_message().unwrap(), b"status\r\n");
+        assert_eq!(reader.read_message().unwrap(), b"on\r\n");
+
+        let mut reader = FramedStream::new(Cursor::new(vec![0, 1, 0, 0])).with_max_len(1024);
+        reader.set_framing(Framing::LengthPrefixed);
+        assert!(matches!(
+            reader.read_message(),
+            Err(ProtocolError::MessageTooLong(1024))
+        ));
+    }
+}
@@ -0,0 +1,521 @@
+use std::{
+    net::{TcpListener, ToSocketAddrs},
+    time::{Duration, Instant},
+};
+
+use smart_home::{
+    devices::device::Device, errors::SmartHouseError, house::House, room::RoomDevice,
+};
+
+use crate::{
+    devices::{light, lock, socket, tcp_device::TcpDevice},
+    protocol::{ErrorCode, ErrorResponse, ParseError, ProtocolCommand, OK},
+};
+
+pub const COMMANDS: &[&str] = &["scenes", "scene", "meter", "rooms", "devices"];
+// Lists the commands of an addressed device, e.g. `kitchen/lamp help`.
+pub const HELP: &str = "help";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum HouseCommand {
+    Scenes,
+    Scene(String),
+    // Reading of the named smart meter, or of the first one if no name is given.
+    Meter(Option<String>),
+    Rooms,
+    // Devices of the room.
+    Devices(String),
+    // `<room>/<device> <command>`, the command as the device itself takes it.
+    Device {
+        room: String,
+        device: String,
+        command: String,
+    },
+}
+
+impl ProtocolCommand for HouseCommand {
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        let line = s.trim();
+        let malformed = || ParseError::MalformedCommand(line.to_owned());
+        let (word, rest) = line
+            .split_once(' ')
+            .map_or((line, ""), |(word, rest)| (word, rest.trim()));
+
+        if let Some((room, device)) = word.split_once('/') {
+            if room.is_empty() || device.is_empty() || rest.is_empty() {
+                return Err(malformed());
+            }
+            return Ok(HouseCommand::Device {
+                room: room.to_owned(),
+                device: device.to_owned(),
+                command: rest.to_owned(),
+            });
+        }
+
+        match (word, rest) {
+            ("scenes", "") => Ok(HouseCommand::Scenes),
+            ("scene", name) if !name.is_empty() => Ok(HouseCommand::Scene(name.to_owned())),
+            ("meter", "") => Ok(HouseCommand::Meter(None)),
+            ("meter", name) => Ok(HouseCommand::Meter(Some(name.to_owned()))),
+            ("rooms", "") => Ok(HouseCommand::Rooms),
+            ("devices", room) if !room.is_empty() => Ok(HouseCommand::Devices(room.to_owned())),
+            ("scenes" | "scene" | "rooms" | "devices", _) => Err(malformed()),
+            _ => Err(ParseError::UnknownCommand(line.to_owned())),
+        }
+    }
+
+    fn to_string(&self) -> String {
+        match self {
+            HouseCommand::Scenes => "scenes\r\n".to_owned(),
+            HouseCommand::Scene(name) => format!("scene {}\r\n", name),
+            HouseCommand::Meter(None) => "meter\r\n".to_owned(),
+            HouseCommand::Meter(Some(name)) => format!("meter {}\r\n", name),
+            HouseCommand::Rooms => "rooms\r\n".to_owned(),
+            HouseCommand::Devices(room) => format!("devices {}\r\n", room),
+            HouseCommand::Device {
+                room,
+                device,
+                command,
+            } => format!("{}/{} {}\r\n", room, device, command),
+        }
+    }
+
+    // The kind of an addressed device is unknown to the client, so its commands are not repeated.
+    fn is_idempotent(&self) -> bool {
+        !matches!(self, HouseCommand::Scene(_) | HouseCommand::Device { .. })
+    }
+}
+
+pub fn device_kind(device: &RoomDevice) -> &'static str {
+    match device {
+        RoomDevice::SmartSocket(_) => "socket",
+        RoomDevice::Thermometer(_) => "thermometer",
+        RoomDevice::SmartLight(_) => "light",
+        RoomDevice::BinarySensor(_) => "sensor",
+        RoomDevice::SmartLock(_) => "lock",
+        RoomDevice::EnvironmentSensor(_) => "environment",
+        RoomDevice::SmartMeter(_) => "meter",
+    }
+}
+
+// Commands the gateway forwards to the device, none for devices it cannot control.
+pub fn device_commands(device: &RoomDevice) -> &'static [&'static str] {
+    match device {
+        RoomDevice::SmartSocket(_) => socket::COMMANDS,
+        RoomDevice::SmartLight(_) => light::COMMANDS,
+        RoomDevice::SmartLock(_) => lock::COMMANDS,
+        _ => &[],
+    }
+}
+
+fn device_fault(error: SmartHouseError) -> String {
+    ErrorResponse::new(ErrorCode::DeviceFault, error.to_string()).to_line()
+}
+
+fn run<C: ProtocolCommand>(command: &str, execute: impl FnOnce(C) -> String) -> String {
+    match C::from_str(command) {
+        Ok(command) if command.is_watch() => {
+            ErrorResponse::unsupported(&command.to_string()).to_line()
+        }
+        Ok(command) => execute(command),
+        Err(error) => ErrorResponse::from(error).to_line(),
+    }
+}
+
+// Serves a whole house on one port: house commands, and commands of single
+// devices addressed as `<room>/<device> <command>`. Run it through `ConcurrentServer`
+// for concurrent sessions, TLS, client keys and graceful shutdown.
+#[derive(Debug)]
+pub struct HouseServer {
+    listener: TcpListener,
+    house: House,
+    last_update: Instant,
+    default_device: Option<(String, String)>,
+}
+
+impl HouseServer {
+    // Commands without an address that are not house commands go to this device,
+    // so clients of a single device server can talk to the gateway unchanged.
+    pub fn with_default_device(mut self, room: &str, device: &str) -> Self {
+        self.default_device = Some((room.to_owned(), device.to_owned()));
+        self
+    }
+
+    pub fn get_house(&self) -> &House {
+        &self.house
+    }
+
+    // Meters and light transitions run in real time between commands.
+    fn update(&mut self) {
+        let now = Instant::now();
+        let dt = now - self.last_update;
+        self.last_update = now;
+
+        let rooms = self
+            .house
+            .get_rooms()
+            .map(|room| room.get_name().to_owned())
+            .collect::<Vec<_>>();
+        for name in rooms {
+            let Some(room) = self.house.get_room_mut(&name) else {
+                continue;
+            };
+            for device in room.get_devices_mut() {
+                if let RoomDevice::SmartLight(light) = device {
+                    light.advance(dt);
+                }
+            }
+        }
+        self.house.update_meters(dt);
+    }
+
+    fn apply(&mut self, command: HouseCommand) -> String {
+        match command {
+            HouseCommand::Scenes => {
+                let scenes: Vec<&str> = self.house.get_scenes().map(|s| s.get_name()).collect();
+                format!("{}\r\n", scenes.join(","))
+            }
+            HouseCommand::Scene(name) => match self.house.apply_scene(&name) {
+                Ok(()) => OK.to_owned(),
+                Err(err) => device_fault(err),
+            },
+            HouseCommand::Meter(name) => {
+                let meter = self
+                    .house
+                    .get_meters()
+                    .find(|meter| name.as_deref().is_none_or(|name| meter.get_name() == name));
+                match meter {
+                    // `<power, W> <energy, Wh>`
+                    Some(meter) => format!("{} {:.3}\r\n", meter.power(), meter.energy()),
+                    None => device_fault(SmartHouseError::DeviceNotFoundError(
+                        name.unwrap_or_else(|| "smart meter".to_owned()),
+                    )),
+                }
+            }
+            HouseCommand::Rooms => {
+                let mut rooms: Vec<&str> = self.house.get_rooms().map(|r| r.get_name()).collect();
+                rooms.sort();
+                format!("{}\r\n", rooms.join(","))
+            }
+            // `<device>:<kind>,...`
+            HouseCommand::Devices(name) => match self.house.get_room(&name) {
+                Some(room) => {
+                    let mut devices = room
+                        .get_devices()
+                        .map(|device| format!("{}:{}", device.get_name(), device_kind(device)))
+                        .collect::<Vec<_>>();
+                    devices.sort();
+                    format!("{}\r\n", devices.join(","))
+                }
+                None => device_fault(SmartHouseError::RoomNotFoundError(name)),
+            },
+            HouseCommand::Device {
+                room,
+                device,
+                command,
+            } => self.execute_device(&room, &device, &command),
+        }
+    }
+
+    fn execute_device(&mut self, room: &str, name: &str, command: &str) -> String {
+        let Some(device) = self
+            .house
+            .get_room_mut(room)
+            .and_then(|room| room.get_device_mut(name))
+        else {
+            return device_fault(SmartHouseError::DeviceNotFoundError(format!(
+                "{}/{}",
+                room, name
+            )));
+        };
+        if command == HELP {
+            return format!("{}\r\n", device_commands(device).join(", "));
+        }
+
+        match device {
+            RoomDevice::SmartSocket(device) => run(command, |c| socket::execute(device, c)),
+            RoomDevice::SmartLight(device) => run(command, |c| light::execute(device, c)),
+            RoomDevice::SmartLock(device) => run(command, |c| lock::execute(device, c)),
+            device => ErrorResponse::new(
+                ErrorCode::UnknownCommand,
+                SmartHouseError::UnsupportedActionError(format!("{}/{}", room, device.get_name()))
+                    .to_string(),
+            )
+            .to_line(),
+        }
+    }
+}
+
+impl TcpDevice<House> for HouseServer {
+    type Command = HouseCommand;
+    const KIND: &'static str = "house";
+    const COMMANDS: &'static [&'static str] = COMMANDS;
+
+    fn bind<Addrs: ToSocketAddrs>(house: House, addr: Addrs) -> Result<Self, std::io::Error> {
+        let listener = TcpListener::bind(addr)?;
+        Ok(Self {
+            listener,
+            house,
+            last_update: Instant::now(),
+            default_device: None,
+        })
+    }
+
+    fn get_listener(&self) -> TcpListener {
+        self.listener.try_clone().expect("Failed to clone listener")
+    }
+
+    fn parse_command(&self, line: &str) -> Result<HouseCommand, ParseError> {
+        match (HouseCommand::from_str(line), &self.default_device) {
+            (Err(ParseError::UnknownCommand(_)), Some((room, device))) => {
+                Ok(HouseCommand::Device {
+                    room: room.clone(),
+                    device: device.clone(),
+                    command: line.trim().to_owned(),
+                })
+            }
+            (result, _) => result,
+        }
+    }
+
+    fn execute(&mut self, command: HouseCommand) -> String {
+        self.update();
+        let result = self.apply(command);
+        self.house.update_meters(Duration::ZERO);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpStream, thread};
+
+    use smart_home::{
+        devices::{
+            light::SmartLight,
+            lock::{PinCode, SmartLock},
+            meter::SmartMeter,
+            socket::SmartSocket,
+        },
+        scene::{Scene, SceneAction},
+    };
+
+    use super::*;
+    use crate::{
+        client::{receive_response, send_command, TcpSession},
+        devices::socket::SocketCommand,
+        protocol::ProtocolError,
+        server::ConcurrentServer,
+    };
+
+    #[test]
+    fn test_house_command_parse() {
+        assert_eq!(
+            HouseCommand::from_str("scene Night\r\n").unwrap(),
+            HouseCommand::Scene("Night".to_owned())
+        );
+        assert_eq!(
+            HouseCommand::from_str("scenes\r\n").unwrap(),
+            HouseCommand::Scenes
+        );
+        assert!(matches!(
+            HouseCommand::from_str("scene \r\n"),
+            Err(ParseError::MalformedCommand(_))
+        ));
+        assert!(HouseCommand::from_str("dance\r\n").is_err());
+        assert_eq!(
+            HouseCommand::from_str("meter\r\n").unwrap(),
+            HouseCommand::Meter(None)
+        );
+        assert_eq!(
+            HouseCommand::from_str("meter Main meter\r\n").unwrap(),
+            HouseCommand::Meter(Some("Main meter".to_owned()))
+        );
+        assert_eq!(
+            HouseCommand::from_str("devices Kitchen\r\n").unwrap(),
+            HouseCommand::Devices("Kitchen".to_owned())
+        );
+
+        let command = HouseCommand::Device {
+            room: "Kitchen".to_owned(),
+            device: "Lamp".to_owned(),
+            command: "brightness 40 500".to_owned(),
+        };
+        assert_eq!(
+            HouseCommand::from_str("Kitchen/Lamp brightness 40 500\r\n").unwrap(),
+            command
+        );
+        assert_eq!(
+            HouseCommand::from_str(&command.to_string()).unwrap(),
+            command
+        );
+        assert!(!command.is_idempotent());
+        assert!(matches!(
+            HouseCommand::from_str("Kitchen/Lamp\r\n"),
+            Err(ParseError::MalformedCommand(_))
+        ));
+        assert!(matches!(
+            HouseCommand::from_str("/Lamp on\r\n"),
+            Err(ParseError::MalformedCommand(_))
+        ));
+    }
+
+    #[test]
+    fn test_apply_scene_over_tcp() {
+        let mut house = House::new("House");
+        house.add_room("Kitchen").unwrap();
+        house
+            .get_room_mut("Kitchen")
+            .unwrap()
+            .add_device(SmartSocket::new("Fridge", "Fridge socket", 100).into())
+            .unwrap();
+        let mut night = Scene::new("Night");
+        night.add_action("Kitchen", "Fridge", SceneAction::TurnOn);
+        house.add_scene(night).unwrap();
+
+        let mut server = HouseServer::bind(house, "127.0.0.1:0").expect("Failed to bind");
+        let addr = server
+            .get_listener()
+            .local_addr()
+            .expect("No local address");
+
+        let server_thread = thread::spawn(move || {
+            for _ in 0..2 {
+                let mut stream = server.accept().expect("Failed to accept");
+                server.handle(&mut stream).expect("Failed to handle");
+            }
+            server
+        });
+
+        let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+        send_command(HouseCommand::Scene("Night".to_owned()), &mut stream).unwrap();
+        assert_eq!(receive_response(&mut stream).unwrap(), OK);
+
+        let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+        send_command(HouseCommand::Scene("Away".to_owned()), &mut stream).unwrap();
+        assert!(matches!(
+            receive_response(&mut stream),
+            Err(ProtocolError::ServerError(ErrorResponse {
+                code: ErrorCode::DeviceFault,
+                message,
+            })) if message == "Scene not found: Away"
+        ));
+
+        let server = server_thread.join().expect("Failed to join server thread");
+        let fridge = server
+            .get_house()
+            .get_room("Kitchen")
+            .and_then(|room| room.get_device("Fridge"));
+        assert!(matches!(fridge, Some(RoomDevice::SmartSocket(s)) if s.is_on()));
+    }
+
+    #[test]
+    fn test_meter_over_tcp() {
+        let mut house = House::new("House");
+        house.add_room("Kitchen").unwrap();
+        let kitchen = house.get_room_mut("Kitchen").unwrap();
+        kitchen
+            .add_device(SmartSocket::new("Fridge", "Fridge socket", 100).into())
+            .unwrap();
+        kitchen
+            .add_device(SmartMeter::new("Meter", "Main meter", 50).into())
+            .unwrap();
+        let mut night = Scene::new("Night");
+        night.add_action("Kitchen", "Fridge", SceneAction::TurnOn);
+        house.add_scene(night).unwrap();
+
+        let mut server = HouseServer::bind(house, "127.0.0.1:0").expect("Failed to bind");
+        let addr = server
+            .get_listener()
+            .local_addr()
+            .expect("No local address");
+        thread::spawn(move || loop {
+            let mut stream = server.accept().expect("Failed to accept");
+            server.handle(&mut stream).expect("Failed to handle");
+        });
+
+        let send = |command: HouseCommand| {
+            let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+            send_command(command, &mut stream).unwrap();
+            receive_response(&mut stream)
+        };
+
+        assert!(send(HouseCommand::Meter(None)).unwrap().starts_with("50 "));
+        assert_eq!(send(HouseCommand::Scene("Night".to_owned())).unwrap(), OK);
+        assert!(send(HouseCommand::Meter(Some("Meter".to_owned())))
+            .unwrap()
+            .starts_with("150 "));
+        assert!(matches!(
+            send(HouseCommand::Meter(Some("Boiler".to_owned()))),
+            Err(ProtocolError::ServerError(error)) if error.message == "Device not found: Boiler"
+        ));
+    }
+
+    #[test]
+    fn test_gateway() {
+        let mut house = House::new("House");
+        house.add_room("Kitchen").unwrap();
+        house.add_room("Hall").unwrap();
+        let kitchen = house.get_room_mut("Kitchen").unwrap();
+        kitchen
+            .add_device(SmartSocket::new("Kettle", "Kettle socket", 2000).into())
+            .unwrap();
+        kitchen
+            .add_device(SmartLight::new("Lamp", "Ceiling lamp").into())
+            .unwrap();
+        let hall = house.get_room_mut("Hall").unwrap();
+        let mut lock = SmartLock::new("Door", "Front door lock");
+        lock.add_pin(PinCode::new("Alice", "1234"));
+        hall.add_device(lock.into()).unwrap();
+        hall.add_device(SmartMeter::new("Meter", "Main meter", 50).into())
+            .unwrap();
+
+        let server = HouseServer::bind(house, "127.0.0.1:0")
+            .expect("Failed to bind")
+            .with_default_device("Kitchen", "Kettle");
+        let server = ConcurrentServer::new(server, 2);
+        let addr = server.local_addr().expect("No local address");
+        thread::spawn(move || server.run());
+
+        // Sessions are served at the same time.
+        let mut other = TcpSession::connect(addr).expect("Failed to connect");
+        let mut session = TcpSession::connect(addr).expect("Failed to connect");
+        assert_eq!(session.get_server_info().kind, "house");
+        assert_eq!(other.send(HouseCommand::Rooms).unwrap(), "Hall,Kitchen\r\n");
+        let mut send = |line: &str| session.send(HouseCommand::from_str(line).unwrap());
+
+        assert_eq!(send("rooms").unwrap(), "Hall,Kitchen\r\n");
+        assert_eq!(
+            send("devices Kitchen").unwrap(),
+            "Kettle:socket,Lamp:light\r\n"
+        );
+        assert_eq!(send("Kitchen/Kettle on").unwrap(), OK);
+        assert_eq!(send("Kitchen/Lamp brightness 40").unwrap(), OK);
+        assert_eq!(send("Kitchen/Lamp status").unwrap(), "off 40 2700\r\n");
+        assert_eq!(send("Hall/Door unlock 0000").unwrap(), "denied\r\n");
+        assert_eq!(send("Hall/Door unlock 1234").unwrap(), OK);
+        assert_eq!(
+            send("Hall/Door help").unwrap(),
+            "lock, unlock, status, log\r\n"
+        );
+        assert!(send("meter").unwrap().starts_with("2050 "));
+
+        assert!(matches!(
+            send("Kitchen/Toaster on"),
+            Err(ProtocolError::ServerError(error)) if error.message == "Device not found: Kitchen/Toaster"
+        ));
+        assert!(matches!(
+            send("Kitchen/Lamp dance"),
+            Err(ProtocolError::ServerError(error)) if error.code == ErrorCode::UnknownCommand
+        ));
+        assert!(matches!(
+            send("Hall/Meter status"),
+            Err(ProtocolError::ServerError(error)) if error.code == ErrorCode::UnknownCommand
+        ));
+
+        // Single device commands go to the default device.
+        assert_eq!(session.send(SocketCommand::Status).unwrap(), "on\r\n");
+        assert_eq!(session.send(SocketCommand::Off).unwrap(), OK);
+        assert_eq!(session.send(SocketCommand::Status).unwrap(), "off\r\n");
+    }
+}
@@ -1,3 +1,11 @@
+pub mod auth;
 pub mod client;
 pub mod devices;
+pub mod framing;
+pub mod house;
+pub mod pool;
 pub mod protocol;
+pub mod retry;
+pub mod server;
+pub mod state;
+pub mod tls;
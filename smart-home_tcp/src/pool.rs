@@ -0,0 +1,89 @@
+use std::{
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+// Fixed number of worker threads. Jobs beyond that wait in the queue.
+#[derive(Debug)]
+pub struct ThreadPool {
+    sender: Sender<Job>,
+    size: usize,
+}
+
+impl ThreadPool {
+    pub fn new(size: usize) -> Self {
+        assert!(size > 0, "Thread pool needs at least one worker");
+
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..size {
+            let receiver = receiver.clone();
+            thread::spawn(move || worker(receiver));
+        }
+
+        Self { sender, size }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn execute(&self, job: impl FnOnce() + Send + 'static) {
+        self.sender
+            .send(Box::new(job))
+            .expect("Thread pool workers have stopped");
+    }
+}
+
+// Runs jobs until the pool is dropped.
+fn worker(receiver: Arc<Mutex<Receiver<Job>>>) {
+    loop {
+        let job = receiver.lock().expect("Failed to lock mutex").recv();
+        match job {
+            Ok(job) => job(),
+            Err(_) => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_thread_pool() {
+        let pool = ThreadPool::new(2);
+        let (sender, receiver) = mpsc::channel();
+        let (release, wait) = mpsc::channel::<()>();
+        let wait = Arc::new(Mutex::new(wait));
+
+        // Both workers are busy until released.
+        for _ in 0..2 {
+            let wait = wait.clone();
+            let sender = sender.clone();
+            pool.execute(move || {
+                let _ = wait.lock().unwrap().recv();
+                sender.send("blocked").unwrap();
+            });
+        }
+        let queued = sender.clone();
+        pool.execute(move || queued.send("queued").unwrap());
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+
+        release.send(()).unwrap();
+        release.send(()).unwrap();
+        let mut done = (0..3)
+            .map(|_| receiver.recv_timeout(Duration::from_secs(5)).unwrap())
+            .collect::<Vec<_>>();
+        done.sort();
+        assert_eq!(done, ["blocked", "blocked", "queued"]);
+    }
+}
@@ -1,22 +1,185 @@
-use std::io::Read;
+use std::{
+    fmt,
+    io::{self, Read},
+};
 
+use log::{debug, info};
 use thiserror::Error;
 
+use crate::{
+    auth::{self, Credentials, KeyStore},
+    devices::tcp_device::ConnectError,
+};
+
 pub const OK: &str = "ok\r\n";
 pub const CLIENT_HANDSHAKE: &[u8] = b"clnt";
 pub const SERVER_HANDSHAKE: &[u8] = b"serv";
 
+// Version 1 is the bare `clnt`/`serv` exchange, servers still accept it from old clients.
+pub const LEGACY_VERSION: u16 = 1;
+pub const PROTOCOL_VERSION: u16 = 3;
+// Versioned handshakes start with this tag instead of `clnt`. The client sends
+// `smhp <min version> <max version>`, the server answers with the agreed version,
+// its device kind and commands: `smhp 3 socket switch,status`, or `smhp error <message>`.
+pub const HELLO: &str = "smhp";
+// From this version on the server appends `framing=length` to its answer if it offers
+// length-prefixed messages, and the client then picks one with `smhp framing=<name>`.
+// Servers of version 2 reject a hello with a framing token, so clients never send one.
+pub const FRAMING_VERSION: u16 = 3;
+pub const FRAMING: &str = "framing=";
+// Longer messages are rejected, so a peer that never ends a line cannot exhaust memory.
+pub const MAX_LINE_LEN: usize = 8 * 1024;
+
 pub trait ProtocolCommand {
     fn from_str(s: &str) -> Result<Self, ParseError>
     where
         Self: Sized;
     fn to_string(&self) -> String;
+
+    // Idempotent commands may be repeated after a timeout or a lost connection.
+    fn is_idempotent(&self) -> bool {
+        false
+    }
+
+    // After a watch command is answered, the server only pushes notifications.
+    fn is_watch(&self) -> bool {
+        false
+    }
 }
 
 #[derive(Debug, Error)]
 pub enum ParseError {
     #[error("Unknown command: {0}\r\n")]
     UnknownCommand(String),
+
+    #[error("Malformed command: {0}")]
+    MalformedCommand(String),
+
+    #[error("Unexpected response: {0}")]
+    UnexpectedResponse(String),
+}
+
+// Sent by servers instead of a regular response: `ERR <code> <message>`.
+pub const ERROR_PREFIX: &str = "ERR";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    UnknownCommand = 1,
+    MalformedInput = 2,
+    DeviceFault = 3,
+}
+
+impl ErrorCode {
+    pub fn from_code(code: u8) -> Option<Self> {
+        match code {
+            1 => Some(ErrorCode::UnknownCommand),
+            2 => Some(ErrorCode::MalformedInput),
+            3 => Some(ErrorCode::DeviceFault),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorCode::UnknownCommand => write!(f, "unknown command"),
+            ErrorCode::MalformedInput => write!(f, "malformed input"),
+            ErrorCode::DeviceFault => write!(f, "device fault"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorResponse {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+impl ErrorResponse {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn to_line(&self) -> String {
+        format!("{} {} {}\r\n", ERROR_PREFIX, self.code as u8, self.message)
+    }
+
+    // For known commands a server cannot handle, e.g. `watch` outside `ConcurrentServer`.
+    pub fn unsupported(command: &str) -> Self {
+        Self::new(
+            ErrorCode::UnknownCommand,
+            format!("Not supported by this server: {}", command.trim()),
+        )
+    }
+
+    // None if the line is not an error response.
+    pub fn from_line(line: &str) -> Option<Self> {
+        let rest = line
+            .trim_end()
+            .strip_prefix(ERROR_PREFIX)?
+            .strip_prefix(' ')?;
+        let (code, message) = rest.split_once(' ').unwrap_or((rest, ""));
+        Some(Self::new(
+            ErrorCode::from_code(code.parse().ok()?)?,
+            message,
+        ))
+    }
+}
+
+impl fmt::Display for ErrorResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+// Pushed to watching clients: `event <state>` when the device state changes,
+// `event fault <message>` when a command fails with a device fault.
+pub const EVENT: &str = "event";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Notification {
+    State(String),
+    Fault(String),
+    // Sent to idle watchers, so the server notices clients that went away.
+    Keepalive,
+}
+
+impl Notification {
+    pub fn to_line(&self) -> String {
+        match self {
+            Notification::State(state) => format!("{} {}\r\n", EVENT, state),
+            Notification::Fault(message) => format!("{} fault {}\r\n", EVENT, message),
+            Notification::Keepalive => format!("{} keepalive\r\n", EVENT),
+        }
+    }
+
+    // None if the line is not a notification.
+    pub fn from_line(line: &str) -> Option<Self> {
+        let rest = line.trim_end().strip_prefix(EVENT)?.strip_prefix(' ')?;
+        if rest == "keepalive" {
+            return Some(Notification::Keepalive);
+        }
+        match rest.strip_prefix("fault") {
+            Some(message) => Some(Notification::Fault(message.trim_start().to_owned())),
+            None => Some(Notification::State(rest.to_owned())),
+        }
+    }
+}
+
+impl From<ParseError> for ErrorResponse {
+    fn from(error: ParseError) -> Self {
+        let code = match error {
+            ParseError::UnknownCommand(_) => ErrorCode::UnknownCommand,
+            ParseError::MalformedCommand(_) | ParseError::UnexpectedResponse(_) => {
+                ErrorCode::MalformedInput
+            }
+        };
+        Self::new(code, error.to_string().trim_end())
+    }
 }
 
 #[derive(Debug, Error)]
@@ -30,29 +193,487 @@ pub enum ProtocolError {
     #[error("Invalid response")]
     InvalidResponse,
 
+    // The peer closed the connection between messages.
+    #[error("Connection closed")]
+    Closed,
+
     #[error("Bad handshake: {0}")]
     BadHandshake(String),
+
+    #[error("Unsupported protocol version: {0}")]
+    UnsupportedVersion(String),
+
+    #[error("Server error: {0}")]
+    ServerError(ErrorResponse),
+
+    #[error("Timed out: {0}")]
+    Timeout(String),
+
+    #[error("Connection refused: {0}")]
+    ConnectionRefused(String),
+
+    #[error("Message longer than {0} bytes")]
+    MessageTooLong(usize),
 }
 
-pub fn read_till_rn<Reader: Read>(reader: &mut Reader) -> Result<String, ProtocolError> {
-    let mut buffer = Vec::with_capacity(64);
-    let mut last_byte = 0u8;
+// Timeouts and refused connections get their own errors, anything else the fallback.
+pub fn io_error(error: io::Error, fallback: ProtocolError) -> ProtocolError {
+    match error.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => {
+            ProtocolError::Timeout(error.to_string())
+        }
+        io::ErrorKind::ConnectionRefused => ProtocolError::ConnectionRefused(error.to_string()),
+        _ => fallback,
+    }
+}
 
-    loop {
-        let mut byte = [0u8; 1];
-        if reader.read_exact(&mut byte).is_err() {
-            return Err(ProtocolError::InvalidResponse);
+// How messages are delimited after the handshake. Length-prefixed messages
+// carry the same text as lines, after a 4-byte big-endian length.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Framing {
+    #[default]
+    Lines,
+    LengthPrefixed,
+}
+
+impl Framing {
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Framing::Lines => "lines",
+            Framing::LengthPrefixed => "length",
         }
+    }
 
-        buffer.push(byte[0]);
+    // `framing=<name>`, None for any other token.
+    pub fn from_token(token: &str) -> Option<Self> {
+        match token.strip_prefix(FRAMING)? {
+            "lines" => Some(Framing::Lines),
+            "length" => Some(Framing::LengthPrefixed),
+            _ => None,
+        }
+    }
 
-        if last_byte == b'\r' && byte[0] == b'\n' {
-            break;
+    fn to_token(self) -> String {
+        match self {
+            Framing::Lines => String::new(),
+            framing => format!(" {}{}", FRAMING, framing.get_name()),
         }
-        last_byte = byte[0];
     }
+}
 
-    String::from_utf8(buffer).map_err(|_| ProtocolError::InvalidResponse)
+// What the server announces in a versioned handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServerInfo {
+    pub version: u16,
+    pub kind: String,
+    pub commands: Vec<String>,
+    pub framing: Framing,
+}
+
+impl ServerInfo {
+    pub fn supports(&self, command: &str) -> bool {
+        self.commands.iter().any(|name| name == command)
+    }
+
+    pub fn to_line(&self) -> String {
+        format!(
+            "{} {} {} {}{}\r\n",
+            HELLO,
+            self.version,
+            self.kind,
+            self.commands.join(","),
+            self.framing.to_token()
+        )
+    }
+
+    pub fn from_line(line: &str) -> Result<Self, ProtocolError> {
+        let bad = || ProtocolError::BadHandshake(line.trim_end().to_owned());
+        let rest = line.trim_end().strip_prefix(HELLO).ok_or_else(bad)?;
+        if let Some(message) = rest.strip_prefix(" error ") {
+            return Err(ProtocolError::UnsupportedVersion(message.to_owned()));
+        }
+
+        let mut parts = rest.split_whitespace().collect::<Vec<_>>();
+        let framing = match parts.last().and_then(|token| Framing::from_token(token)) {
+            Some(framing) => {
+                parts.pop();
+                framing
+            }
+            None => Framing::Lines,
+        };
+        let (version, kind, commands) = match parts.as_slice() {
+            [version, kind] => (version, kind, ""),
+            [version, kind, commands] => (version, kind, *commands),
+            _ => return Err(bad()),
+        };
+        Ok(Self {
+            version: version.parse().map_err(|_| bad())?,
+            kind: (*kind).to_owned(),
+            commands: commands
+                .split(',')
+                .filter(|name| !name.is_empty())
+                .map(str::to_owned)
+                .collect(),
+            framing,
+        })
+    }
+}
+
+pub fn format_hello(min_version: u16, max_version: u16, framing: Framing) -> String {
+    format!(
+        "{} {} {}{}\r\n",
+        HELLO,
+        min_version,
+        max_version,
+        framing.to_token()
+    )
+}
+
+// Parses the part of the client hello after the tag: ` <min> <max> [framing=<name>]\r\n`.
+// Framings the server does not know fall back to lines.
+pub fn parse_hello(line: &str) -> Option<(u16, u16, Framing)> {
+    let parts = line.split_whitespace().collect::<Vec<_>>();
+    let (min, max, framing) = match parts.as_slice() {
+        [min, max] => (min, max, Framing::Lines),
+        [min, max, framing] if framing.starts_with(FRAMING) => {
+            (min, max, Framing::from_token(framing).unwrap_or_default())
+        }
+        _ => return None,
+    };
+    Some((min.parse().ok()?, max.parse().ok()?, framing))
+}
+
+// The framing a client picked: `smhp framing=<name>`.
+fn parse_framing_choice(line: &str) -> Option<Framing> {
+    let token = line.trim_end().strip_prefix(HELLO)?.strip_prefix(' ')?;
+    Framing::from_token(token)
+}
+
+// The highest version both sides support, if any.
+pub fn negotiate_version(min_version: u16, max_version: u16) -> Option<u16> {
+    let version = max_version.min(PROTOCOL_VERSION);
+    (version >= min_version.max(LEGACY_VERSION)).then_some(version)
+}
+
+// What a handshake needs next from the IO around it. The handshakes below keep
+// the protocol logic free of IO, so blocking and async code drive the same steps.
+#[derive(Debug)]
+pub enum HandshakeStep<T> {
+    // Send the text (none if empty), then pass on the next line of the peer.
+    Reply(String),
+    // Send the text (none if empty), the handshake is done.
+    Done(String, T),
+    // Send the text (none if empty), then give up with the error.
+    Reject(String, ConnectError),
+}
+
+#[derive(Debug)]
+enum ServerState {
+    Tag,
+    Hello,
+    Challenge(Vec<u8>, u16, Framing),
+    // The framing offered to the client.
+    Framing(Framing),
+    Finished,
+}
+
+// The server side: accepts both the legacy `clnt` handshake and the versioned one.
+// With keys, only clients passing the challenge are accepted, legacy ones never are.
+// Done with the framing the session uses from then on.
+#[derive(Debug)]
+pub struct ServerHandshake<'a> {
+    kind: &'a str,
+    commands: &'a [&'a str],
+    keys: Option<&'a KeyStore>,
+    lines_only: bool,
+    state: ServerState,
+}
+
+impl<'a> ServerHandshake<'a> {
+    pub fn new(kind: &'a str, commands: &'a [&'a str], keys: Option<&'a KeyStore>) -> Self {
+        Self {
+            kind,
+            commands,
+            keys,
+            lines_only: false,
+            state: ServerState::Tag,
+        }
+    }
+
+    // Declines requests for other framings than lines.
+    pub fn with_lines_only(mut self) -> Self {
+        self.lines_only = true;
+        self
+    }
+
+    // The first `CLIENT_HANDSHAKE.len()` bytes the client sent.
+    pub fn start(&mut self, tag: &[u8]) -> HandshakeStep<Framing> {
+        if !matches!(self.state, ServerState::Tag) {
+            return self.fail(String::new(), ConnectError::BadHandshake);
+        }
+        if tag == CLIENT_HANDSHAKE {
+            if self.keys.is_some() {
+                return self.fail(
+                    String::new(),
+                    ConnectError::Unauthorized(
+                        "legacy handshake without authentication".to_owned(),
+                    ),
+                );
+            }
+            self.state = ServerState::Finished;
+            let reply = String::from_utf8_lossy(SERVER_HANDSHAKE).into_owned();
+            return HandshakeStep::Done(reply, Framing::Lines);
+        }
+        if tag != HELLO.as_bytes() {
+            return self.fail(String::new(), ConnectError::BadHandshake);
+        }
+        self.state = ServerState::Hello;
+        HandshakeStep::Reply(String::new())
+    }
+
+    // The next line the client sent.
+    pub fn next_line(&mut self, line: &str) -> HandshakeStep<Framing> {
+        match std::mem::replace(&mut self.state, ServerState::Finished) {
+            ServerState::Hello => {
+                let Some((min_version, max_version, framing)) = parse_hello(line) else {
+                    return self.fail(String::new(), ConnectError::BadHandshake);
+                };
+                let Some(version) = negotiate_version(min_version, max_version) else {
+                    let message = format!(
+                        "client supports {}-{}, server supports {}-{}",
+                        min_version, max_version, LEGACY_VERSION, PROTOCOL_VERSION
+                    );
+                    let reply = format!("{} error {}\r\n", HELLO, message);
+                    return self.fail(reply, ProtocolError::UnsupportedVersion(message).into());
+                };
+                // Clients of version 2 ask for a framing in the hello, newer ones after it.
+                let framing = match (self.lines_only, version >= FRAMING_VERSION) {
+                    (true, _) => Framing::Lines,
+                    (false, true) => Framing::LengthPrefixed,
+                    (false, false) => framing,
+                };
+                if self.keys.is_some() {
+                    let challenge = auth::new_challenge().to_vec();
+                    let reply = auth::challenge_line(&challenge);
+                    self.state = ServerState::Challenge(challenge, version, framing);
+                    return HandshakeStep::Reply(reply);
+                }
+                self.accept(version, framing)
+            }
+            ServerState::Challenge(challenge, version, framing) => {
+                let keys = self.keys.expect("Challenge without keys");
+                match keys.verify(&challenge, line) {
+                    Some(client_id) => {
+                        info!("Authenticated client: {}", client_id);
+                        self.accept(version, framing)
+                    }
+                    None => {
+                        let client_id = line.split_whitespace().next().unwrap_or_default();
+                        let error = ConnectError::Unauthorized(format!(
+                            "client {} failed the challenge",
+                            client_id
+                        ));
+                        self.fail(auth::unauthorized_line(), error)
+                    }
+                }
+            }
+            ServerState::Framing(offered) => match parse_framing_choice(line) {
+                Some(framing) if framing == Framing::Lines || framing == offered => {
+                    self.state = ServerState::Finished;
+                    HandshakeStep::Done(String::new(), framing)
+                }
+                _ => self.fail(String::new(), ConnectError::BadHandshake),
+            },
+            ServerState::Tag | ServerState::Finished => {
+                self.fail(String::new(), ConnectError::BadHandshake)
+            }
+        }
+    }
+
+    fn accept(&mut self, version: u16, framing: Framing) -> HandshakeStep<Framing> {
+        let info = ServerInfo {
+            version,
+            kind: self.kind.to_owned(),
+            commands: self.commands.iter().map(|&name| name.to_owned()).collect(),
+            framing,
+        };
+        if version >= FRAMING_VERSION {
+            self.state = ServerState::Framing(framing);
+            return HandshakeStep::Reply(info.to_line());
+        }
+        self.state = ServerState::Finished;
+        HandshakeStep::Done(info.to_line(), framing)
+    }
+
+    fn fail(&mut self, reply: String, error: ConnectError) -> HandshakeStep<Framing> {
+        self.state = ServerState::Finished;
+        HandshakeStep::Reject(reply, error)
+    }
+}
+
+// The client side of the versioned handshake: send `hello`, then pass on the
+// lines of the server. Done with what the server announced and the framing in use.
+// Servers of version 2 and older always use lines.
+#[derive(Debug)]
+pub struct ClientHandshake<'a> {
+    credentials: Option<&'a Credentials>,
+    framing: Framing,
+    answered: bool,
+}
+
+impl<'a> ClientHandshake<'a> {
+    pub fn new(credentials: Option<&'a Credentials>, framing: Framing) -> Self {
+        Self {
+            credentials,
+            framing,
+            answered: false,
+        }
+    }
+
+    pub fn hello(&self) -> String {
+        format_hello(LEGACY_VERSION, PROTOCOL_VERSION, Framing::Lines)
+    }
+
+    pub fn next_line(&mut self, line: &str) -> HandshakeStep<ServerInfo> {
+        if let Some(challenge) = auth::parse_challenge(line) {
+            return match self.credentials {
+                // A server asks only once.
+                Some(_) if self.answered => {
+                    HandshakeStep::Reject(String::new(), ConnectError::BadHandshake)
+                }
+                Some(credentials) => {
+                    self.answered = true;
+                    HandshakeStep::Reply(credentials.respond(&challenge))
+                }
+                None => HandshakeStep::Reject(
+                    String::new(),
+                    ConnectError::Unauthorized("server requires authentication".to_string()),
+                ),
+            };
+        }
+        if line == auth::unauthorized_line() {
+            return HandshakeStep::Reject(
+                String::new(),
+                ConnectError::Unauthorized("server rejected the credentials".to_string()),
+            );
+        }
+
+        let info = match ServerInfo::from_line(line) {
+            Ok(info) => info,
+            Err(error) => return HandshakeStep::Reject(String::new(), error.into()),
+        };
+        if !(LEGACY_VERSION..=PROTOCOL_VERSION).contains(&info.version) {
+            let error =
+                ProtocolError::UnsupportedVersion(format!("server chose version {}", info.version));
+            return HandshakeStep::Reject(String::new(), error.into());
+        }
+        if info.version < FRAMING_VERSION {
+            let info = ServerInfo {
+                framing: Framing::Lines,
+                ..info
+            };
+            return HandshakeStep::Done(String::new(), info);
+        }
+        let framing = match info.framing {
+            Framing::LengthPrefixed => self.framing,
+            Framing::Lines => Framing::Lines,
+        };
+        let choice = format!("{} {}{}\r\n", HELLO, FRAMING, framing.get_name());
+        HandshakeStep::Done(choice, ServerInfo { framing, ..info })
+    }
+}
+
+// For failed writes of the client during the handshake.
+pub fn handshake_error(error: io::Error) -> ProtocolError {
+    let message = error.to_string();
+    io_error(error, ProtocolError::BadHandshake(message))
+}
+
+// For failed reads of the client during the handshake.
+pub fn handshake_read_error(error: ProtocolError) -> ProtocolError {
+    match error {
+        ProtocolError::Timeout(_) => error,
+        // Servers without version negotiation drop the connection on an unknown handshake.
+        _ => {
+            ProtocolError::BadHandshake("Server closed the connection during handshake".to_string())
+        }
+    }
+}
+
+// What a server answers when it cannot read a command, if anything. Overlong
+// lines are answered, but end the session as the rest of the line is unread.
+pub fn read_error_response(error: &ProtocolError) -> Option<ErrorResponse> {
+    match error {
+        ProtocolError::MessageTooLong(_) => Some(ErrorResponse::new(
+            ErrorCode::MalformedInput,
+            error.to_string(),
+        )),
+        _ => None,
+    }
+}
+
+// Lines that are not UTF-8 get an error response.
+pub fn decode_command_line(line: Vec<u8>) -> Result<String, ErrorResponse> {
+    let line = String::from_utf8(line)
+        .map_err(|_| ErrorResponse::new(ErrorCode::MalformedInput, "Command is not valid UTF-8"))?;
+    debug!("Received command: {}", line.trim());
+    Ok(line)
+}
+
+// The command of a line, or the error response for the client.
+pub fn parse_command<C: ProtocolCommand>(line: Vec<u8>) -> Result<C, ErrorResponse> {
+    Ok(C::from_str(&decode_command_line(line)?)?)
+}
+
+// Collects a line a byte at a time, so nothing after the line is consumed.
+#[derive(Debug, Default)]
+pub struct LineBuffer {
+    buffer: Vec<u8>,
+}
+
+impl LineBuffer {
+    // True once the line including the trailing `\r\n` is complete.
+    pub fn push(&mut self, byte: u8) -> Result<bool, ProtocolError> {
+        self.buffer.push(byte);
+        if self.buffer.ends_with(b"\r\n") {
+            return Ok(true);
+        }
+        if self.buffer.len() == MAX_LINE_LEN {
+            return Err(ProtocolError::MessageTooLong(MAX_LINE_LEN));
+        }
+        Ok(false)
+    }
+
+    // The reader failed before the line was complete, e.g. the peer closed the connection.
+    pub fn read_error(&self, error: io::Error) -> ProtocolError {
+        if error.kind() == io::ErrorKind::UnexpectedEof && self.buffer.is_empty() {
+            return ProtocolError::Closed;
+        }
+        io_error(error, ProtocolError::InvalidResponse)
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buffer
+    }
+}
+
+pub fn read_till_rn<Reader: Read>(reader: &mut Reader) -> Result<String, ProtocolError> {
+    String::from_utf8(read_line_bytes(reader)?).map_err(|_| ProtocolError::InvalidResponse)
+}
+
+// Reads a raw line including the trailing `\r\n`, a byte at a time so nothing
+// after the line is consumed. Use `framing::FramedStream` to read whole sessions.
+pub fn read_line_bytes<Reader: Read>(reader: &mut Reader) -> Result<Vec<u8>, ProtocolError> {
+    let mut line = LineBuffer::default();
+    loop {
+        let mut byte = [0u8; 1];
+        if let Err(error) = reader.read_exact(&mut byte) {
+            return Err(line.read_error(error));
+        }
+        if line.push(byte[0])? {
+            return Ok(line.into_bytes());
+        }
+    }
 }
 
 #[cfg(test)]
@@ -66,5 +687,177 @@ mod tests {
         let mut reader = Cursor::new(b"hello\r\nworld\r\n");
         let result = read_till_rn(&mut reader).expect("Failed to read");
         assert_eq!(result, "hello\r\n");
+
+        let mut reader = Cursor::new(vec![b'a'; MAX_LINE_LEN * 2]);
+        assert!(matches!(
+            read_till_rn(&mut reader),
+            Err(ProtocolError::MessageTooLong(MAX_LINE_LEN))
+        ));
+    }
+
+    #[test]
+    fn test_error_response() {
+        let error = ErrorResponse::new(ErrorCode::DeviceFault, "Scene not found: Away");
+        assert_eq!(error.to_line(), "ERR 3 Scene not found: Away\r\n");
+        assert_eq!(ErrorResponse::from_line(&error.to_line()), Some(error));
+
+        let error = ErrorResponse::from(ParseError::UnknownCommand("dance".to_owned()));
+        assert_eq!(error.to_line(), "ERR 1 Unknown command: dance\r\n");
+        assert_eq!(
+            error.to_string(),
+            "Unknown command: dance (unknown command)"
+        );
+
+        assert_eq!(ErrorResponse::from_line("ok\r\n"), None);
+        assert_eq!(ErrorResponse::from_line("ERR 9 What\r\n"), None);
+        assert_eq!(ErrorResponse::from_line("ERRATIC\r\n"), None);
+    }
+
+    #[test]
+    fn test_notification() {
+        let state = Notification::State("on".to_owned());
+        assert_eq!(state.to_line(), "event on\r\n");
+        assert_eq!(Notification::from_line(&state.to_line()), Some(state));
+
+        let fault = Notification::Fault("Overheated".to_owned());
+        assert_eq!(fault.to_line(), "event fault Overheated\r\n");
+        assert_eq!(Notification::from_line(&fault.to_line()), Some(fault));
+
+        let line = Notification::Keepalive.to_line();
+        assert_eq!(
+            Notification::from_line(&line),
+            Some(Notification::Keepalive)
+        );
+
+        assert_eq!(Notification::from_line("on\r\n"), None);
+        assert_eq!(Notification::from_line("eventful\r\n"), None);
+    }
+
+    #[test]
+    fn test_negotiate_version() {
+        assert_eq!(negotiate_version(1, 2), Some(2));
+        assert_eq!(negotiate_version(2, 5), Some(PROTOCOL_VERSION));
+        assert_eq!(negotiate_version(1, 1), Some(LEGACY_VERSION));
+        assert_eq!(negotiate_version(4, 5), None);
+        assert_eq!(negotiate_version(2, 1), None);
+    }
+
+    #[test]
+    fn test_hello() {
+        let hello = format_hello(1, 2, Framing::Lines);
+        assert_eq!(hello, "smhp 1 2\r\n");
+        assert_eq!(
+            parse_hello(&hello[HELLO.len()..]),
+            Some((1, 2, Framing::Lines))
+        );
+        let hello = format_hello(1, 2, Framing::LengthPrefixed);
+        assert_eq!(hello, "smhp 1 2 framing=length\r\n");
+        assert_eq!(
+            parse_hello(&hello[HELLO.len()..]),
+            Some((1, 2, Framing::LengthPrefixed))
+        );
+        assert_eq!(
+            parse_hello(" 1 2 framing=zstd\r\n"),
+            Some((1, 2, Framing::Lines))
+        );
+        assert_eq!(parse_hello(" 1 2 3\r\n"), None);
+        assert_eq!(parse_hello(" 1\r\n"), None);
+        assert_eq!(parse_hello(" one two\r\n"), None);
+    }
+
+    #[test]
+    fn test_handshake_steps() {
+        let mut keys = KeyStore::new();
+        keys.add_client("gui", b"secret");
+        let credentials = Credentials::new("gui", b"secret");
+        let mut server = ServerHandshake::new("socket", &["on", "off"], Some(&keys));
+        let mut client = ClientHandshake::new(Some(&credentials), Framing::LengthPrefixed);
+
+        let hello = client.hello();
+        let (tag, hello) = hello.split_at(HELLO.len());
+        assert!(
+            matches!(server.start(tag.as_bytes()), HandshakeStep::Reply(reply) if reply.is_empty())
+        );
+        let HandshakeStep::Reply(challenge) = server.next_line(hello) else {
+            panic!("Challenge expected");
+        };
+        let HandshakeStep::Reply(response) = client.next_line(&challenge) else {
+            panic!("Response expected");
+        };
+        let HandshakeStep::Reply(line) = server.next_line(&response) else {
+            panic!("Server info expected");
+        };
+        assert_eq!(line, "smhp 3 socket on,off framing=length\r\n");
+        let HandshakeStep::Done(choice, info) = client.next_line(&line) else {
+            panic!("Server info expected");
+        };
+        assert_eq!(choice, "smhp framing=length\r\n");
+        assert!(matches!(
+            server.next_line(&choice),
+            HandshakeStep::Done(reply, Framing::LengthPrefixed) if reply.is_empty()
+        ));
+        assert_eq!(info.version, PROTOCOL_VERSION);
+        assert_eq!(info.framing, Framing::LengthPrefixed);
+        assert!(info.supports("off"));
+
+        // Legacy clients are not served with keys, and other framings are declined on request.
+        let mut server = ServerHandshake::new("socket", &[], Some(&keys));
+        assert!(matches!(
+            server.start(CLIENT_HANDSHAKE),
+            HandshakeStep::Reject(_, ConnectError::Unauthorized(_))
+        ));
+        let mut server = ServerHandshake::new("socket", &[], None).with_lines_only();
+        server.start(HELLO.as_bytes());
+        assert!(matches!(
+            server.next_line(" 1 2 framing=length\r\n"),
+            HandshakeStep::Done(_, Framing::Lines)
+        ));
+        // Clients of version 2 ask for a framing in the hello.
+        let mut server = ServerHandshake::new("socket", &[], None);
+        server.start(HELLO.as_bytes());
+        assert!(matches!(
+            server.next_line(" 1 2 framing=length\r\n"),
+            HandshakeStep::Done(line, Framing::LengthPrefixed) if line.starts_with("smhp 2 ")
+        ));
+        let mut server = ServerHandshake::new("socket", &[], None);
+        server.start(HELLO.as_bytes());
+        assert!(matches!(
+            server.next_line(" 7 9\r\n"),
+            HandshakeStep::Reject(reply, ConnectError::Protocol(ProtocolError::UnsupportedVersion(_)))
+                if reply.starts_with("smhp error ")
+        ));
+    }
+
+    #[test]
+    fn test_server_info() {
+        let info = ServerInfo {
+            version: 2,
+            kind: "socket".to_owned(),
+            commands: vec!["switch".to_owned(), "status".to_owned()],
+            framing: Framing::Lines,
+        };
+        assert_eq!(info.to_line(), "smhp 2 socket switch,status\r\n");
+        assert_eq!(ServerInfo::from_line(&info.to_line()).unwrap(), info);
+
+        let framed = ServerInfo {
+            framing: Framing::LengthPrefixed,
+            ..info.clone()
+        };
+        assert_eq!(
+            framed.to_line(),
+            "smhp 2 socket switch,status framing=length\r\n"
+        );
+        assert_eq!(ServerInfo::from_line(&framed.to_line()).unwrap(), framed);
+        assert!(info.supports("status"));
+        assert!(!info.supports("power"));
+
+        assert!(matches!(
+            ServerInfo::from_line("smhp error supported versions are 1-2\r\n"),
+            Err(ProtocolError::UnsupportedVersion(message)) if message == "supported versions are 1-2"
+        ));
+        assert!(matches!(
+            ServerInfo::from_line("serv\r\n"),
+            Err(ProtocolError::BadHandshake(_))
+        ));
     }
 }
@@ -0,0 +1,160 @@
+use std::{io, thread, time::Duration};
+
+use crate::{devices::tcp_device::ConnectError, protocol::ProtocolError};
+
+// Whether an attempt that failed with the error may be repeated. Commands that are
+// not idempotent are only repeated if they certainly did not reach the server.
+pub trait Retryable {
+    fn is_retryable(&self, idempotent: bool) -> bool;
+}
+
+impl Retryable for ProtocolError {
+    fn is_retryable(&self, idempotent: bool) -> bool {
+        match self {
+            ProtocolError::ConnectionRefused(_) => true,
+            ProtocolError::Timeout(_)
+            | ProtocolError::CouldNotSend
+            | ProtocolError::InvalidResponse
+            | ProtocolError::Closed => idempotent,
+            _ => false,
+        }
+    }
+}
+
+impl Retryable for io::Error {
+    fn is_retryable(&self, idempotent: bool) -> bool {
+        match self.kind() {
+            io::ErrorKind::ConnectionRefused => true,
+            io::ErrorKind::TimedOut
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe => idempotent,
+            _ => false,
+        }
+    }
+}
+
+impl Retryable for ConnectError {
+    fn is_retryable(&self, idempotent: bool) -> bool {
+        match self {
+            ConnectError::Protocol(error) => error.is_retryable(idempotent),
+            ConnectError::Io(error) => error.is_retryable(idempotent),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    // Including the first one.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, initial_backoff: Duration, max_backoff: Duration) -> Self {
+        Self {
+            max_attempts,
+            initial_backoff,
+            max_backoff,
+        }
+    }
+
+    pub fn no_retry() -> Self {
+        Self::new(1, Duration::ZERO, Duration::ZERO)
+    }
+
+    // The delay before the given retry, doubling from the initial backoff.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_backoff)
+    }
+
+    // Runs the attempt until it succeeds, fails with an error that may not be
+    // retried or the attempts run out. The last error is returned.
+    pub fn run<T, E: Retryable>(
+        &self,
+        idempotent: bool,
+        mut attempt: impl FnMut() -> Result<T, E>,
+    ) -> Result<T, E> {
+        let mut retry = 0;
+        loop {
+            match attempt() {
+                Ok(value) => return Ok(value),
+                Err(error) if retry + 1 < self.max_attempts && error.is_retryable(idempotent) => {
+                    retry += 1;
+                    thread::sleep(self.backoff(retry));
+                }
+                Err(error) => return Err(error),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(100), Duration::from_millis(350));
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(350));
+        assert_eq!(policy.backoff(40), Duration::from_millis(350));
+    }
+
+    #[test]
+    fn test_retry() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(1), Duration::from_millis(1));
+
+        let mut attempts = 0;
+        let result = policy.run(true, || {
+            attempts += 1;
+            match attempts {
+                1 => Err(ProtocolError::Timeout("read".to_owned())),
+                _ => Ok(attempts),
+            }
+        });
+        assert_eq!(result.unwrap(), 2);
+
+        // Timeouts of commands that are not idempotent are not retried.
+        let mut attempts = 0;
+        let result: Result<(), _> = policy.run(false, || {
+            attempts += 1;
+            Err(ProtocolError::Timeout("read".to_owned()))
+        });
+        assert!(matches!(result, Err(ProtocolError::Timeout(_))));
+        assert_eq!(attempts, 1);
+
+        // A refused connection never reached the server.
+        let mut attempts = 0;
+        let result: Result<(), _> = policy.run(false, || {
+            attempts += 1;
+            Err(ProtocolError::ConnectionRefused("refused".to_owned()))
+        });
+        assert!(matches!(result, Err(ProtocolError::ConnectionRefused(_))));
+        assert_eq!(attempts, 3);
+
+        let mut attempts = 0;
+        let result: Result<(), _> = policy.run(true, || {
+            attempts += 1;
+            Err(ProtocolError::InvalidCommand)
+        });
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+}
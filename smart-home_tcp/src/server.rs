@@ -0,0 +1,851 @@
+use std::{
+    collections::HashMap,
+    io::{Read, Write},
+    marker::PhantomData,
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Condvar, Mutex, MutexGuard,
+    },
+    thread,
+    time::Duration,
+};
+
+use log::{error, info, warn};
+use rustls::ServerConfig;
+
+use crate::{
+    auth::KeyStore,
+    devices::tcp_device::{read_line, send_response, try_handshake, TcpDevice},
+    framing::FramedStream,
+    pool::ThreadPool,
+    protocol::{ErrorCode, ErrorResponse, Notification, ProtocolCommand, ProtocolError, OK},
+    state::{StateError, StateFile},
+    tls,
+};
+
+pub const DEFAULT_WORKERS: usize = 4;
+// Each watcher takes a thread of its own, more are answered with an error.
+pub const MAX_WATCHERS: usize = 64;
+// Idle watchers get a keepalive this often, as a client that went away is only
+// noticed when writing to it.
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30);
+// Clients that do not read what is sent to them for this long are disconnected.
+pub const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+// Clients that do not finish the handshake, TLS included, in this time are disconnected,
+// so connections that send nothing do not hold on to the workers.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// Sessions without a command for this long are closed.
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(300);
+
+// Channels to the connections watching the device. A disconnected watcher is
+// dropped with the first notification that cannot be delivered to it.
+#[derive(Debug, Default)]
+struct Subscribers {
+    senders: Mutex<Vec<Sender<Notification>>>,
+}
+
+impl Subscribers {
+    fn subscribe(&self) -> Receiver<Notification> {
+        let (sender, receiver) = mpsc::channel();
+        self.lock().push(sender);
+        receiver
+    }
+
+    fn notify(&self, notification: Notification) {
+        self.lock()
+            .retain(|sender| sender.send(notification.clone()).is_ok());
+    }
+
+    // Ends the watching connections.
+    fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Sender<Notification>>> {
+        self.senders.lock().expect("Failed to lock mutex")
+    }
+}
+
+// Held by a watching connection until it ends.
+struct WatcherSlot(Arc<AtomicUsize>);
+
+impl WatcherSlot {
+    fn take(watchers: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        watchers
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                (count < max).then_some(count + 1)
+            })
+            .ok()?;
+        Some(Self(watchers.clone()))
+    }
+}
+
+impl Drop for WatcherSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Accepted connections until they are served, so they can be ended on shutdown.
+#[derive(Debug, Default)]
+struct Connections {
+    streams: Mutex<HashMap<u64, TcpStream>>,
+    next_id: AtomicU64,
+    closed: Condvar,
+}
+
+impl Connections {
+    fn open(connections: &Arc<Self>, stream: &TcpStream) -> OpenConnection {
+        let id = connections.next_id.fetch_add(1, Ordering::SeqCst);
+        if let Ok(stream) = stream.try_clone() {
+            connections.lock().insert(id, stream);
+        }
+        OpenConnection {
+            connections: connections.clone(),
+            id,
+        }
+    }
+
+    // Sessions waiting for a command see the client disconnect, a command being
+    // executed is still answered.
+    fn close_all(&self) {
+        for stream in self.lock().values() {
+            let _ = stream.shutdown(Shutdown::Read);
+        }
+    }
+
+    fn wait_closed(&self) {
+        let mut streams = self.lock();
+        while !streams.is_empty() {
+            streams = self.closed.wait(streams).expect("Failed to lock mutex");
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<u64, TcpStream>> {
+        self.streams.lock().expect("Failed to lock mutex")
+    }
+}
+
+struct OpenConnection {
+    connections: Arc<Connections>,
+    id: u64,
+}
+
+impl Drop for OpenConnection {
+    fn drop(&mut self) {
+        self.connections.lock().remove(&self.id);
+        self.connections.closed.notify_all();
+    }
+}
+
+// Stops `ConcurrentServer::run` from another thread, e.g. a signal handler.
+#[derive(Debug, Clone)]
+pub struct ShutdownHandle {
+    requested: Arc<AtomicBool>,
+    addr: SocketAddr,
+}
+
+impl ShutdownHandle {
+    pub fn shutdown(&self) {
+        self.requested.store(true, Ordering::SeqCst);
+        // Wakes up the thread waiting for connections.
+        let _ = TcpStream::connect(self.addr);
+    }
+
+    // Shuts down on Ctrl-C or SIGTERM. A process can only have one such handler.
+    pub fn on_signal(self) -> Result<(), ctrlc::Error> {
+        ctrlc::set_handler(move || {
+            info!("Shutting down");
+            self.shutdown();
+        })
+    }
+}
+
+// Serves up to `workers` connections at the same time. The device server is
+// shared between connections and locked only while a command is executed.
+#[derive(Debug)]
+pub struct ConcurrentServer<D, S: TcpDevice<D>> {
+    listener: TcpListener,
+    server: Arc<Mutex<S>>,
+    pool: ThreadPool,
+    // Connections are plaintext without a TLS configuration.
+    tls: Option<Arc<ServerConfig>>,
+    // Only clients with a key in the store are served, if set.
+    keys: Option<Arc<KeyStore>>,
+    subscribers: Arc<Subscribers>,
+    watchers: Arc<AtomicUsize>,
+    max_watchers: usize,
+    keepalive: Duration,
+    handshake_timeout: Duration,
+    idle_timeout: Duration,
+    // The device state is saved after each change and restored on start, if set.
+    state_file: Option<Arc<StateFile>>,
+    shutdown: Arc<AtomicBool>,
+    connections: Arc<Connections>,
+    device: PhantomData<D>,
+}
+
+impl<D, S> ConcurrentServer<D, S>
+where
+    S: TcpDevice<D> + Send + 'static,
+{
+    pub fn new(server: S, workers: usize) -> Self {
+        Self {
+            listener: server.get_listener(),
+            server: Arc::new(Mutex::new(server)),
+            pool: ThreadPool::new(workers),
+            tls: None,
+            keys: None,
+            subscribers: Arc::new(Subscribers::default()),
+            watchers: Arc::new(AtomicUsize::new(0)),
+            max_watchers: MAX_WATCHERS,
+            keepalive: KEEPALIVE_INTERVAL,
+            handshake_timeout: HANDSHAKE_TIMEOUT,
+            idle_timeout: IDLE_TIMEOUT,
+            state_file: None,
+            shutdown: Arc::new(AtomicBool::new(false)),
+            connections: Arc::new(Connections::default()),
+            device: PhantomData,
+        }
+    }
+
+    pub fn with_tls(mut self, config: Arc<ServerConfig>) -> Self {
+        self.tls = Some(config);
+        self
+    }
+
+    pub fn with_keys(mut self, keys: KeyStore) -> Self {
+        self.keys = Some(Arc::new(keys));
+        self
+    }
+
+    // At most `max` connections watch at the same time, idle ones get a keepalive every `keepalive`.
+    pub fn with_watch_limits(mut self, max: usize, keepalive: Duration) -> Self {
+        self.max_watchers = max;
+        self.keepalive = keepalive;
+        self
+    }
+
+    // Connections have `handshake` to finish the handshake, sessions are closed after `idle`
+    // without a command.
+    pub fn with_session_timeouts(mut self, handshake: Duration, idle: Duration) -> Self {
+        self.handshake_timeout = handshake;
+        self.idle_timeout = idle;
+        self
+    }
+
+    // Restores the state saved in the file, if there is one.
+    pub fn with_state_file(mut self, file: StateFile) -> Result<Self, StateError> {
+        if let Some(state) = file.load()? {
+            self.get_server().set_state(&state)?;
+            info!(
+                "Restored state {} from {}",
+                state,
+                file.get_path().display()
+            );
+        }
+        self.state_file = Some(Arc::new(file));
+        Ok(self)
+    }
+
+    pub fn local_addr(&self) -> Result<SocketAddr, std::io::Error> {
+        self.listener.local_addr()
+    }
+
+    pub fn shutdown_handle(&self) -> Result<ShutdownHandle, std::io::Error> {
+        Ok(ShutdownHandle {
+            requested: self.shutdown.clone(),
+            addr: self.local_addr()?,
+        })
+    }
+
+    pub fn get_server(&self) -> MutexGuard<'_, S> {
+        self.server.lock().expect("Failed to lock mutex")
+    }
+
+    // Accepts connections until shut down. Failed connections are logged and do not stop
+    // the server. On shutdown, commands being executed are answered and then every
+    // connection is closed before returning.
+    pub fn run(&self) {
+        for stream in self.listener.incoming() {
+            if self.shutdown.load(Ordering::SeqCst) {
+                break;
+            }
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(error) => {
+                    warn!("Connection failed: {error}");
+                    continue;
+                }
+            };
+
+            let server = self.server.clone();
+            let tls = self.tls.clone();
+            let keys = self.keys.clone();
+            let subscribers = self.subscribers.clone();
+            let watchers = self.watchers.clone();
+            let (max_watchers, keepalive) = (self.max_watchers, self.keepalive);
+            let (handshake_timeout, idle_timeout) = (self.handshake_timeout, self.idle_timeout);
+            let state_file = self.state_file.clone();
+            let connection = Connections::open(&self.connections, &stream);
+            self.pool.execute(move || {
+                let _connection = connection;
+                let peer = stream
+                    .peer_addr()
+                    .map(|addr| addr.to_string())
+                    .unwrap_or_default();
+                info!("Accepted connection from: {peer}");
+                let timeouts = stream
+                    .set_write_timeout(Some(WRITE_TIMEOUT))
+                    .and_then(|()| stream.set_read_timeout(Some(handshake_timeout)))
+                    .and_then(|()| stream.try_clone());
+                // Kept to change the read timeout once the handshake is done.
+                let socket = match timeouts {
+                    Ok(socket) => socket,
+                    Err(error) => {
+                        warn!("Failed to set timeouts for {peer}: {error}");
+                        return;
+                    }
+                };
+
+                let shared = Shared {
+                    server: &server,
+                    subscribers: &subscribers,
+                    watchers: &watchers,
+                    max_watchers,
+                    keepalive,
+                    state_file: state_file.as_deref(),
+                };
+                let session = Session {
+                    socket: &socket,
+                    keys: keys.as_deref(),
+                    idle_timeout,
+                    peer: &peer,
+                };
+                match tls {
+                    None => serve_connection(shared, session, stream),
+                    Some(config) => match tls::accept(stream, &config) {
+                        Ok(stream) => serve_connection(shared, session, stream),
+                        Err(error) => warn!("TLS with {peer} failed: {error}"),
+                    },
+                }
+            });
+        }
+
+        self.connections.close_all();
+        self.subscribers.clear();
+        self.connections.wait_closed();
+        info!("Server stopped");
+    }
+}
+
+// What the connections of a server share.
+struct Shared<'a, S> {
+    server: &'a Mutex<S>,
+    subscribers: &'a Subscribers,
+    watchers: &'a Arc<AtomicUsize>,
+    max_watchers: usize,
+    keepalive: Duration,
+    state_file: Option<&'a StateFile>,
+}
+
+impl<S> Clone for Shared<'_, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<S> Copy for Shared<'_, S> {}
+
+// What a connection has of its own, besides the stream it is served on.
+struct Session<'a> {
+    // The accepted TCP stream, also when served over TLS.
+    socket: &'a TcpStream,
+    keys: Option<&'a KeyStore>,
+    idle_timeout: Duration,
+    peer: &'a str,
+}
+
+fn serve_connection<D, S: TcpDevice<D>, T: Read + Write + Send + 'static>(
+    shared: Shared<S>,
+    session: Session,
+    stream: T,
+) {
+    let Session {
+        socket,
+        keys,
+        idle_timeout,
+        peer,
+    } = session;
+    let mut stream = match try_handshake(stream, S::KIND, S::COMMANDS, keys) {
+        Ok(stream) => stream,
+        Err(error) => {
+            warn!("Handshake with {peer} failed: {error}");
+            return;
+        }
+    };
+    if let Err(error) = socket.set_read_timeout(Some(idle_timeout)) {
+        warn!("Failed to set read timeout for {peer}: {error}");
+        return;
+    }
+    match serve(shared, &mut stream) {
+        // Watchers get a thread of their own so they do not hold on to a worker.
+        Ok(Some((notifications, slot))) => {
+            let peer = peer.to_owned();
+            let keepalive = shared.keepalive;
+            thread::spawn(move || {
+                let _slot = slot;
+                push(stream, notifications, keepalive, &peer)
+            });
+        }
+        Ok(None) => {}
+        Err(ProtocolError::Timeout(_)) => info!("Closed idle connection from {peer}"),
+        Err(error) => warn!("Connection with {peer} failed: {error}"),
+    }
+}
+
+// Returns the notifications for the client once it starts watching.
+fn serve<D, S: TcpDevice<D>, T: Read + Write>(
+    shared: Shared<S>,
+    stream: &mut FramedStream<T>,
+) -> Result<Option<(Receiver<Notification>, WatcherSlot)>, ProtocolError> {
+    let Shared {
+        server,
+        subscribers,
+        watchers,
+        max_watchers,
+        state_file,
+        ..
+    } = shared;
+    loop {
+        let line = match read_line(stream) {
+            Ok(line) => line,
+            Err(ProtocolError::Closed) => return Ok(None),
+            // The client got an error response and may go on.
+            Err(ProtocolError::InvalidCommand) => continue,
+            Err(error) => return Err(error),
+        };
+        let parsed = server
+            .lock()
+            .expect("Failed to lock mutex")
+            .parse_command(&line);
+        let command = match parsed {
+            Ok(command) => command,
+            Err(error) => {
+                send_response(stream, &ErrorResponse::from(error).to_line())?;
+                continue;
+            }
+        };
+
+        if command.is_watch() {
+            let Some(slot) = WatcherSlot::take(watchers, max_watchers) else {
+                let error = ErrorResponse::new(ErrorCode::DeviceFault, "Too many watchers");
+                send_response(stream, &error.to_line())?;
+                continue;
+            };
+            // Subscribing under the lock, so no change after the current state is missed.
+            let watch = {
+                let server = server.lock().expect("Failed to lock mutex");
+                server
+                    .get_state()
+                    .map(|state| (state, subscribers.subscribe()))
+            };
+            let Some((state, notifications)) = watch else {
+                let error = ErrorResponse::unsupported(&command.to_string());
+                send_response(stream, &error.to_line())?;
+                continue;
+            };
+            send_response(stream, OK)?;
+            send_response(stream, &Notification::State(state).to_line())?;
+            return Ok(Some((notifications, slot)));
+        }
+
+        let response = {
+            let mut server = server.lock().expect("Failed to lock mutex");
+            let before = server.get_state();
+            let response = server.execute(command);
+            // Handled under the lock, so changes are saved and notified in the order they were made.
+            match server.get_state() {
+                Some(state) if Some(&state) != before.as_ref() => {
+                    if let Some(Err(error)) = state_file.map(|file| file.save(&state)) {
+                        error!("Failed to save state: {error}");
+                    }
+                    subscribers.notify(Notification::State(state))
+                }
+                _ => {}
+            }
+            if let Some(error) = ErrorResponse::from_line(&response) {
+                if error.code == ErrorCode::DeviceFault {
+                    subscribers.notify(Notification::Fault(error.message));
+                }
+            }
+            response
+        };
+        send_response(stream, &response)?;
+    }
+}
+
+// Forwards notifications to a watching client until it disconnects.
+fn push<T: Write>(
+    mut stream: FramedStream<T>,
+    notifications: Receiver<Notification>,
+    keepalive: Duration,
+    peer: &str,
+) {
+    loop {
+        let notification = match notifications.recv_timeout(keepalive) {
+            Ok(notification) => notification,
+            Err(RecvTimeoutError::Timeout) => Notification::Keepalive,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        if let Err(error) = send_response(&mut stream, &notification.to_line()) {
+            warn!("Watcher {peer} disconnected: {error}");
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Write, net::TcpStream, thread};
+
+    use smart_home::devices::socket::SmartSocket;
+
+    use super::*;
+    use crate::{
+        auth::Credentials,
+        client::{receive_response, send_command, TcpSession},
+        devices::{
+            socket::{SocketCommand, SocketServer},
+            tcp_device::ConnectError,
+        },
+        protocol::{ErrorCode, ErrorResponse, ParseError, ProtocolCommand, CLIENT_HANDSHAKE},
+    };
+
+    struct Dance;
+
+    impl ProtocolCommand for Dance {
+        fn from_str(_s: &str) -> Result<Self, ParseError> {
+            Ok(Dance)
+        }
+
+        fn to_string(&self) -> String {
+            "dance\r\n".to_owned()
+        }
+    }
+
+    #[test]
+    fn test_concurrent_sessions() {
+        let socket = SmartSocket::new("Socket", "A smart socket", 100);
+        let server = ConcurrentServer::new(
+            SocketServer::bind(socket, "127.0.0.1:0").expect("Failed to bind"),
+            2,
+        );
+        let addr = server.local_addr().expect("No local address");
+        let server = Arc::new(server);
+        let runner = server.clone();
+        thread::spawn(move || runner.run());
+
+        // The first session stays open while the second one is served.
+        let mut first = TcpSession::connect(addr).expect("Failed to connect");
+        let mut second = TcpSession::connect(addr).expect("Failed to connect");
+        assert_eq!(second.send(SocketCommand::Switch).unwrap(), "ok\r\n");
+        assert_eq!(first.send(SocketCommand::Status).unwrap(), "on\r\n");
+        drop(second);
+
+        // An invalid command gets an error response and the connection stays usable.
+        let mut stream = TcpStream::connect(addr).expect("Failed to connect");
+        send_command(Dance, &mut stream).unwrap();
+        assert!(matches!(
+            receive_response(&mut stream),
+            Err(ProtocolError::ServerError(ErrorResponse {
+                code: ErrorCode::UnknownCommand,
+                ..
+            }))
+        ));
+        stream.write_all(b"status\r\n").unwrap();
+        assert_eq!(receive_response(&mut stream).unwrap(), "on\r\n");
+        drop(stream);
+
+        let mut third = TcpSession::connect(addr).expect("Failed to connect");
+        assert_eq!(third.send(SocketCommand::Status).unwrap(), "on\r\n");
+        assert_eq!(first.send(SocketCommand::Switch).unwrap(), "ok\r\n");
+        assert!(!server.get_server().get_device().is_on());
+    }
+
+    #[test]
+    fn test_idle_connections() {
+        let socket = SmartSocket::new("Socket", "A smart socket", 100);
+        let server = ConcurrentServer::new(
+            SocketServer::bind(socket, "127.0.0.1:0").expect("Failed to bind"),
+            2,
+        )
+        .with_session_timeouts(Duration::from_millis(100), Duration::from_millis(300));
+        let addr = server.local_addr().expect("No local address");
+        thread::spawn(move || server.run());
+
+        // Connections that send nothing take every worker until they time out.
+        let silent = [
+            TcpStream::connect(addr).unwrap(),
+            TcpStream::connect(addr).unwrap(),
+        ];
+        let mut session = TcpSession::connect(addr).expect("Failed to connect");
+        assert_eq!(session.send(SocketCommand::Status).unwrap(), "off\r\n");
+        drop(silent);
+
+        // Idle sessions are closed as well.
+        thread::sleep(Duration::from_millis(500));
+        assert!(session.send(SocketCommand::Status).is_err());
+        let mut session = TcpSession::connect(addr).expect("Failed to connect");
+        assert_eq!(session.send(SocketCommand::Status).unwrap(), "off\r\n");
+    }
+
+    #[test]
+    fn test_authenticated_sessions() {
+        let mut keys = KeyStore::new();
+        keys.add_client("panel", b"secret");
+        let socket = SmartSocket::new("Socket", "A smart socket", 100);
+        let server = ConcurrentServer::new(
+            SocketServer::bind(socket, "127.0.0.1:0").expect("Failed to bind"),
+            2,
+        )
+        .with_keys(keys);
+        let addr = server.local_addr().expect("No local address");
+        thread::spawn(move || server.run());
+
+        let connect = |credentials: Option<&Credentials>| {
+            TcpSession::open(TcpStream::connect(addr).unwrap(), credentials)
+        };
+
+        let credentials = Credentials::new("panel", b"secret");
+        let mut session = connect(Some(&credentials)).expect("Failed to connect");
+        assert_eq!(session.send(SocketCommand::Switch).unwrap(), "ok\r\n");
+        drop(session);
+
+        assert!(matches!(connect(None), Err(ConnectError::Unauthorized(_))));
+        let wrong_key = Credentials::new("panel", b"guess");
+        assert!(matches!(
+            connect(Some(&wrong_key)),
+            Err(ConnectError::Unauthorized(_))
+        ));
+        let unknown = Credentials::new("stranger", b"secret");
+        assert!(matches!(
+            connect(Some(&unknown)),
+            Err(ConnectError::Unauthorized(_))
+        ));
+
+        // Neither single commands without credentials nor legacy clients are served.
+        let mut stream = TcpStream::connect(addr).unwrap();
+        assert!(send_command(SocketCommand::Switch, &mut stream).is_err());
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream.write_all(CLIENT_HANDSHAKE).unwrap();
+        assert!(receive_response(&mut stream).is_err());
+
+        let mut session = connect(Some(&credentials)).expect("Failed to connect");
+        assert_eq!(session.send(SocketCommand::Status).unwrap(), "on\r\n");
+    }
+
+    // A socket whose power meter is broken.
+    struct FaultySocket(SocketServer);
+
+    impl TcpDevice<SmartSocket> for FaultySocket {
+        type Command = SocketCommand;
+        const KIND: &'static str = SocketServer::KIND;
+        const COMMANDS: &'static [&'static str] = SocketServer::COMMANDS;
+
+        fn bind<Addrs: std::net::ToSocketAddrs>(
+            device: SmartSocket,
+            addr: Addrs,
+        ) -> Result<Self, std::io::Error> {
+            SocketServer::bind(device, addr).map(FaultySocket)
+        }
+
+        fn get_listener(&self) -> TcpListener {
+            self.0.get_listener()
+        }
+
+        fn execute(&mut self, command: SocketCommand) -> String {
+            match command {
+                SocketCommand::Power => {
+                    ErrorResponse::new(ErrorCode::DeviceFault, "Power meter broken").to_line()
+                }
+                command => self.0.execute(command),
+            }
+        }
+
+        fn get_state(&self) -> Option<String> {
+            self.0.get_state()
+        }
+    }
+
+    #[test]
+    fn test_watch() {
+        let socket = SmartSocket::new("Socket", "A smart socket", 100);
+        let server = ConcurrentServer::new(
+            FaultySocket::bind(socket, "127.0.0.1:0").expect("Failed to bind"),
+            2,
+        );
+        let addr = server.local_addr().expect("No local address");
+        thread::spawn(move || server.run());
+
+        let watch = || {
+            TcpSession::connect(addr)
+                .and_then(|session| Ok(session.watch(SocketCommand::Watch)?))
+                .expect("Failed to watch")
+        };
+        // More watchers than workers, commands are still served.
+        let mut watchers = [watch(), watch(), watch()];
+        for watcher in &mut watchers {
+            assert_eq!(
+                watcher.next_notification().unwrap(),
+                Notification::State("off".to_owned())
+            );
+        }
+
+        let mut session = TcpSession::connect(addr).expect("Failed to connect");
+        assert_eq!(session.send(SocketCommand::Switch).unwrap(), "ok\r\n");
+        assert_eq!(session.send(SocketCommand::On).unwrap(), "ok\r\n");
+        assert!(session.send(SocketCommand::Power).is_err());
+        assert_eq!(session.send(SocketCommand::Off).unwrap(), "ok\r\n");
+
+        // Unchanged states are not notified, faults are.
+        for watcher in &mut watchers {
+            assert_eq!(
+                watcher.next_notification().unwrap(),
+                Notification::State("on".to_owned())
+            );
+            assert_eq!(
+                watcher.next_notification().unwrap(),
+                Notification::Fault("Power meter broken".to_owned())
+            );
+            assert_eq!(
+                watcher.next_notification().unwrap(),
+                Notification::State("off".to_owned())
+            );
+        }
+
+        // Watchers that went away do not stop the others.
+        let [first, mut second, third] = watchers;
+        drop(first);
+        drop(third);
+        for _ in 0..2 {
+            session.send(SocketCommand::Switch).unwrap();
+        }
+        assert_eq!(
+            second.next_notification().unwrap(),
+            Notification::State("on".to_owned())
+        );
+        assert_eq!(
+            second.next_notification().unwrap(),
+            Notification::State("off".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_watch_limits() {
+        let socket = SmartSocket::new("Socket", "A smart socket", 100);
+        let server = ConcurrentServer::new(
+            SocketServer::bind(socket, "127.0.0.1:0").expect("Failed to bind"),
+            2,
+        )
+        .with_watch_limits(1, Duration::from_millis(20));
+        let addr = server.local_addr().expect("No local address");
+        thread::spawn(move || server.run());
+
+        let watch = || {
+            TcpSession::connect(addr)
+                .expect("Failed to connect")
+                .watch(SocketCommand::Watch)
+        };
+        let mut watcher = watch().expect("Failed to watch");
+        assert!(matches!(
+            watch(),
+            Err(ProtocolError::ServerError(error)) if error.message == "Too many watchers"
+        ));
+
+        // Keepalives are not notifications.
+        thread::sleep(Duration::from_millis(100));
+        let mut session = TcpSession::connect(addr).expect("Failed to connect");
+        assert_eq!(session.send(SocketCommand::On).unwrap(), "ok\r\n");
+        for state in ["off", "on"] {
+            assert_eq!(
+                watcher.next_notification().unwrap(),
+                Notification::State(state.to_owned())
+            );
+        }
+
+        // A watcher that went away is noticed without any change of state.
+        drop(watcher);
+        let mut watched = false;
+        for _ in 0..100 {
+            thread::sleep(Duration::from_millis(20));
+            if watch().is_ok() {
+                watched = true;
+                break;
+            }
+        }
+        assert!(watched);
+    }
+
+    #[test]
+    fn test_shutdown_and_restore() {
+        let dir = std::env::temp_dir().join(format!("smart-home-server-{}", std::process::id()));
+        let state_file = StateFile::new(dir.join("socket_state"));
+        let start = || {
+            let socket = SmartSocket::new("Socket", "A smart socket", 100);
+            ConcurrentServer::new(
+                SocketServer::bind(socket, "127.0.0.1:0").expect("Failed to bind"),
+                2,
+            )
+            .with_state_file(state_file.clone())
+            .expect("Failed to restore state")
+        };
+
+        let server = start();
+        assert!(!server.get_server().get_device().is_on());
+        let addr = server.local_addr().expect("No local address");
+        let handle = server.shutdown_handle().expect("No local address");
+        let runner = thread::spawn(move || server.run());
+
+        let mut idle = TcpSession::connect(addr).expect("Failed to connect");
+        let mut watcher = TcpSession::connect(addr)
+            .expect("Failed to connect")
+            .watch(SocketCommand::Watch)
+            .expect("Failed to watch");
+        let mut session = TcpSession::connect(addr).expect("Failed to connect");
+        assert_eq!(session.send(SocketCommand::Switch).unwrap(), "ok\r\n");
+        assert_eq!(state_file.load().unwrap().as_deref(), Some("on"));
+
+        // Open sessions and watchers are closed, and no more connections are taken.
+        handle.shutdown();
+        runner.join().expect("Server panicked");
+        assert!(idle.send(SocketCommand::Status).is_err());
+        for state in ["off", "on"] {
+            assert_eq!(
+                watcher.next_notification().unwrap(),
+                Notification::State(state.to_owned())
+            );
+        }
+        assert!(watcher.next_notification().is_err());
+        assert!(TcpSession::connect(addr).is_err());
+
+        let server = start();
+        assert!(server.get_server().get_device().is_on());
+        drop(server);
+
+        state_file.save("maybe").unwrap();
+        let socket = SmartSocket::new("Socket", "A smart socket", 100);
+        let server = ConcurrentServer::new(
+            SocketServer::bind(socket, "127.0.0.1:0").expect("Failed to bind"),
+            1,
+        );
+        assert!(matches!(
+            server.with_state_file(state_file.clone()),
+            Err(StateError::Invalid(state)) if state == "maybe"
+        ));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
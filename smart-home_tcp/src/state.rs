@@ -0,0 +1,75 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum StateError {
+    #[error("Invalid device state: {0}")]
+    Invalid(String),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+// Device state kept in a file, so a restarted server goes on where it stopped.
+// The state is the line `TcpDevice::get_state` returns, e.g. `on` for a socket.
+#[derive(Debug, Clone)]
+pub struct StateFile {
+    path: PathBuf,
+}
+
+impl StateFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+
+    // None if nothing was saved yet.
+    pub fn load(&self) -> Result<Option<String>, StateError> {
+        match fs::read_to_string(&self.path) {
+            Ok(state) => Ok(Some(state.trim().to_owned())),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    // Written to a temporary file first and renamed, so a crash never leaves half a state.
+    pub fn save(&self, state: &str) -> Result<(), StateError> {
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let mut temporary = self.path.clone().into_os_string();
+        temporary.push(".tmp");
+        fs::write(&temporary, format!("{}\n", state))?;
+        fs::rename(&temporary, &self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    #[test]
+    fn test_state_file() {
+        let dir = env::temp_dir().join(format!("smart-home-state-{}", process::id()));
+        let file = StateFile::new(dir.join("socket_state"));
+        assert!(file.load().unwrap().is_none());
+
+        file.save("on").unwrap();
+        assert_eq!(file.load().unwrap().as_deref(), Some("on"));
+        file.save("off").unwrap();
+        assert_eq!(file.load().unwrap().as_deref(), Some("off"));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
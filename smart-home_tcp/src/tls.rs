@@ -0,0 +1,304 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, Read, Write},
+    net::TcpStream,
+    path::Path,
+    sync::Arc,
+};
+
+use log::info;
+use rcgen::CertifiedKey;
+use rustls::{
+    crypto::{ring, CryptoProvider},
+    pki_types::{CertificateDer, PrivateKeyDer, ServerName},
+    ClientConfig, ClientConnection, RootCertStore, ServerConfig, ServerConnection, StreamOwned,
+};
+use smart_home::config::{Config, Setting};
+use thiserror::Error;
+
+pub type ServerTlsStream = StreamOwned<ServerConnection, TcpStream>;
+pub type ClientTlsStream = StreamOwned<ClientConnection, TcpStream>;
+
+// Connections are encrypted if the certificate exists, see `tls-gen-cert`.
+pub const SERVER_SETTINGS: &[Setting] = &[
+    Setting::new("tls_cert", "settings/tls_cert.pem", "TLS certificate"),
+    Setting::new("tls_key", "settings/tls_key.pem", "TLS private key"),
+];
+
+// The server certificate is trusted if present, and the connection is then encrypted.
+pub const CLIENT_SETTINGS: &[Setting] = &[Setting::new(
+    "tls_cert",
+    "settings/tls_cert.pem",
+    "trusted server certificate",
+)];
+
+#[derive(Debug, Error)]
+pub enum TlsError {
+    #[error("No certificates in {0}")]
+    NoCertificates(String),
+
+    #[error("No private key in {0}")]
+    NoPrivateKey(String),
+
+    #[error("Invalid server name: {0}")]
+    InvalidServerName(String),
+
+    #[error(transparent)]
+    Tls(#[from] rustls::Error),
+
+    #[error(transparent)]
+    Certificate(#[from] rcgen::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+// Set explicitly, so other crates enabling another provider do not make the choice ambiguous.
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+pub fn load_certs(path: impl AsRef<Path>) -> Result<Vec<CertificateDer<'static>>, TlsError> {
+    let path = path.as_ref();
+    let mut reader = BufReader::new(File::open(path)?);
+    let certs = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificates(path.display().to_string()));
+    }
+    Ok(certs)
+}
+
+pub fn load_key(path: impl AsRef<Path>) -> Result<PrivateKeyDer<'static>, TlsError> {
+    let path = path.as_ref();
+    let mut reader = BufReader::new(File::open(path)?);
+    rustls_pemfile::private_key(&mut reader)?
+        .ok_or_else(|| TlsError::NoPrivateKey(path.display().to_string()))
+}
+
+pub fn server_config(
+    cert_path: impl AsRef<Path>,
+    key_path: impl AsRef<Path>,
+) -> Result<Arc<ServerConfig>, TlsError> {
+    let config = ServerConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()?
+        .with_no_client_auth()
+        .with_single_cert(load_certs(cert_path)?, load_key(key_path)?)?;
+    Ok(Arc::new(config))
+}
+
+// Trusts only the certificates in the file, e.g. a self-signed server certificate.
+pub fn client_config(ca_path: impl AsRef<Path>) -> Result<Arc<ClientConfig>, TlsError> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(ca_path)? {
+        roots.add(cert)?;
+    }
+    let config = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    Ok(Arc::new(config))
+}
+
+// From `SERVER_SETTINGS`, None if there is no certificate.
+pub fn server_config_from(config: &Config) -> Result<Option<Arc<ServerConfig>>, TlsError> {
+    let cert_path = config.get("tls_cert");
+    if !Path::new(cert_path).exists() {
+        return Ok(None);
+    }
+    let server_config = server_config(cert_path, config.get("tls_key"))?;
+    info!("TLS enabled with certificate {}", cert_path);
+    Ok(Some(server_config))
+}
+
+// From `CLIENT_SETTINGS`, None if there is no certificate.
+pub fn client_config_from(config: &Config) -> Result<Option<Arc<ClientConfig>>, TlsError> {
+    let ca_path = config.get("tls_cert");
+    if !Path::new(ca_path).exists() {
+        return Ok(None);
+    }
+    client_config(ca_path).map(Some)
+}
+
+// Writes a certificate valid for the given host names and IP addresses, for development only.
+pub fn generate_self_signed(
+    names: &[&str],
+    cert_path: impl AsRef<Path>,
+    key_path: impl AsRef<Path>,
+) -> Result<(), TlsError> {
+    let names = names
+        .iter()
+        .map(|&name| name.to_owned())
+        .collect::<Vec<_>>();
+    let CertifiedKey { cert, key_pair } = rcgen::generate_simple_self_signed(names)?;
+    fs::write(cert_path, cert.pem())?;
+    fs::write(key_path, key_pair.serialize_pem())?;
+    Ok(())
+}
+
+// The TLS handshake itself runs on the first read or write.
+pub fn accept(stream: TcpStream, config: &Arc<ServerConfig>) -> Result<ServerTlsStream, TlsError> {
+    let connection = ServerConnection::new(config.clone())?;
+    Ok(StreamOwned::new(connection, stream))
+}
+
+pub fn connect(
+    stream: TcpStream,
+    server_name: &str,
+    config: &Arc<ClientConfig>,
+) -> Result<ClientTlsStream, TlsError> {
+    let name = ServerName::try_from(server_name.to_owned())
+        .map_err(|_| TlsError::InvalidServerName(server_name.to_owned()))?;
+    let connection = ClientConnection::new(config.clone(), name)?;
+    Ok(StreamOwned::new(connection, stream))
+}
+
+// The host of a `host:port` address, without the brackets of an IPv6 address.
+pub fn server_name(addr: &str) -> &str {
+    let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+    host.strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
+// A client connection, encrypted if there is a configuration.
+#[derive(Debug)]
+pub enum ClientStream {
+    Plain(TcpStream),
+    Tls(Box<ClientTlsStream>),
+}
+
+impl Read for ClientStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            ClientStream::Plain(stream) => stream.read(buf),
+            ClientStream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for ClientStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            ClientStream::Plain(stream) => stream.write(buf),
+            ClientStream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            ClientStream::Plain(stream) => stream.flush(),
+            ClientStream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+// The certificate must be valid for the host of `addr`.
+pub fn connect_optional(
+    stream: TcpStream,
+    addr: &str,
+    config: Option<&Arc<ClientConfig>>,
+) -> Result<ClientStream, TlsError> {
+    Ok(match config {
+        Some(config) => ClientStream::Tls(Box::new(connect(stream, server_name(addr), config)?)),
+        None => ClientStream::Plain(stream),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, path::PathBuf, thread};
+
+    use smart_home::devices::socket::SmartSocket;
+
+    use super::*;
+    use crate::{
+        client::TcpSession,
+        devices::{
+            socket::{SocketCommand, SocketServer},
+            tcp_device::TcpDevice,
+        },
+        server::ConcurrentServer,
+    };
+
+    // Certificate and key files in a directory of their own.
+    fn generate(name: &str) -> (PathBuf, PathBuf) {
+        let dir = env::temp_dir().join(format!("smart-home-tls-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (cert, key) = (dir.join("cert.pem"), dir.join("key.pem"));
+        generate_self_signed(&["localhost", "127.0.0.1"], &cert, &key).unwrap();
+        (cert, key)
+    }
+
+    #[test]
+    fn test_load_config() {
+        let (cert, key) = generate("load");
+        assert_eq!(load_certs(&cert).unwrap().len(), 1);
+        assert!(server_config(&cert, &key).is_ok());
+        assert!(client_config(&cert).is_ok());
+
+        assert!(matches!(load_key(&cert), Err(TlsError::NoPrivateKey(_))));
+        assert!(matches!(load_certs(&key), Err(TlsError::NoCertificates(_))));
+    }
+
+    #[test]
+    fn test_server_name() {
+        assert_eq!(server_name("localhost:55331"), "localhost");
+        assert_eq!(server_name("127.0.0.1:55331"), "127.0.0.1");
+        assert_eq!(server_name("[::1]:55331"), "::1");
+        assert!(ServerName::try_from(server_name("[::1]:55331").to_owned()).is_ok());
+    }
+
+    #[test]
+    fn test_session_over_tls() {
+        let (cert, key) = generate("session");
+        let config = server_config(&cert, &key).unwrap();
+        let mut server = SocketServer::bind(
+            SmartSocket::new("Socket", "A smart socket", 100),
+            "127.0.0.1:0",
+        )
+        .unwrap();
+        let addr = server.get_listener().local_addr().unwrap();
+
+        let server_thread = thread::spawn(move || {
+            let mut stream = server.accept_tls(&config).expect("Failed to accept");
+            server.serve(&mut stream).expect("Failed to serve session");
+        });
+
+        let client = client_config(&cert).unwrap();
+        let mut session = TcpSession::connect_tls(addr, "localhost", &client).unwrap();
+        assert_eq!(session.get_server_info().kind, "socket");
+        assert_eq!(session.send(SocketCommand::Switch).unwrap(), "ok\r\n");
+        assert_eq!(session.send(SocketCommand::Status).unwrap(), "on\r\n");
+        drop(session);
+
+        server_thread.join().expect("Failed to join server thread");
+    }
+
+    #[test]
+    fn test_concurrent_server_over_tls() {
+        let (cert, key) = generate("concurrent");
+        let server = ConcurrentServer::new(
+            SocketServer::bind(
+                SmartSocket::new("Socket", "A smart socket", 100),
+                "127.0.0.1:0",
+            )
+            .unwrap(),
+            2,
+        )
+        .with_tls(server_config(&cert, &key).unwrap());
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+
+        let client = client_config(&cert).unwrap();
+        let mut session = TcpSession::connect_tls(addr, "127.0.0.1", &client).unwrap();
+        assert_eq!(session.send(SocketCommand::Status).unwrap(), "off\r\n");
+
+        // A client that does not trust the certificate cannot connect.
+        let (other_cert, _) = generate("untrusted");
+        let untrusted = client_config(&other_cert).unwrap();
+        assert!(TcpSession::connect_tls(addr, "localhost", &untrusted).is_err());
+
+        // Plaintext clients are not served either.
+        assert!(TcpSession::connect(addr).is_err());
+    }
+}
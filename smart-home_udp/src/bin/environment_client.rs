@@ -0,0 +1,46 @@
+use std::{net::UdpSocket, thread, time::Duration};
+
+use smart_home::config::{Config, Setting};
+use smart_home::devices::environment::EnvironmentSensor;
+use smart_home_udp_client::devices::{environment::EnvironmentSocketServer, udp_device::UdpDevice};
+
+const SETTINGS: &[Setting] = &[
+    Setting::addr("bind_addr", "127.0.0.1:4327", "address readings arrive at"),
+    Setting::addr(
+        "receiver_addr",
+        "127.0.0.1:4321",
+        "address readings are forwarded to",
+    ),
+    Setting::new("name", "Air", "device name"),
+    Setting::new("description", "An air quality sensor", "device description"),
+];
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::from_env("environment-udp-client", SETTINGS);
+    let bind_addr = config.get_addr("bind_addr");
+    let receiver_addr = config.get_addr("receiver_addr");
+    config.init_logger();
+
+    let mut sensor = EnvironmentSocketServer::bind(
+        EnvironmentSensor::new(config.get("name"), config.get("description")),
+        receiver_addr,
+    )?;
+
+    let mut data_stream = UdpSocket::bind(bind_addr)?;
+    sensor.handle(&mut data_stream)?;
+
+    for _ in 0..120 {
+        thread::sleep(Duration::from_secs(1));
+        let reading = sensor.get_reading();
+        println!(
+            "Temperature {:.1}, humidity {:.0}%, CO2 {} ppm, dew point {:.1}, humidex {:.1}",
+            reading.temperature,
+            reading.humidity,
+            reading.co2,
+            reading.dew_point(),
+            reading.humidex()
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use std::{
+    error::Error,
+    net::UdpSocket,
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::warn;
+use smart_home::config::{Config, Setting};
+use smart_home::devices::environment::EnvironmentReading;
+use smart_home_udp_client::{devices::environment::format_reading, protocol::send_message};
+
+const SETTINGS: &[Setting] = &[
+    Setting::addr("bind_addr", "127.0.0.1:4326", "address to send from"),
+    Setting::addr(
+        "receiver_addr",
+        "127.0.0.1:4327",
+        "address to send readings to",
+    ),
+];
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let config = Config::from_env("environment-generator-udp-server", SETTINGS);
+    config.init_logger();
+    let generator = EnvironmentGenerator::default();
+    let mut socket = UdpSocket::bind(config.get_addr("bind_addr"))?;
+    let receiver_addr = config.get_addr("receiver_addr");
+
+    loop {
+        let message = format_reading(&generator.generate());
+        println!("Sending reading: {message}");
+        if let Err(error) = send_message(&mut socket, receiver_addr, &message) {
+            warn!("Error sending message: {:?}", error);
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+}
+
+struct EnvironmentGenerator {
+    started: Instant,
+}
+
+impl Default for EnvironmentGenerator {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+        }
+    }
+}
+
+impl EnvironmentGenerator {
+    pub fn generate(&self) -> EnvironmentReading {
+        let t = (Instant::now() - self.started).as_secs_f32();
+        // CO2 slowly builds up and drops as if a window was opened now and then.
+        EnvironmentReading {
+            temperature: 20.0 + (t / 2.0).sin(),
+            humidity: 45.0 + 10.0 * (t / 7.0).sin(),
+            co2: (800.0 + 300.0 * (t / 11.0).sin()) as u16,
+        }
+    }
+}
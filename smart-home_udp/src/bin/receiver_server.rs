@@ -1,15 +1,26 @@
 use std::{error::Error, net::UdpSocket};
 
+use log::warn;
+use smart_home::config::{Config, Setting};
 use smart_home_udp_client::protocol::receive_message;
 
+const SETTINGS: &[Setting] = &[Setting::addr(
+    "bind_addr",
+    "127.0.0.1:4321",
+    "address to receive messages at",
+)];
+
 fn main() -> Result<(), Box<dyn Error>> {
-    let receiver_address = "127.0.0.1:4321";
-    let mut socket = UdpSocket::bind(receiver_address)?;
+    let config = Config::from_env("thermometer-udp-server", SETTINGS);
+    let receiver_addr = config.get_addr("bind_addr");
+    config.init_logger();
+
+    let mut socket = UdpSocket::bind(receiver_addr)?;
 
     loop {
         match receive_message(&mut socket) {
             Ok(message) => println!("Received message: {message}"),
-            Err(error) => println!("Error receiving message: {:?}", error),
+            Err(error) => warn!("Error receiving message: {:?}", error),
         }
     }
 }
@@ -0,0 +1,47 @@
+use std::{net::UdpSocket, thread, time::Duration};
+
+use smart_home::config::{Config, Setting};
+use smart_home::devices::sensor::BinarySensor;
+use smart_home_udp_client::devices::{sensor::SensorPublisher, udp_device::UdpDevice};
+
+const SETTINGS: &[Setting] = &[
+    Setting::addr("bind_addr", "127.0.0.1:4325", "address to send from"),
+    Setting::addr(
+        "receiver_addr",
+        "127.0.0.1:4324",
+        "address to send events to",
+    ),
+];
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::from_env("sensor-udp-publisher", SETTINGS);
+    let receiver_addr = config.get_addr("receiver_addr");
+    config.init_logger();
+
+    let mut motion = SensorPublisher::bind(
+        BinarySensor::motion("Hallway motion", "A motion sensor"),
+        receiver_addr,
+    )?;
+    let mut door = SensorPublisher::bind(
+        BinarySensor::contact("Front door", "A door contact sensor"),
+        receiver_addr,
+    )?;
+
+    let mut socket = UdpSocket::bind(config.get_addr("bind_addr"))?;
+    motion.handle(&mut socket)?;
+    door.handle(&mut socket)?;
+
+    // Someone opens the door and walks through the hallway every few seconds.
+    for step in 0..120 {
+        let active = step % 4 < 2;
+        if let Some(event) = door.set_active(active && step % 8 < 4)? {
+            println!("Door: {:?}", event);
+        }
+        if let Some(event) = motion.set_active(active)? {
+            println!("Motion: {:?}", event);
+        }
+        thread::sleep(Duration::from_secs(1));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use std::{error::Error, net::UdpSocket};
+
+use smart_home::config::{Config, Setting};
+use smart_home_udp_client::devices::sensor::SensorReceiver;
+
+const SETTINGS: &[Setting] = &[Setting::addr(
+    "bind_addr",
+    "127.0.0.1:4324",
+    "address to receive events at",
+)];
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let config = Config::from_env("sensor-udp-receiver", SETTINGS);
+    let receiver_addr = config.get_addr("bind_addr");
+    config.init_logger();
+
+    let socket = UdpSocket::bind(receiver_addr)?;
+    let (_receiver, events) = SensorReceiver::listen(socket)?;
+
+    for message in events {
+        println!(
+            "{} ({}): {}",
+            message.name,
+            message.kind,
+            message.kind.state_name(message.event.active)
+        );
+    }
+
+    Ok(())
+}
@@ -1,17 +1,32 @@
 use std::{net::UdpSocket, thread, time::Duration};
 
+use smart_home::config::{Config, Setting};
 use smart_home::devices::thermometer::SmartThermometer;
 use smart_home_udp_client::devices::{thermometer::ThermometerSocketServer, udp_device::UdpDevice};
 
+const SETTINGS: &[Setting] = &[
+    Setting::addr("bind_addr", "127.0.0.1:4322", "address readings arrive at"),
+    Setting::addr(
+        "receiver_addr",
+        "127.0.0.1:4321",
+        "address readings are forwarded to",
+    ),
+    Setting::new("name", "Thermometer", "device name"),
+    Setting::new("description", "A smart thermometer", "device description"),
+];
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let receiver_address = "127.0.0.1:4321";
+    let config = Config::from_env("thermometer-udp-client", SETTINGS);
+    let bind_addr = config.get_addr("bind_addr");
+    let receiver_addr = config.get_addr("receiver_addr");
+    config.init_logger();
+
     let mut thermo = ThermometerSocketServer::bind(
-        SmartThermometer::new("Thermometer", "A smart thermometer"),
-        receiver_address,
+        SmartThermometer::new(config.get("name"), config.get("description")),
+        receiver_addr,
     )?;
 
-    let generator_address = "127.0.0.1:4322";
-    let mut data_stream = UdpSocket::bind(generator_address)?;
+    let mut data_stream = UdpSocket::bind(bind_addr)?;
     thermo.handle(&mut data_stream)?;
 
     for _ in 0..120 {
@@ -5,19 +5,31 @@ use std::{
     time::{Duration, Instant},
 };
 
+use log::warn;
+use smart_home::config::{Config, Setting};
 use smart_home_udp_client::protocol::send_message;
 
+const SETTINGS: &[Setting] = &[
+    Setting::addr("bind_addr", "127.0.0.1:4323", "address to send from"),
+    Setting::addr(
+        "receiver_addr",
+        "127.0.0.1:4322",
+        "address to send readings to",
+    ),
+];
+
 fn main() -> Result<(), Box<dyn Error>> {
+    let config = Config::from_env("thermometer-generator-udp-server", SETTINGS);
+    config.init_logger();
     let generator = TemperatureGenerator::default();
-    let generator_address = "127.0.0.1:4323";
-    let mut socket = UdpSocket::bind(generator_address)?;
-    let receiver_address = "127.0.0.1:4322";
+    let mut socket = UdpSocket::bind(config.get_addr("bind_addr"))?;
+    let receiver_addr = config.get_addr("receiver_addr");
 
     loop {
         let temperature = generator.generate();
         println!("Sending temperature: {temperature}");
-        if let Err(error) = send_message(&mut socket, receiver_address, &temperature.to_string()) {
-            println!("Error sending message: {:?}", error);
+        if let Err(error) = send_message(&mut socket, receiver_addr, &temperature.to_string()) {
+            warn!("Error sending message: {:?}", error);
         }
         thread::sleep(Duration::from_secs(1));
     }
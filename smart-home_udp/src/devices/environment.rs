@@ -0,0 +1,179 @@
+use log::warn;
+use smart_home::devices::environment::{EnvironmentReading, EnvironmentSensor};
+use std::{
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use crate::protocol::{receive_message, send_message, ProtocolError};
+
+use super::udp_device::UdpDevice;
+
+// Readings are sent as `key=value` pairs separated by `;`, e.g.
+// `t=21.50;rh=45.0;co2=600`. The order of the fields does not matter.
+pub fn format_reading(reading: &EnvironmentReading) -> String {
+    format!(
+        "t={:.2};rh={:.1};co2={}",
+        reading.temperature, reading.humidity, reading.co2
+    )
+}
+
+pub fn parse_reading(message: &str) -> Result<EnvironmentReading, ProtocolError> {
+    let invalid = || ProtocolError::InvalidMessage(message.to_owned());
+    let (mut temperature, mut humidity, mut co2) = (None, None, None);
+
+    for field in message.trim().split(';') {
+        let (key, value) = field.split_once('=').ok_or_else(invalid)?;
+        match key.trim() {
+            "t" => temperature = Some(value.trim().parse().map_err(|_| invalid())?),
+            "rh" => humidity = Some(value.trim().parse().map_err(|_| invalid())?),
+            "co2" => co2 = Some(value.trim().parse().map_err(|_| invalid())?),
+            _ => return Err(invalid()),
+        }
+    }
+
+    Ok(EnvironmentReading {
+        temperature: temperature.ok_or_else(invalid)?,
+        humidity: humidity.ok_or_else(invalid)?,
+        co2: co2.ok_or_else(invalid)?,
+    })
+}
+
+// Same as `ThermometerSocketServer`, but for all the metrics of an environment sensor.
+#[derive(Debug)]
+pub struct EnvironmentSocketServer {
+    receiver_adr: SocketAddr,
+    device: EnvironmentSensor,
+    reading: Arc<Mutex<EnvironmentReading>>,
+    finished: Arc<AtomicBool>,
+}
+
+impl UdpDevice<EnvironmentSensor> for EnvironmentSocketServer {
+    fn bind<Addrs: ToSocketAddrs>(
+        device: EnvironmentSensor,
+        receiver_addr: Addrs,
+    ) -> Result<Self, std::io::Error> {
+        let receiver_adr = receiver_addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Invalid receiver address",
+            ))?;
+
+        Ok(Self {
+            receiver_adr,
+            reading: Arc::new(Mutex::new(device.get_reading())),
+            device,
+            finished: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    fn handle(&mut self, data_stream: &mut UdpSocket) -> Result<(), ProtocolError> {
+        let mut data_stream_clone = data_stream
+            .try_clone()
+            .map_err(|_| ProtocolError::CouldNotSend)?;
+        let finished_clone = self.finished.clone();
+        let reading_clone = self.reading.clone();
+        let receiver_adr = self.receiver_adr;
+
+        thread::spawn(move || loop {
+            if finished_clone.load(Ordering::SeqCst) {
+                return;
+            }
+
+            match receive_message(&mut data_stream_clone).and_then(|m| parse_reading(&m)) {
+                Ok(reading) => *reading_clone.lock().expect("Failed to lock mutex") = reading,
+                Err(error) => {
+                    warn!("Error receiving message: {:?}", error);
+                }
+            }
+
+            let reading = *reading_clone.lock().expect("Failed to lock mutex");
+            if let Err(error) = send_message(
+                &mut data_stream_clone,
+                receiver_adr,
+                &format_reading(&reading),
+            ) {
+                warn!("Error sending message: {:?}", error);
+            }
+
+            thread::sleep(Duration::from_secs(1));
+        });
+
+        Ok(())
+    }
+}
+
+impl EnvironmentSocketServer {
+    pub fn get_reading(&self) -> EnvironmentReading {
+        *self.reading.lock().expect("Failed to lock mutex")
+    }
+
+    pub fn get_device(&self) -> EnvironmentSensor {
+        let mut device = self.device.clone();
+        device.set_reading(self.get_reading());
+        device
+    }
+}
+
+impl Drop for EnvironmentSocketServer {
+    fn drop(&mut self) {
+        self.finished.store(true, Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reading_format() {
+        let reading = EnvironmentReading {
+            temperature: 21.5,
+            humidity: 45.0,
+            co2: 600,
+        };
+        assert_eq!(format_reading(&reading), "t=21.50;rh=45.0;co2=600");
+        assert_eq!(parse_reading(&format_reading(&reading)).unwrap(), reading);
+        assert_eq!(
+            parse_reading("co2=900; rh=60; t=19\r\n").unwrap(),
+            EnvironmentReading {
+                temperature: 19.0,
+                humidity: 60.0,
+                co2: 900,
+            }
+        );
+
+        assert!(parse_reading("t=20;rh=40").is_err());
+        assert!(parse_reading("t=20;rh=40;co2=-1").is_err());
+        assert!(parse_reading("t=20;rh=40;co2=600;pm=10").is_err());
+    }
+
+    #[test]
+    fn test_environment_server() {
+        let receiver = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let mut server = EnvironmentSocketServer::bind(
+            EnvironmentSensor::new("Air", "Air quality sensor"),
+            receiver.local_addr().unwrap(),
+        )
+        .unwrap();
+
+        let mut data_stream = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let data_addr = data_stream.local_addr().unwrap();
+        server.handle(&mut data_stream).unwrap();
+
+        let mut generator = UdpSocket::bind("127.0.0.1:0").unwrap();
+        send_message(&mut generator, data_addr, "t=22;rh=50;co2=700").unwrap();
+
+        let mut receiver = receiver;
+        let forwarded = parse_reading(&receive_message(&mut receiver).unwrap()).unwrap();
+        assert_eq!(forwarded.co2, 700);
+        assert_eq!(server.get_device().get_reading().humidity, 50.0);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod environment;
+pub mod sensor;
 pub mod thermometer;
 
 pub mod udp_device;
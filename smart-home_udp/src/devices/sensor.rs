@@ -0,0 +1,261 @@
+use log::warn;
+use smart_home::devices::{
+    device::Device,
+    sensor::{BinarySensor, BinarySensorKind, SensorEvent},
+};
+use std::{
+    collections::HashMap,
+    net::{SocketAddr, ToSocketAddrs, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use crate::protocol::{receive_message, send_message, ProtocolError};
+
+use super::udp_device::UdpDevice;
+
+// A sensor state change as sent over the wire:
+// `<kind> <active|inactive> <unix time in ms> <sensor name>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SensorMessage {
+    pub name: String,
+    pub kind: BinarySensorKind,
+    pub event: SensorEvent,
+}
+
+impl SensorMessage {
+    pub fn to_message(&self) -> String {
+        let millis = self
+            .event
+            .timestamp
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+        format!(
+            "{} {} {} {}",
+            self.kind,
+            if self.event.active {
+                "active"
+            } else {
+                "inactive"
+            },
+            millis,
+            self.name
+        )
+    }
+
+    pub fn from_message(message: &str) -> Result<Self, ProtocolError> {
+        let invalid = || ProtocolError::InvalidMessage(message.to_owned());
+        let mut parts = message.splitn(4, ' ');
+
+        let kind = match parts.next() {
+            Some("motion") => BinarySensorKind::Motion,
+            Some("contact") => BinarySensorKind::Contact,
+            _ => return Err(invalid()),
+        };
+        let active = match parts.next() {
+            Some("active") => true,
+            Some("inactive") => false,
+            _ => return Err(invalid()),
+        };
+        let millis: u64 = parts
+            .next()
+            .and_then(|millis| millis.parse().ok())
+            .ok_or_else(invalid)?;
+        let name = parts
+            .next()
+            .filter(|name| !name.is_empty())
+            .ok_or_else(invalid)?;
+
+        Ok(Self {
+            name: name.to_owned(),
+            kind,
+            event: SensorEvent {
+                active,
+                timestamp: SystemTime::UNIX_EPOCH + Duration::from_millis(millis),
+            },
+        })
+    }
+}
+
+// Sends every state change of a sensor to the receiver address.
+#[derive(Debug)]
+pub struct SensorPublisher {
+    receiver_adr: SocketAddr,
+    device: BinarySensor,
+    socket: Option<UdpSocket>,
+}
+
+impl UdpDevice<BinarySensor> for SensorPublisher {
+    fn bind<Addrs: ToSocketAddrs>(
+        device: BinarySensor,
+        receiver_addr: Addrs,
+    ) -> Result<Self, std::io::Error> {
+        let receiver_adr = receiver_addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "Invalid receiver address",
+            ))?;
+
+        Ok(Self {
+            receiver_adr,
+            device,
+            socket: None,
+        })
+    }
+
+    fn handle(&mut self, data_stream: &mut UdpSocket) -> Result<(), ProtocolError> {
+        self.socket = Some(
+            data_stream
+                .try_clone()
+                .map_err(|_| ProtocolError::CouldNotSend)?,
+        );
+        Ok(())
+    }
+}
+
+impl SensorPublisher {
+    pub fn get_device(&self) -> BinarySensor {
+        self.device.clone()
+    }
+
+    // Updates the sensor and publishes the event if the state changed.
+    pub fn set_active(&mut self, active: bool) -> Result<Option<SensorEvent>, ProtocolError> {
+        let socket = self.socket.as_mut().ok_or(ProtocolError::CouldNotSend)?;
+        let Some(event) = self.device.set_active(active) else {
+            return Ok(None);
+        };
+
+        let message = SensorMessage {
+            name: self.device.get_name().to_owned(),
+            kind: self.device.get_kind(),
+            event,
+        };
+        send_message(socket, self.receiver_adr, &message.to_message())?;
+
+        Ok(Some(event))
+    }
+}
+
+// Receives events of any number of sensors, keeps their latest state and
+// forwards every event to a channel, so they can drive automation.
+#[derive(Debug)]
+pub struct SensorReceiver {
+    sensors: Arc<Mutex<HashMap<String, BinarySensor>>>,
+    finished: Arc<AtomicBool>,
+}
+
+impl SensorReceiver {
+    pub fn listen(
+        mut socket: UdpSocket,
+    ) -> Result<(Self, Receiver<SensorMessage>), std::io::Error> {
+        // The timeout lets the thread notice that the receiver was dropped.
+        socket.set_read_timeout(Some(Duration::from_millis(200)))?;
+        let sensors = Arc::new(Mutex::new(HashMap::<String, BinarySensor>::new()));
+        let finished = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+
+        let sensors_clone = sensors.clone();
+        let finished_clone = finished.clone();
+        thread::spawn(move || {
+            while !finished_clone.load(Ordering::SeqCst) {
+                let Ok(message) = receive_message(&mut socket) else {
+                    continue;
+                };
+
+                let message = match SensorMessage::from_message(&message) {
+                    Ok(message) => message,
+                    Err(error) => {
+                        warn!("Error receiving message: {:?}", error);
+                        continue;
+                    }
+                };
+
+                let changed = sensors_clone
+                    .lock()
+                    .expect("Failed to lock mutex")
+                    .entry(message.name.clone())
+                    .or_insert_with(|| BinarySensor::new(&message.name, "", message.kind))
+                    .record(message.event);
+
+                if changed && sender.send(message).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok((Self { sensors, finished }, receiver))
+    }
+
+    pub fn get_sensor(&self, name: &str) -> Option<BinarySensor> {
+        self.sensors
+            .lock()
+            .expect("Failed to lock mutex")
+            .get(name)
+            .cloned()
+    }
+}
+
+impl Drop for SensorReceiver {
+    fn drop(&mut self) {
+        self.finished.store(true, Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sensor_message() {
+        let message = SensorMessage {
+            name: "Front door".to_owned(),
+            kind: BinarySensorKind::Contact,
+            event: SensorEvent {
+                active: true,
+                timestamp: SystemTime::UNIX_EPOCH + Duration::from_millis(1500),
+            },
+        };
+        assert_eq!(message.to_message(), "contact active 1500 Front door");
+        assert_eq!(
+            SensorMessage::from_message(&message.to_message()).unwrap(),
+            message
+        );
+
+        assert!(SensorMessage::from_message("smoke active 1 Kitchen").is_err());
+        assert!(SensorMessage::from_message("motion active 1").is_err());
+        assert!(SensorMessage::from_message("motion maybe 1 Hall").is_err());
+    }
+
+    #[test]
+    fn test_publish_receive() {
+        let receiver_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let receiver_addr = receiver_socket.local_addr().unwrap();
+        let (receiver, events) = SensorReceiver::listen(receiver_socket).unwrap();
+
+        let mut publisher =
+            SensorPublisher::bind(BinarySensor::motion("Hall", "Hall motion"), receiver_addr)
+                .unwrap();
+        let mut socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        publisher.handle(&mut socket).unwrap();
+
+        assert!(publisher.set_active(true).unwrap().is_some());
+        assert!(publisher.set_active(true).unwrap().is_none());
+
+        let message = events.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(message.name, "Hall");
+        assert_eq!(message.kind, BinarySensorKind::Motion);
+        assert!(message.event.active);
+
+        let sensor = receiver.get_sensor("Hall").expect("Sensor expected");
+        assert!(sensor.is_active());
+        assert!(sensor.last_triggered().is_some());
+    }
+}
@@ -1,3 +1,4 @@
+use log::warn;
 use smart_home::devices::thermometer::SmartThermometer;
 use std::{
     net::{SocketAddr, ToSocketAddrs, UdpSocket},
@@ -62,7 +63,7 @@ impl UdpDevice<SmartThermometer> for ThermometerSocketServer {
                     }
                 }
                 Err(error) => {
-                    println!("Error receiving message: {:?}", error);
+                    warn!("Error receiving message: {:?}", error);
                 }
             }
 
@@ -71,7 +72,7 @@ impl UdpDevice<SmartThermometer> for ThermometerSocketServer {
                 receiver_adr,
                 &temperature_clone.get().to_string(),
             ) {
-                println!("Error sending message: {:?}", error);
+                warn!("Error sending message: {:?}", error);
             }
 
             thread::sleep(Duration::from_secs(1));
@@ -1,10 +1,20 @@
 use reqwest::Client;
 use serde_json::json;
+use smart_home::config::{Config, Setting};
+
+const SETTINGS: &[Setting] = &[Setting::addr(
+    "web_addr",
+    "localhost:3331",
+    "address of the web server",
+)];
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::from_env("smart-home-web-client", SETTINGS);
+    let base_url = format!("http://{}", config.get_addr("web_addr"));
+    config.init_logger();
+
     let client = Client::new();
-    let base_url = "http://localhost:3331";
 
     let response = client
         .post(format!("{}/rooms", base_url))
@@ -41,6 +51,41 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let report = response.text().await?;
     println!("House Report:\n{}", report);
 
+    let response = client
+        .post(format!("{}/houses", base_url))
+        .json(&json!({
+            "name": "Country House"
+        }))
+        .send()
+        .await?;
+    println!("Create house status: {}", response.status());
+
+    let response = client
+        .post(format!("{}/houses/Country House/rooms", base_url))
+        .json(&json!({
+            "name": "Veranda"
+        }))
+        .send()
+        .await?;
+    println!("Create room in house status: {}", response.status());
+
+    let response = client.get(format!("{}/houses", base_url)).send().await?;
+    let houses: serde_json::Value = response.json().await?;
+    println!("Houses: {}", serde_json::to_string_pretty(&houses)?);
+
+    let response = client
+        .get(format!("{}/estate/report", base_url))
+        .send()
+        .await?;
+    let report = response.text().await?;
+    println!("Estate Report:\n{}", report);
+
+    let response = client
+        .delete(format!("{}/houses/Country House", base_url))
+        .send()
+        .await?;
+    println!("Delete house status: {}", response.status());
+
     let response = client
         .delete(format!("{}/rooms/Living Room/devices/Socket1", base_url))
         .send()
@@ -1,13 +1,24 @@
 use axum::serve::serve;
+use smart_home::config::{Config, Setting};
 use smart_home_web::create_router;
-use std::net::SocketAddr;
+
+const SETTINGS: &[Setting] = &[Setting::addr(
+    "web_addr",
+    "127.0.0.1:3331",
+    "address to listen on",
+)];
 
 #[tokio::main]
-async fn main() {
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::from_env("smart-home-web", SETTINGS);
+    let addr = config.get_addr("web_addr");
+    config.init_logger();
+
     let app = create_router();
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3331));
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
+    let listener = tokio::net::TcpListener::bind(addr).await?;
 
     println!("Server running on http://{}", addr);
-    serve(listener, app.into_make_service()).await.unwrap();
+    serve(listener, app.into_make_service()).await?;
+
+    Ok(())
 }
@@ -4,22 +4,26 @@ use axum::{
     extract::{Path, State},
     http::StatusCode,
     response::{IntoResponse, Response},
-    routing::{delete, get},
+    routing::{delete, get, post},
     Json, Router,
 };
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use smart_home::{
     devices::{device::Device, socket::SmartSocket},
+    estate::Estate,
     house::House,
     report_provider::DeviceInfoProvider,
+    scene::{Scene, SceneAction},
 };
 use thiserror::Error;
 use tokio::sync::RwLock;
 
+pub const DEFAULT_HOUSE: &str = "Smart House";
+
 #[derive(Clone)]
 pub struct AppState {
-    house: Arc<RwLock<House>>,
+    estate: Arc<RwLock<Estate>>,
 }
 
 #[derive(Error, Debug)]
@@ -28,6 +32,8 @@ pub enum ApiError {
     SmartHouse(#[from] smart_home::errors::SmartHouseError),
     #[error("Not found")]
     NotFound,
+    #[error("{0}")]
+    Conflict(String),
 }
 
 impl IntoResponse for ApiError {
@@ -35,18 +41,30 @@ impl IntoResponse for ApiError {
         let (status, message) = match self {
             ApiError::SmartHouse(err) => (StatusCode::BAD_REQUEST, err.to_string()),
             ApiError::NotFound => (StatusCode::NOT_FOUND, "Not found".to_string()),
+            ApiError::Conflict(message) => (StatusCode::CONFLICT, message),
         };
 
         (status, Json(json!({ "error": message }))).into_response()
     }
 }
 
+#[derive(Debug, Serialize)]
+pub struct HouseResponse {
+    name: String,
+    rooms: Vec<String>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct RoomResponse {
     name: String,
     devices: Vec<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct CreateHouseRequest {
+    name: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CreateRoomRequest {
     name: String,
@@ -59,6 +77,49 @@ pub struct CreateDeviceRequest {
     power: u32,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SceneEntryBody {
+    room: String,
+    device: String,
+    on: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SceneBody {
+    name: String,
+    actions: Vec<SceneEntryBody>,
+}
+
+// Routes without `/houses/{house_name}` prefix are aliases for the default house,
+// so `house_name` is optional in every path below.
+#[derive(Debug, Deserialize)]
+pub struct HousePath {
+    house_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoomPath {
+    house_name: Option<String>,
+    room_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ScenePath {
+    house_name: Option<String>,
+    scene_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DevicePath {
+    house_name: Option<String>,
+    room_name: String,
+    device_name: String,
+}
+
+fn house_name(name: &Option<String>) -> &str {
+    name.as_deref().unwrap_or(DEFAULT_HOUSE)
+}
+
 pub struct MockDeviceInfoProvider;
 
 impl DeviceInfoProvider for MockDeviceInfoProvider {
@@ -68,10 +129,27 @@ impl DeviceInfoProvider for MockDeviceInfoProvider {
 }
 
 pub fn create_router() -> Router {
-    let state = AppState {
-        house: Arc::new(RwLock::new(House::new("Smart House"))),
-    };
+    let mut estate = Estate::new("Smart Estate");
+    estate
+        .add_house(House::new(DEFAULT_HOUSE))
+        .expect("Estate is empty");
+
+    router(AppState {
+        estate: Arc::new(RwLock::new(estate)),
+    })
+}
+
+fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/houses", get(get_houses).post(create_house))
+        .route("/houses/{house_name}", delete(delete_house))
+        .nest("/houses/{house_name}", house_router())
+        .merge(house_router())
+        .route("/estate/report", get(get_estate_report))
+        .with_state(state)
+}
 
+fn house_router() -> Router<AppState> {
     Router::new()
         .route("/rooms", get(get_rooms).post(create_room))
         .route("/rooms/{room_name}", delete(delete_room))
@@ -83,13 +161,70 @@ pub fn create_router() -> Router {
             "/rooms/{room_name}/devices/{device_name}",
             delete(delete_device),
         )
+        .route("/scenes", get(get_scenes).post(create_scene))
+        .route("/scenes/{scene_name}", delete(delete_scene))
+        .route("/scenes/{scene_name}/apply", post(apply_scene))
         .route("/report", get(get_report))
-        .with_state(state)
 }
 
 #[axum::debug_handler]
-async fn get_rooms(State(state): State<AppState>) -> Json<Vec<RoomResponse>> {
-    let house = state.house.read().await;
+async fn get_houses(State(state): State<AppState>) -> Json<Vec<HouseResponse>> {
+    let estate = state.estate.read().await;
+    let houses = estate
+        .get_houses()
+        .map(|house| {
+            let mut rooms: Vec<String> = house
+                .get_rooms()
+                .map(|room| room.get_name().to_string())
+                .collect();
+            rooms.sort();
+            HouseResponse {
+                name: house.get_name().to_string(),
+                rooms,
+            }
+        })
+        .collect();
+
+    Json(houses)
+}
+
+#[axum::debug_handler]
+async fn create_house(
+    State(state): State<AppState>,
+    Json(request): Json<CreateHouseRequest>,
+) -> Result<StatusCode, ApiError> {
+    let mut estate = state.estate.write().await;
+    estate.add_house(House::new(&request.name))?;
+    Ok(StatusCode::CREATED)
+}
+
+#[axum::debug_handler]
+async fn delete_house(
+    State(state): State<AppState>,
+    Path(path): Path<HousePath>,
+) -> Result<StatusCode, ApiError> {
+    let name = house_name(&path.house_name);
+    // The routes without a house prefix need it.
+    if name == DEFAULT_HOUSE {
+        return Err(ApiError::Conflict(format!(
+            "The default house {} cannot be deleted",
+            DEFAULT_HOUSE
+        )));
+    }
+    let mut estate = state.estate.write().await;
+    estate.remove_house(name)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler]
+async fn get_rooms(
+    State(state): State<AppState>,
+    Path(path): Path<HousePath>,
+) -> Result<Json<Vec<RoomResponse>>, ApiError> {
+    let estate = state.estate.read().await;
+    let house = estate
+        .get_house(house_name(&path.house_name))
+        .ok_or(ApiError::NotFound)?;
     let rooms = house
         .get_rooms()
         .map(|room| RoomResponse {
@@ -101,15 +236,19 @@ async fn get_rooms(State(state): State<AppState>) -> Json<Vec<RoomResponse>> {
         })
         .collect();
 
-    Json(rooms)
+    Ok(Json(rooms))
 }
 
 #[axum::debug_handler]
 async fn create_room(
     State(state): State<AppState>,
+    Path(path): Path<HousePath>,
     Json(request): Json<CreateRoomRequest>,
 ) -> Result<StatusCode, ApiError> {
-    let mut house = state.house.write().await;
+    let mut estate = state.estate.write().await;
+    let house = estate
+        .get_house_mut(house_name(&path.house_name))
+        .ok_or(ApiError::NotFound)?;
     house.add_room(&request.name)?;
     Ok(StatusCode::CREATED)
 }
@@ -117,22 +256,25 @@ async fn create_room(
 #[axum::debug_handler]
 async fn delete_room(
     State(state): State<AppState>,
-    Path(room_name): Path<String>,
+    Path(path): Path<RoomPath>,
 ) -> Result<StatusCode, ApiError> {
-    let mut house = state.house.write().await;
-    house.remove_room(&room_name)?;
+    let mut estate = state.estate.write().await;
+    let house = estate
+        .get_house_mut(house_name(&path.house_name))
+        .ok_or(ApiError::NotFound)?;
+    house.remove_room(&path.room_name)?;
     Ok(StatusCode::NO_CONTENT)
 }
 
 #[axum::debug_handler]
 async fn get_devices(
     State(state): State<AppState>,
-    Path(room_name): Path<String>,
+    Path(path): Path<RoomPath>,
 ) -> Result<Json<Vec<String>>, ApiError> {
-    let house = state.house.read().await;
-    let room = house
-        .get_rooms()
-        .find(|room| room.get_name() == room_name)
+    let estate = state.estate.read().await;
+    let room = estate
+        .get_house(house_name(&path.house_name))
+        .and_then(|house| house.get_room(&path.room_name))
         .ok_or(ApiError::NotFound)?;
 
     Ok(Json(
@@ -145,11 +287,14 @@ async fn get_devices(
 #[axum::debug_handler]
 async fn create_device(
     State(state): State<AppState>,
-    Path(room_name): Path<String>,
+    Path(path): Path<RoomPath>,
     Json(request): Json<CreateDeviceRequest>,
 ) -> Result<StatusCode, ApiError> {
-    let mut house = state.house.write().await;
-    let room = house.get_room_mut(&room_name).ok_or(ApiError::NotFound)?;
+    let mut estate = state.estate.write().await;
+    let room = estate
+        .get_house_mut(house_name(&path.house_name))
+        .and_then(|house| house.get_room_mut(&path.room_name))
+        .ok_or(ApiError::NotFound)?;
 
     let socket = SmartSocket::new(&request.name, &request.description, request.power);
     room.add_device(socket.into())?;
@@ -159,17 +304,272 @@ async fn create_device(
 #[axum::debug_handler]
 async fn delete_device(
     State(state): State<AppState>,
-    Path((room_name, device_name)): Path<(String, String)>,
+    Path(path): Path<DevicePath>,
+) -> Result<StatusCode, ApiError> {
+    let mut estate = state.estate.write().await;
+    let room = estate
+        .get_house_mut(house_name(&path.house_name))
+        .and_then(|house| house.get_room_mut(&path.room_name))
+        .ok_or(ApiError::NotFound)?;
+
+    room.remove_device(&path.device_name)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler]
+async fn get_scenes(
+    State(state): State<AppState>,
+    Path(path): Path<HousePath>,
+) -> Result<Json<Vec<SceneBody>>, ApiError> {
+    let estate = state.estate.read().await;
+    let house = estate
+        .get_house(house_name(&path.house_name))
+        .ok_or(ApiError::NotFound)?;
+    let scenes = house
+        .get_scenes()
+        .map(|scene| SceneBody {
+            name: scene.get_name().to_string(),
+            actions: scene
+                .get_entries()
+                .map(|entry| SceneEntryBody {
+                    room: entry.room.clone(),
+                    device: entry.device.clone(),
+                    on: entry.action == SceneAction::TurnOn,
+                })
+                .collect(),
+        })
+        .collect();
+
+    Ok(Json(scenes))
+}
+
+#[axum::debug_handler]
+async fn create_scene(
+    State(state): State<AppState>,
+    Path(path): Path<HousePath>,
+    Json(request): Json<SceneBody>,
 ) -> Result<StatusCode, ApiError> {
-    let mut house = state.house.write().await;
-    let room = house.get_room_mut(&room_name).ok_or(ApiError::NotFound)?;
+    let mut estate = state.estate.write().await;
+    let house = estate
+        .get_house_mut(house_name(&path.house_name))
+        .ok_or(ApiError::NotFound)?;
 
-    room.remove_device(&device_name)?;
+    let mut scene = Scene::new(&request.name);
+    for entry in request.actions {
+        let action = if entry.on {
+            SceneAction::TurnOn
+        } else {
+            SceneAction::TurnOff
+        };
+        scene.add_action(&entry.room, &entry.device, action);
+    }
+
+    house.add_scene(scene)?;
+    Ok(StatusCode::CREATED)
+}
+
+#[axum::debug_handler]
+async fn delete_scene(
+    State(state): State<AppState>,
+    Path(path): Path<ScenePath>,
+) -> Result<StatusCode, ApiError> {
+    let mut estate = state.estate.write().await;
+    let house = estate
+        .get_house_mut(house_name(&path.house_name))
+        .ok_or(ApiError::NotFound)?;
+    house.remove_scene(&path.scene_name)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[axum::debug_handler]
+async fn apply_scene(
+    State(state): State<AppState>,
+    Path(path): Path<ScenePath>,
+) -> Result<StatusCode, ApiError> {
+    let mut estate = state.estate.write().await;
+    let house = estate
+        .get_house_mut(house_name(&path.house_name))
+        .ok_or(ApiError::NotFound)?;
+    house.apply_scene(&path.scene_name)?;
     Ok(StatusCode::NO_CONTENT)
 }
 
 #[axum::debug_handler]
-async fn get_report(State(state): State<AppState>) -> String {
-    let house = state.house.read().await;
-    house.create_report(&MockDeviceInfoProvider)
+async fn get_report(
+    State(state): State<AppState>,
+    Path(path): Path<HousePath>,
+) -> Result<String, ApiError> {
+    let estate = state.estate.read().await;
+    let house = estate
+        .get_house(house_name(&path.house_name))
+        .ok_or(ApiError::NotFound)?;
+    Ok(house.create_report(&MockDeviceInfoProvider))
+}
+
+#[axum::debug_handler]
+async fn get_estate_report(State(state): State<AppState>) -> String {
+    let estate = state.estate.read().await;
+    estate.create_report(&MockDeviceInfoProvider)
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{
+        body::{to_bytes, Body},
+        http::{Method, Request},
+    };
+    use serde_json::Value;
+    use smart_home::room::RoomDevice;
+    use tower::ServiceExt;
+
+    use super::*;
+
+    async fn send(router: &Router, method: Method, uri: &str, body: Option<Value>) -> Response {
+        let request = Request::builder().method(method).uri(uri);
+        let request = match body {
+            Some(body) => request
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        };
+        router.clone().oneshot(request.unwrap()).await.unwrap()
+    }
+
+    async fn json(response: Response) -> Value {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_houses() {
+        let router = create_router();
+
+        let response = send(
+            &router,
+            Method::POST,
+            "/houses",
+            Some(json!({ "name": "Country House" })),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = send(
+            &router,
+            Method::POST,
+            "/houses/Country%20House/rooms",
+            Some(json!({ "name": "Veranda" })),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let response = send(&router, Method::GET, "/houses", None).await;
+        let mut houses = json(response).await.as_array().unwrap().clone();
+        houses.sort_by_key(|house| house["name"].as_str().unwrap().to_owned());
+        assert_eq!(
+            houses,
+            [
+                json!({ "name": "Country House", "rooms": ["Veranda"] }),
+                json!({ "name": DEFAULT_HOUSE, "rooms": [] }),
+            ]
+        );
+
+        let response = send(&router, Method::DELETE, "/houses/Country%20House", None).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = send(&router, Method::GET, "/houses/Country%20House/rooms", None).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_aliases_use_default_house() {
+        let router = create_router();
+
+        let response = send(
+            &router,
+            Method::POST,
+            "/rooms",
+            Some(json!({ "name": "Kitchen" })),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = send(&router, Method::GET, "/houses/Smart%20House/rooms", None).await;
+        assert_eq!(
+            json(response).await,
+            json!([{ "name": "Kitchen", "devices": [] }])
+        );
+
+        let response = send(&router, Method::DELETE, "/houses/Smart%20House", None).await;
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = send(&router, Method::GET, "/rooms", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_scenes() {
+        let mut estate = Estate::new("Test Estate");
+        estate.add_house(House::new(DEFAULT_HOUSE)).unwrap();
+        let estate = Arc::new(RwLock::new(estate));
+        let router = router(AppState {
+            estate: estate.clone(),
+        });
+        let kettle_on = || async {
+            let estate = estate.read().await;
+            let device = estate
+                .get_house(DEFAULT_HOUSE)
+                .and_then(|house| house.get_room("Kitchen"))
+                .and_then(|room| room.get_device("Kettle"));
+            match device {
+                Some(RoomDevice::SmartSocket(socket)) => socket.is_on(),
+                device => panic!("Expected a socket, got {device:?}"),
+            }
+        };
+
+        let response = send(
+            &router,
+            Method::POST,
+            "/rooms",
+            Some(json!({ "name": "Kitchen" })),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let kettle = json!({ "name": "Kettle", "description": "Kettle socket", "power": 2000 });
+        let response = send(
+            &router,
+            Method::POST,
+            "/rooms/Kitchen/devices",
+            Some(kettle),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let morning = json!({
+            "name": "morning",
+            "actions": [{ "room": "Kitchen", "device": "Kettle", "on": true }],
+        });
+        let response = send(&router, Method::POST, "/scenes", Some(morning.clone())).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = send(&router, Method::GET, "/scenes", None).await;
+        assert_eq!(json(response).await, json!([morning]));
+
+        let response = send(&router, Method::POST, "/scenes/morning/apply", None).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(kettle_on().await);
+
+        // The kettle is switched back when a later action fails.
+        let broken = json!({
+            "name": "broken",
+            "actions": [
+                { "room": "Kitchen", "device": "Kettle", "on": false },
+                { "room": "Kitchen", "device": "Toaster", "on": true },
+            ],
+        });
+        let response = send(&router, Method::POST, "/scenes", Some(broken)).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = send(&router, Method::POST, "/scenes/broken/apply", None).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert!(kettle_on().await);
+
+        let response = send(&router, Method::DELETE, "/scenes/broken", None).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = send(&router, Method::POST, "/scenes/broken/apply", None).await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
 }